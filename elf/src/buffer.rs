@@ -0,0 +1,118 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::ops::RangeBounds;
+use std::rc::Rc;
+use std::slice::SliceIndex;
+
+// The bytes of a file, shared by the Elf and the section and segment views
+// on it. Writing through any of them is seen by all of them.
+#[derive(Clone)]
+pub struct Buffer {
+    bin: Rc<RefCell<Vec<u8>>>
+}
+
+impl Buffer {
+    pub fn new(bin: Vec<u8>) -> Buffer {
+        Buffer {
+            bin: Rc::new(RefCell::new(bin))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.bin.borrow().len();
+    }
+
+    pub fn get<I: SliceIndex<[u8], Output = [u8]>>(&self, index: I) -> Option<Ref<'_, [u8]>> {
+        return Ref::filter_map(self.bin.borrow(), |bin| bin.get(index)).ok();
+    }
+
+    pub fn bytes(&self) -> Ref<'_, Vec<u8>> {
+        return self.bin.borrow();
+    }
+
+    pub fn bytes_mut(&self) -> RefMut<'_, Vec<u8>> {
+        return self.bin.borrow_mut();
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        return self.bin.borrow().clone();
+    }
+
+    // put new content in the buffer, the views on it stay attached
+    pub fn replace(&self, bin: Vec<u8>) {
+        *self.bin.borrow_mut() = bin;
+    }
+
+    pub fn resize(&self, len: usize, val: u8) {
+        self.bin.borrow_mut().resize(len, val);
+    }
+
+    pub fn extend_from_slice(&self, bin: &[u8]) {
+        self.bin.borrow_mut().extend_from_slice(bin);
+    }
+
+    pub fn splice<R: RangeBounds<usize>, I: IntoIterator<Item = u8>>(&self, range: R, bin: I) {
+        self.bin.borrow_mut().splice(range, bin);
+    }
+
+    // is this the same buffer as `other`
+    pub fn shares(&self, other: &Buffer) -> bool {
+        return Rc::ptr_eq(&self.bin, &other.bin);
+    }
+
+    pub fn view(&self, offset: u64, len: u64) -> View {
+        View {
+            buffer: self.clone(),
+            offset,
+            len
+        }
+    }
+}
+
+// A range of a buffer, the content of a section or segment. A view is
+// tied to offsets in the file, laying the file out again moves the content
+// under it, so views should be taken again after structural edits.
+#[derive(Clone)]
+pub struct View {
+    pub buffer: Buffer,
+    pub offset: u64,
+    pub len: u64
+}
+
+impl View {
+    // a view on bytes of its own, not shared with a file
+    pub fn detached(bin: Vec<u8>) -> View {
+        let len = bin.len() as u64;
+        return Buffer::new(bin).view(0, len);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.len as usize;
+    }
+
+    // the bytes in view, empty if the buffer no longer holds them
+    pub fn bytes(&self) -> Ref<'_, [u8]> {
+        let (start, end) = (self.offset as usize, (self.offset + self.len) as usize);
+        return Ref::map(self.buffer.bytes(), |bin| bin.get(start..end).unwrap_or(&[]));
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        return self.bytes().to_vec();
+    }
+
+    // overwrite bytes at `at`, the size of the view doesn't change
+    pub fn write(&self, at: u64, bin: &[u8]) -> crate::Result<()> {
+        if at + bin.len() as u64 > self.len {
+            return Err(crate::ParsingError::ParsingError);
+        }
+        let start = (self.offset + at) as usize;
+        let mut buffer = self.buffer.bytes_mut();
+        let target = buffer.get_mut(start..start + bin.len()).ok_or(crate::ParsingError::ParsingError)?;
+        target.copy_from_slice(bin);
+        return Ok(());
+    }
+
+    // a part of this view
+    pub fn sub(&self, at: u64, len: u64) -> View {
+        return self.buffer.view(self.offset + at, len.min(self.len.saturating_sub(at)));
+    }
+}
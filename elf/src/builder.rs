@@ -0,0 +1,393 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_header;
+use crate::Elf_class;
+use crate::Elf_arch;
+use crate::Elf_type;
+use crate::buffer::Buffer;
+use crate::phdr::ProgramHeader;
+use crate::phdr::Phdr_type;
+use crate::phdr::Phdr_flags;
+use crate::shdr::SectionHeader;
+use crate::shdr::Shdr_type;
+use crate::shdr::Shdr_flags;
+use crate::symbol;
+use crate::symbol::Symbol;
+use crate::symbol::Sym_type;
+use crate::symbol::Sym_bind;
+use crate::strtab::StringTable;
+
+// Put a file together from nothing, without a toolchain. A linked file
+// (EXEC or DYN) gets a PT_LOAD for every segment and one more for every
+// SHF_ALLOC section, those are mapped on the pages after the segments. A
+// relocatable object (REL) has no segments, its sections are only laid out
+// in the file. The ELF and program headers themselves aren't mapped.
+//
+//     let mut b = ElfBuilder::new(Elf_class::ELF64, Elf_arch::AMD64, Elf_type::EXEC);
+//     b.section(".text", Shdr_type::PROGBITS, ALLOC | EXECINSTR, &code)
+//      .symbol("_start", ".text", 0, code.len() as u64, Sym_type::FUNC, Sym_bind::GLOBAL)
+//      .entry_symbol("_start");
+//     b.build()?.write_file("a.out")?;
+
+const PAGE: u64 = 0x1000;
+// where mapping starts when there are no segments, as for static executables
+const BASE: u64 = 0x400000;
+
+struct BuilderSegment {
+    vaddr: u64,
+    flags: u32,
+    content: Vec<u8>,
+    memsz: u64,
+    // the section mapped by this segment
+    section: Option<usize>
+}
+
+struct BuilderSection {
+    name: String,
+    sh_type: Shdr_type,
+    flags: u64,
+    content: Vec<u8>
+}
+
+struct BuilderSymbol {
+    name: String,
+    section: String,
+    value: u64,
+    size: u64,
+    sym_type: Sym_type,
+    bind: Sym_bind
+}
+
+pub struct ElfBuilder {
+    class: Elf_class,
+    arch: Elf_arch,
+    e_type: Elf_type,
+    entry: u64,
+    entry_symbol: Option<String>,
+    segments: Vec<BuilderSegment>,
+    sections: Vec<BuilderSection>,
+    symbols: Vec<BuilderSymbol>
+}
+
+fn round(v: u64, align: u64) -> u64 {
+    return (v + align - 1) / align * align;
+}
+
+impl ElfBuilder {
+    pub fn new(class: Elf_class, arch: Elf_arch, e_type: Elf_type) -> ElfBuilder {
+        ElfBuilder {
+            class,
+            arch,
+            e_type,
+            entry: 0,
+            entry_symbol: None,
+            segments: vec![],
+            sections: vec![],
+            symbols: vec![]
+        }
+    }
+
+    pub fn entry(&mut self, addr: u64) -> &mut ElfBuilder {
+        self.entry = addr;
+        return self;
+    }
+
+    // start at a symbol, for code in a section whose address isn't known yet
+    pub fn entry_symbol(&mut self, name: &str) -> &mut ElfBuilder {
+        self.entry_symbol = Some(String::from(name));
+        return self;
+    }
+
+    // a load segment mapping `content` at `vaddr`, `flags` are Phdr_flags
+    pub fn segment(&mut self, vaddr: u64, flags: u32, content: &[u8]) -> &mut ElfBuilder {
+        self.segments.push(BuilderSegment {
+            vaddr,
+            flags,
+            content: content.to_vec(),
+            memsz: content.len() as u64,
+            section: None
+        });
+        return self;
+    }
+
+    // A section, in the order of the section header table after the null
+    // section. A NOBITS section takes its size from `content`.
+    pub fn section(&mut self, name: &str, sh_type: Shdr_type, flags: u64, content: &[u8]) -> &mut ElfBuilder {
+        self.sections.push(BuilderSection {
+            name: String::from(name),
+            sh_type,
+            flags,
+            content: content.to_vec()
+        });
+        return self;
+    }
+
+    // A symbol `value` bytes into a section, an empty section name makes it
+    // undefined. In a linked file the value becomes an address.
+    pub fn symbol(&mut self, name: &str, section: &str, value: u64, size: u64, sym_type: Sym_type, bind: Sym_bind) -> &mut ElfBuilder {
+        self.symbols.push(BuilderSymbol {
+            name: String::from(name),
+            section: String::from(section),
+            value,
+            size,
+            sym_type,
+            bind
+        });
+        return self;
+    }
+
+    pub fn build(&self) -> Result<Elf> {
+        let linked = if let Elf_type::REL = self.e_type { false } else { true };
+        if !linked && !self.segments.is_empty() {
+            return Err(ParsingError::Refused(String::from("a relocatable object has no segments")));
+        }
+
+        // the segments, and one for each allocated section after them
+        let mut loads: Vec<BuilderSegment> = self.segments.iter().map(|s| BuilderSegment {
+            vaddr: s.vaddr,
+            flags: s.flags,
+            content: s.content.clone(),
+            memsz: s.memsz,
+            section: None
+        }).collect();
+        let mut next = loads.iter().map(|s| round(s.vaddr + s.memsz, PAGE)).max().unwrap_or(BASE);
+
+        for (i, section) in self.sections.iter().enumerate() {
+            if !linked || section.flags & Shdr_flags::ALLOC as u64 == 0 {
+                continue;
+            }
+            let mut flags = Phdr_flags::R as u32;
+            if section.flags & Shdr_flags::WRITE as u64 != 0 {
+                flags |= Phdr_flags::W as u32;
+            }
+            if section.flags & Shdr_flags::EXECINSTR as u64 != 0 {
+                flags |= Phdr_flags::X as u32;
+            }
+            let nobits = section.sh_type == Shdr_type::NOBITS;
+            loads.push(BuilderSegment {
+                vaddr: next,
+                flags,
+                content: if nobits { vec![] } else { section.content.clone() },
+                memsz: section.content.len() as u64,
+                section: Some(i)
+            });
+            next = round(next + section.content.len().max(1) as u64, PAGE);
+        }
+
+        loads.sort_by_key(|s| s.vaddr);
+        if let Elf_class::ELF32 = self.class {
+            if let Some(load) = loads.iter().find(|s| s.vaddr + s.memsz > u32::MAX as u64) {
+                return Err(ParsingError::Refused(format!("the segment at {:#x} doesn't fit in a 32-bit address space", load.vaddr)));
+            }
+        }
+        for pair in loads.windows(2) {
+            if round(pair[0].vaddr + pair[0].memsz, PAGE) > pair[1].vaddr / PAGE * PAGE {
+                return Err(ParsingError::Refused(format!("the segments at {:#x} and {:#x} share a page", pair[0].vaddr, pair[1].vaddr)));
+            }
+        }
+
+        // the headers, then the content of the segments
+        let mut header = Elf_header::new(self.class, self.arch, self.e_type, self.entry);
+        let mut raw = vec![0; header.size as usize + loads.len() * header.phdr_size as usize];
+        let mut phdrs = vec![];
+        let mut mapped = vec![None; self.sections.len()];
+        for load in &loads {
+            // the offset has to be the address modulo the page size
+            let mut offset = raw.len() as u64 / PAGE * PAGE + load.vaddr % PAGE;
+            if offset < raw.len() as u64 {
+                offset += PAGE;
+            }
+            raw.resize(offset as usize, 0);
+            raw.extend_from_slice(&load.content);
+            phdrs.push(ProgramHeader::new(Phdr_type::LOAD, load.flags, offset, load.vaddr, load.content.len() as u64, load.memsz, PAGE));
+            if let Some(i) = load.section {
+                mapped[i] = Some((load.vaddr, offset));
+            }
+        }
+
+        // the sections, the ones that aren't mapped go after the segments
+        let mut names = StringTable::new();
+        let mut shdrs = vec![SectionHeader::new("", Shdr_type::NULL, 0, 0, 0, 0, 0)];
+        for (i, section) in self.sections.iter().enumerate() {
+            // notes are read in 4 byte words unless they are aligned to 8
+            let align = if section.sh_type == Shdr_type::NOTE { 4 } else { 8 };
+            let (addr, offset) = match mapped[i] {
+                Some(place) => place,
+                None => {
+                    let offset = round(raw.len() as u64, align);
+                    raw.resize(offset as usize, 0);
+                    if section.sh_type != Shdr_type::NOBITS {
+                        raw.extend_from_slice(&section.content);
+                    }
+                    (0, offset)
+                }
+            };
+            let mut shdr = SectionHeader::new(&section.name, section.sh_type, section.flags, addr, offset, section.content.len() as u64, align);
+            shdr.shstrndx_offset = names.add(&section.name);
+            shdrs.push(shdr);
+        }
+
+        // the symbol table, local symbols first
+        let mut entry = self.entry;
+        if !self.symbols.is_empty() {
+            let mut strtab = StringTable::new();
+            let mut syms = vec![Symbol { name: String::new(), name_offset: 0, value: 0, size: 0, info: 0, other: 0, shndx: 0 }];
+
+            let mut ordered: Vec<&BuilderSymbol> = self.symbols.iter().collect();
+            ordered.sort_by_key(|s| if s.bind == Sym_bind::LOCAL { 0 } else { 1 });
+            for sym in ordered {
+                let shndx = match sym.section.as_str() {
+                    "" => 0,
+                    name => self.sections.iter().position(|s| s.name == name).map(|i| i + 1)
+                        .ok_or(ParsingError::Refused(format!("symbol {} is in section {}, which doesn't exist", sym.name, name)))?
+                };
+                let value = if linked && shndx != 0 { shdrs[shndx].addr + sym.value } else { sym.value };
+                syms.push(Symbol {
+                    name: sym.name.clone(),
+                    name_offset: strtab.add(&sym.name),
+                    value,
+                    size: sym.size,
+                    info: (sym.bind as u8) << 4 | sym.sym_type as u8,
+                    other: 0,
+                    shndx: shndx as u16
+                });
+            }
+
+            if let Some(name) = &self.entry_symbol {
+                let sym = syms.iter().find(|s| s.name == *name && s.shndx != 0 && s.shndx < symbol::SHN_LORESERVE);
+                entry = sym.ok_or(ParsingError::Refused(format!("the entry symbol {} isn't defined", name)))?.value;
+            }
+
+            let locals = syms.iter().take_while(|s| s.bind() == Sym_bind::LOCAL).count();
+            let mut bin = vec![];
+            for sym in &syms {
+                bin.extend(sym.to_le(self.class));
+            }
+
+            let offset = round(raw.len() as u64, 8);
+            raw.resize(offset as usize, 0);
+            raw.extend_from_slice(&bin);
+            let mut shdr = SectionHeader::new(".symtab", Shdr_type::SYMTAB, 0, 0, offset, bin.len() as u64, 8);
+            shdr.shstrndx_offset = names.add(".symtab");
+            shdr.link = shdrs.len() as u32 + 1;
+            shdr.info = locals as u32;
+            shdr.entsize = symbol::entry_size(self.class) as u64;
+            shdrs.push(shdr);
+
+            let bin = strtab.to_le();
+            let mut shdr = SectionHeader::new(".strtab", Shdr_type::STRTAB, 0, 0, raw.len() as u64, bin.len() as u64, 1);
+            shdr.shstrndx_offset = names.add(".strtab");
+            raw.extend_from_slice(&bin);
+            shdrs.push(shdr);
+        } else if let Some(name) = &self.entry_symbol {
+            return Err(ParsingError::Refused(format!("the entry symbol {} isn't defined", name)));
+        }
+
+        // the section name table and the section header table close the file
+        let shstrndx = shdrs.len();
+        let mut shdr = SectionHeader::new(".shstrtab", Shdr_type::STRTAB, 0, 0, raw.len() as u64, 0, 1);
+        shdr.shstrndx_offset = names.add(".shstrtab");
+        let bin = names.to_le();
+        shdr.size = bin.len() as u64;
+        raw.extend_from_slice(&bin);
+        shdrs.push(shdr);
+
+        let shdr_offset = round(raw.len() as u64, 8);
+        raw.resize((shdr_offset + shdrs.len() as u64 * header.shdr_size as u64) as usize, 0);
+
+        header.e_entry = entry;
+        header.phdr_offset = if phdrs.is_empty() { 0 } else { header.size as u64 };
+        header.phdr_num = phdrs.len() as u16;
+        header.shdr_offset = shdr_offset;
+        header.shdr_num = shdrs.len() as u16;
+        header.shstrndx = shstrndx as u16;
+
+        return Ok(Elf {
+            header,
+            phdrs,
+            shdrs,
+            raw: Buffer::new(raw),
+            debug: None,
+            write_compression: vec![]
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::ElfBuilder;
+    use crate::{Elf, Elf_class, Elf_arch, Elf_type, ParsingError};
+    use crate::phdr::Phdr_type;
+    use crate::shdr::{Shdr_type, Shdr_flags};
+    use crate::symbol::{Sym_type, Sym_bind};
+
+    // mov eax, 1; mov ebx, 42; int 0x80
+    const EXIT: [u8; 12] = [0xb8, 1, 0, 0, 0, 0xbb, 42, 0, 0, 0, 0xcd, 0x80];
+
+    fn build(class: Elf_class, e_type: Elf_type) -> Elf {
+        let mut b = ElfBuilder::new(class, Elf_arch::X86, e_type);
+        b.section(".text", Shdr_type::PROGBITS, Shdr_flags::ALLOC as u64 | Shdr_flags::EXECINSTR as u64, &EXIT)
+         .section(".data", Shdr_type::PROGBITS, Shdr_flags::ALLOC as u64 | Shdr_flags::WRITE as u64, &[1, 2, 3, 4])
+         .section(".bss", Shdr_type::NOBITS, Shdr_flags::ALLOC as u64 | Shdr_flags::WRITE as u64, &[0; 0x2000])
+         .symbol("counter", ".data", 0, 4, Sym_type::OBJECT, Sym_bind::LOCAL)
+         .symbol("_start", ".text", 0, EXIT.len() as u64, Sym_type::FUNC, Sym_bind::GLOBAL);
+        if let Elf_type::EXEC = e_type {
+            b.entry_symbol("_start");
+        }
+        let bin = b.build().unwrap().to_le();
+        crate::verify::verify(&bin).unwrap();
+        return Elf::parse(bin).unwrap();
+    }
+
+    fn executable(class: Elf_class, sizes: (u16, u16, u16)) {
+        let elf = build(class, Elf_type::EXEC);
+        assert_eq!((elf.header.size, elf.header.phdr_size, elf.header.shdr_size), sizes);
+        assert_eq!(elf.header.phdr_offset, sizes.0 as u64);
+
+        let text = elf.section_header(".text").unwrap();
+        assert_eq!(elf.header.e_entry, text.addr);
+        assert_eq!(elf.read_vaddr(text.addr, EXIT.len() as u64).unwrap().to_vec(), EXIT.to_vec());
+
+        let loads: Vec<_> = elf.phdrs.iter().filter(|p| if let Phdr_type::LOAD = p.p_type { true } else { false }).collect();
+        assert_eq!(loads.len(), 3);
+        let bss = elf.section_header(".bss").unwrap();
+        assert!(loads.iter().any(|p| p.vaddr == bss.addr && p.filesz == 0 && p.memsz == 0x2000));
+
+        let syms = elf.symbols().unwrap();
+        assert_eq!(elf.section_header(".symtab").unwrap().info, 2);
+        assert_eq!(syms.iter().find(|s| s.name == "_start").unwrap().value, text.addr);
+    }
+
+    #[test]
+    fn build_executable() {
+        executable(Elf_class::ELF64, (0x40, 0x38, 0x40));
+        executable(Elf_class::ELF32, (0x34, 0x20, 0x28));
+    }
+
+    #[test]
+    fn build_object() {
+        let elf = build(Elf_class::ELF64, Elf_type::REL);
+        assert!(elf.phdrs.is_empty());
+        assert_eq!(elf.header.phdr_offset, 0);
+        assert_eq!(elf.section_header(".text").unwrap().addr, 0);
+        assert_eq!(elf.section(".data").unwrap().content(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn build_refused() {
+        let mut b = ElfBuilder::new(Elf_class::ELF64, Elf_arch::AMD64, Elf_type::EXEC);
+        b.segment(0x401000, 5, &EXIT).segment(0x401800, 6, &[0; 16]);
+        match b.build() {
+            Err(ParsingError::Refused(_)) => {},
+            _ => panic!("segments sharing a page were built")
+        }
+
+        let mut b = ElfBuilder::new(Elf_class::ELF32, Elf_arch::X86, Elf_type::EXEC);
+        b.segment(0xffff_f000, 5, &[0; 0x2000]);
+        match b.build() {
+            Err(ParsingError::Refused(_)) => {},
+            _ => panic!("a segment above 4GB was built into an ELF32 file")
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_class;
+use crate::shdr::{SectionHeader, Shdr_flags};
+
+use byteorder::*;
+use std::io::Read;
+
+// How the content of a section is compressed in the file
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Compression {
+    NONE,
+    // SHF_COMPRESSED with ELFCOMPRESS_ZLIB
+    ZLIB,
+    // SHF_COMPRESSED with ELFCOMPRESS_ZSTD
+    ZSTD,
+    // the legacy .zdebug_* sections: "ZLIB", the size as big endian u64 and a zlib stream
+    GNU_ZLIB
+}
+
+const ELFCOMPRESS_ZLIB: u32 = 1;
+const ELFCOMPRESS_ZSTD: u32 = 2;
+
+// the Elf_Chdr in front of the content of a SHF_COMPRESSED section
+pub struct CompressionHeader {
+    pub ch_type: u32,
+    pub ch_size: u64,
+    pub ch_addralign: u64
+}
+
+pub fn chdr_size(class: Elf_class) -> usize {
+    return match class {
+        Elf_class::ELF64 => 0x18,
+        Elf_class::ELF32 => 0xC
+    }
+}
+
+impl CompressionHeader {
+    pub fn parse(bin: &[u8], class: Elf_class) -> Result<CompressionHeader> {
+        let bin = bin.get(..chdr_size(class)).ok_or(ParsingError::ParsingError)?;
+
+        return match class {
+            Elf_class::ELF64 => Ok(CompressionHeader {
+                ch_type: LittleEndian::read_u32(&bin[0x0..0x4]),
+                ch_size: LittleEndian::read_u64(&bin[0x8..0x10]),
+                ch_addralign: LittleEndian::read_u64(&bin[0x10..0x18]),
+            }),
+            Elf_class::ELF32 => Ok(CompressionHeader {
+                ch_type: LittleEndian::read_u32(&bin[0x0..0x4]),
+                ch_size: LittleEndian::read_u32(&bin[0x4..0x8]) as u64,
+                ch_addralign: LittleEndian::read_u32(&bin[0x8..0xC]) as u64,
+            })
+        }
+    }
+
+    pub fn to_le(&self, class: Elf_class) -> Vec<u8> {
+        let mut bin = vec![];
+
+        match class {
+            Elf_class::ELF64 => {
+                bin.extend_from_slice(&self.ch_type.to_le_bytes());
+                bin.extend_from_slice(&0u32.to_le_bytes());
+                bin.extend_from_slice(&self.ch_size.to_le_bytes());
+                bin.extend_from_slice(&self.ch_addralign.to_le_bytes());
+            },
+            Elf_class::ELF32 => {
+                bin.extend_from_slice(&self.ch_type.to_le_bytes());
+                bin.extend_from_slice(&(self.ch_size as u32).to_le_bytes());
+                bin.extend_from_slice(&(self.ch_addralign as u32).to_le_bytes());
+            }
+        }
+
+        return bin;
+    }
+}
+
+// how a section with this header and raw content is compressed
+pub fn compression(hdr: &SectionHeader, bin: &[u8]) -> Compression {
+    if hdr.flags & Shdr_flags::COMPRESSED as u64 != 0 {
+        return match bin.get(0..4).map(|t| LittleEndian::read_u32(t)) {
+            Some(ELFCOMPRESS_ZLIB) => Compression::ZLIB,
+            Some(ELFCOMPRESS_ZSTD) => Compression::ZSTD,
+            _ => Compression::NONE
+        }
+    }
+
+    if hdr.name.starts_with(".zdebug") && bin.starts_with(b"ZLIB") && bin.len() >= 12 {
+        return Compression::GNU_ZLIB;
+    }
+
+    return Compression::NONE;
+}
+
+fn inflate(bin: &[u8], method: Compression) -> Result<Vec<u8>> {
+    return match method {
+        Compression::ZLIB | Compression::GNU_ZLIB => {
+            miniz_oxide::inflate::decompress_to_vec_zlib(bin).map_err(|_| ParsingError::ParsingError)
+        },
+        Compression::ZSTD => {
+            let mut out = vec![];
+            let mut decoder = ruzstd::decoding::StreamingDecoder::new(bin).map_err(|_| ParsingError::ParsingError)?;
+            decoder.read_to_end(&mut out).map_err(|_| ParsingError::ParsingError)?;
+            Ok(out)
+        },
+        Compression::NONE => Ok(bin.to_vec())
+    }
+}
+
+fn deflate(bin: &[u8], method: Compression) -> Vec<u8> {
+    return match method {
+        Compression::ZLIB | Compression::GNU_ZLIB => miniz_oxide::deflate::compress_to_vec_zlib(bin, 6),
+        Compression::ZSTD => ruzstd::encoding::compress_to_vec(bin, ruzstd::encoding::CompressionLevel::Fastest),
+        Compression::NONE => bin.to_vec()
+    }
+}
+
+// Decompress the raw content of a section. Returns the content and the
+// alignment it has when uncompressed.
+pub fn decompress(hdr: &SectionHeader, bin: &[u8], class: Elf_class) -> Result<(Vec<u8>, u64)> {
+    let method = compression(hdr, bin);
+
+    return match method {
+        Compression::ZLIB | Compression::ZSTD => {
+            let chdr = CompressionHeader::parse(bin, class)?;
+            let content = inflate(&bin[chdr_size(class)..], method)?;
+            if content.len() as u64 != chdr.ch_size {
+                return Err(ParsingError::Refused(format!("{} decompresses to {:#x} bytes, its header says {:#x}", hdr.name, content.len(), chdr.ch_size)));
+            }
+            Ok((content, chdr.ch_addralign))
+        },
+        Compression::GNU_ZLIB => {
+            let size = BigEndian::read_u64(&bin[0x4..0xC]);
+            let content = inflate(&bin[0xC..], method)?;
+            if content.len() as u64 != size {
+                return Err(ParsingError::Refused(format!("{} decompresses to {:#x} bytes, its header says {:#x}", hdr.name, content.len(), size)));
+            }
+            Ok((content, hdr.addralign))
+        },
+        Compression::NONE => Ok((bin.to_vec(), hdr.addralign))
+    }
+}
+
+// Compress section content, `addralign` is the alignment of the uncompressed content
+pub fn compress(bin: &[u8], addralign: u64, method: Compression, class: Elf_class) -> Vec<u8> {
+    let mut out = vec![];
+
+    match method {
+        Compression::ZLIB | Compression::ZSTD => {
+            let chdr = CompressionHeader {
+                ch_type: if method == Compression::ZLIB { ELFCOMPRESS_ZLIB } else { ELFCOMPRESS_ZSTD },
+                ch_size: bin.len() as u64,
+                ch_addralign: addralign
+            };
+            out.extend(chdr.to_le(class));
+        },
+        Compression::GNU_ZLIB => {
+            out.extend_from_slice(b"ZLIB");
+            out.extend_from_slice(&(bin.len() as u64).to_be_bytes());
+        },
+        Compression::NONE => {}
+    }
+
+    out.extend(deflate(bin, method));
+    return out;
+}
+
+impl Elf {
+    // The index of a section that can be stored with `method`. Sections that
+    // are loaded into memory can't be compressed, a .zdebug section keeps
+    // its name so only the legacy format is an option there, and the legacy
+    // format needs such a name.
+    fn check_compression(&self, name: &str, method: Compression) -> Result<usize> {
+        let index = self.section_index(name).ok_or(ParsingError::Refused(format!("there is no section {}", name)))?;
+        let hdr = &self.shdrs[index];
+        if method == Compression::NONE {
+            return Ok(index);
+        }
+
+        if hdr.flags & Shdr_flags::ALLOC as u64 != 0 {
+            return Err(ParsingError::Refused(format!("{} is loaded into memory, it can't be compressed", name)));
+        }
+        let raw = self.raw.get(hdr.offset as usize..(hdr.offset + hdr.size) as usize).ok_or(ParsingError::ParsingError)?.to_vec();
+        if compression(hdr, &raw) == Compression::GNU_ZLIB && method != Compression::GNU_ZLIB {
+            return Err(ParsingError::Refused(format!("{} is a legacy .zdebug section, it can only be compressed with GNU_ZLIB", name)));
+        }
+        if method == Compression::GNU_ZLIB && !hdr.name.starts_with(".zdebug") {
+            return Err(ParsingError::Refused(format!("GNU_ZLIB is only used by .zdebug sections, {} isn't one", name)));
+        }
+        return Ok(index);
+    }
+
+    // Compress a section in the file, the layout of the file is updated. Only
+    // sections that are not loaded into memory can be compressed.
+    pub fn compress_section(&mut self, name: &str, method: Compression) -> Result<()> {
+        let index = self.check_compression(name, method)?;
+        let hdr = self.shdrs[index].clone();
+        let class = self.header.e_class;
+
+        let raw = self.raw.get(hdr.offset as usize..(hdr.offset + hdr.size) as usize).ok_or(ParsingError::ParsingError)?.to_vec();
+        let (content, addralign) = decompress(&hdr, &raw, class)?;
+        let packed = compress(&content, addralign, method, class);
+
+        match method {
+            Compression::ZLIB | Compression::ZSTD => {
+                self.shdrs[index].flags |= Shdr_flags::COMPRESSED as u64;
+                self.shdrs[index].addralign = match class {
+                    Elf_class::ELF64 => 8,
+                    Elf_class::ELF32 => 4
+                };
+            },
+            _ => {
+                self.shdrs[index].flags &= !(Shdr_flags::COMPRESSED as u64);
+                self.shdrs[index].addralign = addralign;
+            }
+        }
+
+        return self.set_section_content(index, packed);
+    }
+
+    // Store a compressed section uncompressed. A legacy .zdebug section is
+    // renamed to .debug: the "z" in the string table is overwritten with the
+    // "." and the name starts one character further.
+    pub fn decompress_section(&mut self, name: &str) -> Result<()> {
+        let index = self.section_index(name).ok_or(ParsingError::Refused(format!("there is no section {}", name)))?;
+        let hdr = self.shdrs[index].clone();
+
+        let raw = self.raw.get(hdr.offset as usize..(hdr.offset + hdr.size) as usize).ok_or(ParsingError::ParsingError)?.to_vec();
+        let method = compression(&hdr, &raw);
+        if method == Compression::NONE {
+            return Ok(());
+        }
+
+        let (content, addralign) = decompress(&hdr, &raw, self.header.e_class)?;
+
+        if method == Compression::GNU_ZLIB {
+            let strtab = self.shdrs.get(self.header.shstrndx as usize).ok_or(ParsingError::ParsingError)?.offset;
+            let z = (strtab + hdr.shstrndx_offset as u64 + 1) as usize;
+            *self.raw.bytes_mut().get_mut(z).ok_or(ParsingError::ParsingError)? = b'.';
+            self.shdrs[index].shstrndx_offset += 1;
+            self.shdrs[index].name = format!(".{}", &hdr.name[2..]);
+        }
+        self.shdrs[index].flags &= !(Shdr_flags::COMPRESSED as u64);
+        self.shdrs[index].addralign = addralign;
+
+        return self.set_section_content(index, content);
+    }
+
+    pub fn compress_debug_sections(&mut self, method: Compression) -> Result<()> {
+        let names: Vec<String> = self.shdrs.iter().filter(|s| s.name.starts_with(".debug_") && s.size > 0).map(|s| s.name.clone()).collect();
+
+        for name in names {
+            self.compress_section(&name, method)?;
+        }
+        return Ok(());
+    }
+
+    // Store a section compressed with `method`, or uncompressed with NONE,
+    // when the file is written. The sections in the Elf stay as they are,
+    // write_to applies the compression to a copy.
+    pub fn compress_on_write(&mut self, name: &str, method: Compression) -> Result<()> {
+        self.check_compression(name, method)?;
+        self.write_compression.retain(|(n, _)| n != name);
+        self.write_compression.push((String::from(name), method));
+        return Ok(());
+    }
+
+    pub fn compress_debug_sections_on_write(&mut self, method: Compression) -> Result<()> {
+        let names: Vec<String> = self.shdrs.iter().filter(|s| s.name.starts_with(".debug_") && s.size > 0).map(|s| s.name.clone()).collect();
+
+        for name in names {
+            self.compress_on_write(&name, method)?;
+        }
+        return Ok(());
+    }
+
+    // compress the sections picked with compress_on_write
+    pub fn apply_write_compression(&mut self) -> Result<()> {
+        for (name, method) in std::mem::replace(&mut self.write_compression, vec![]) {
+            match method {
+                Compression::NONE => self.decompress_section(&name)?,
+                _ => self.compress_section(&name, method)?
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compress::Compression;
+    use crate::shdr::Shdr_flags;
+    use crate::ParsingError;
+
+    #[test]
+    fn compress_on_write() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let comment = elf.section(".comment").unwrap().content();
+        elf.compress_on_write(".comment", Compression::ZLIB).unwrap();
+        // the Elf itself keeps the section as it was
+        assert_eq!(elf.section(".comment").unwrap().compression, Compression::NONE);
+
+        let mut elf = crate::Elf::parse(elf.to_le()).unwrap();
+        let section = elf.section(".comment").unwrap();
+        assert_eq!(section.compression, Compression::ZLIB);
+        assert!(section.hdr.flags & Shdr_flags::COMPRESSED as u64 != 0);
+        assert_eq!(section.hdr.addralign, 8);
+        assert_eq!(section.content(), comment);
+
+        elf.compress_on_write(".comment", Compression::NONE).unwrap();
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        let section = elf.section(".comment").unwrap();
+        assert_eq!(section.compression, Compression::NONE);
+        assert_eq!(section.hdr.addralign, 1);
+        assert_eq!(section.data.to_vec(), comment);
+    }
+
+    #[test]
+    fn compression_refused() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let refused = [(".text", Compression::ZSTD), (".missing", Compression::ZLIB), (".comment", Compression::GNU_ZLIB)];
+        for (name, method) in refused {
+            match elf.compress_on_write(name, method) {
+                Err(ParsingError::Refused(_)) => {},
+                _ => panic!("{} was compressed", name)
+            }
+        }
+        assert!(elf.write_compression.is_empty());
+    }
+}
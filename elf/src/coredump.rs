@@ -0,0 +1,578 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_arch;
+use crate::Elf_class;
+use crate::Elf_type;
+use crate::note;
+use crate::phdr::Phdr_type;
+use crate::phdr::ProgramHeader;
+
+use byteorder::*;
+use std::collections::HashMap;
+
+// Core dumps (ET_CORE): the process state the kernel writes in the PT_NOTE
+// segment and the memory of the process in the PT_LOAD segments.
+
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_SIGINFO: u32 = 0x53494749;
+const NT_FILE: u32 = 0x46494c45;
+
+// auxiliary vector entries
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_BASE: u64 = 7;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_UID: u64 = 11;
+pub const AT_EUID: u64 = 12;
+pub const AT_GID: u64 = 13;
+pub const AT_EGID: u64 = 14;
+pub const AT_PLATFORM: u64 = 15;
+pub const AT_HWCAP: u64 = 16;
+pub const AT_CLKTCK: u64 = 17;
+pub const AT_SECURE: u64 = 23;
+pub const AT_RANDOM: u64 = 25;
+pub const AT_HWCAP2: u64 = 26;
+pub const AT_EXECFN: u64 = 31;
+pub const AT_SYSINFO_EHDR: u64 = 33;
+
+// offset of pr_reg in struct elf_prstatus, the same on x86-64 and AArch64
+const PR_REG: usize = 0x70;
+
+// user_regs_struct of x86-64, in the order of the kernel
+#[derive(Clone, Debug, Default)]
+pub struct Amd64Registers {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64
+}
+
+// user_pt_regs of AArch64
+#[derive(Clone, Debug, Default)]
+pub struct Aarch64Registers {
+    pub x: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub pstate: u64
+}
+
+#[derive(Clone, Debug)]
+pub enum Registers {
+    AMD64(Amd64Registers),
+    AARCH64(Aarch64Registers),
+    // pr_reg of the architectures we don't decode
+    OTHER(Vec<u8>)
+}
+
+impl Amd64Registers {
+    fn parse(bin: &[u8]) -> Option<Amd64Registers> {
+        let bin = bin.get(..27 * 8)?;
+        let r = |i: usize| LittleEndian::read_u64(&bin[i * 8..i * 8 + 8]);
+
+        Some(Amd64Registers {
+            r15: r(0),
+            r14: r(1),
+            r13: r(2),
+            r12: r(3),
+            rbp: r(4),
+            rbx: r(5),
+            r11: r(6),
+            r10: r(7),
+            r9: r(8),
+            r8: r(9),
+            rax: r(10),
+            rcx: r(11),
+            rdx: r(12),
+            rsi: r(13),
+            rdi: r(14),
+            orig_rax: r(15),
+            rip: r(16),
+            cs: r(17),
+            eflags: r(18),
+            rsp: r(19),
+            ss: r(20),
+            fs_base: r(21),
+            gs_base: r(22),
+            ds: r(23),
+            es: r(24),
+            fs: r(25),
+            gs: r(26)
+        })
+    }
+}
+
+impl Aarch64Registers {
+    fn parse(bin: &[u8]) -> Option<Aarch64Registers> {
+        let bin = bin.get(..34 * 8)?;
+        let r = |i: usize| LittleEndian::read_u64(&bin[i * 8..i * 8 + 8]);
+
+        let mut regs = Aarch64Registers::default();
+        for i in 0..31 {
+            regs.x[i] = r(i);
+        }
+        regs.sp = r(31);
+        regs.pc = r(32);
+        regs.pstate = r(33);
+        return Some(regs);
+    }
+}
+
+impl Registers {
+    pub fn pc(&self) -> Option<u64> {
+        return match self {
+            Registers::AMD64(regs) => Some(regs.rip),
+            Registers::AARCH64(regs) => Some(regs.pc),
+            Registers::OTHER(_) => None
+        }
+    }
+
+    pub fn sp(&self) -> Option<u64> {
+        return match self {
+            Registers::AMD64(regs) => Some(regs.rsp),
+            Registers::AARCH64(regs) => Some(regs.sp),
+            Registers::OTHER(_) => None
+        }
+    }
+
+    // The registers by their DWARF number, as `Elf::backtrace` takes them
+    pub fn dwarf(&self) -> HashMap<u16, u64> {
+        let mut map = HashMap::new();
+
+        match self {
+            Registers::AMD64(r) => {
+                let order = [r.rax, r.rdx, r.rcx, r.rbx, r.rsi, r.rdi, r.rbp, r.rsp,
+                             r.r8, r.r9, r.r10, r.r11, r.r12, r.r13, r.r14, r.r15, r.rip];
+                for (i, val) in order.iter().enumerate() {
+                    map.insert(i as u16, *val);
+                }
+            },
+            Registers::AARCH64(r) => {
+                for (i, val) in r.x.iter().enumerate() {
+                    map.insert(i as u16, *val);
+                }
+                map.insert(31, r.sp);
+            },
+            Registers::OTHER(_) => {}
+        }
+
+        return map;
+    }
+}
+
+// NT_PRSTATUS, one per thread
+#[derive(Clone, Debug)]
+pub struct Thread {
+    pub pid: u32,
+    pub ppid: u32,
+    pub pgrp: u32,
+    pub sid: u32,
+    // the signal the thread is stopped on
+    pub signal: u16,
+    pub sigpend: u64,
+    pub sighold: u64,
+    // user and system time in microseconds
+    pub utime: u64,
+    pub stime: u64,
+    pub registers: Registers
+}
+
+// NT_PRPSINFO
+#[derive(Clone, Debug)]
+pub struct ProcessInfo {
+    pub state: u8,
+    // R, S, D, T, Z...
+    pub sname: char,
+    pub zombie: bool,
+    pub nice: i8,
+    pub flags: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: u32,
+    pub ppid: u32,
+    pub pgrp: u32,
+    pub sid: u32,
+    // the executable name, truncated to 15 characters
+    pub fname: String,
+    // the start of the command line
+    pub psargs: String
+}
+
+// NT_SIGINFO, the signal that killed the process
+#[derive(Clone, Debug)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    // the faulting address of SIGSEGV, SIGBUS, SIGILL, SIGFPE and SIGTRAP
+    pub addr: Option<u64>
+}
+
+// an entry of NT_FILE, a file mapped in the process
+#[derive(Clone, Debug)]
+pub struct FileMapping {
+    pub start: u64,
+    pub end: u64,
+    // offset in the file, in bytes
+    pub offset: u64,
+    pub path: String
+}
+
+// The mappings of a file put together, for a shared library or the
+// executable. `base` is where the start of the file is mapped, the load
+// bias for position independent modules.
+#[derive(Clone, Debug)]
+pub struct Module {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    pub base: u64,
+    pub build_id: Option<String>
+}
+
+impl Module {
+    pub fn contains(&self, addr: u64) -> bool {
+        return addr >= self.start && addr < self.end;
+    }
+}
+
+pub struct Core {
+    pub threads: Vec<Thread>,
+    pub process: Option<ProcessInfo>,
+    pub siginfo: Option<SigInfo>,
+    pub auxv: Vec<(u64, u64)>,
+    pub files: Vec<FileMapping>,
+    pub modules: Vec<Module>
+}
+
+impl Core {
+    pub fn auxv_value(&self, a_type: u64) -> Option<u64> {
+        return self.auxv.iter().find(|(t, _)| *t == a_type).map(|(_, v)| *v);
+    }
+
+    pub fn module_at(&self, addr: u64) -> Option<&Module> {
+        return self.modules.iter().find(|m| m.contains(addr));
+    }
+
+    // the module holding the entry point
+    pub fn executable(&self) -> Option<&Module> {
+        return self.module_at(self.auxv_value(AT_ENTRY)?);
+    }
+}
+
+fn word_size(class: Elf_class) -> usize {
+    return match class {
+        Elf_class::ELF64 => 8,
+        Elf_class::ELF32 => 4
+    }
+}
+
+fn read_word(bin: &[u8], class: Elf_class) -> u64 {
+    return match class {
+        Elf_class::ELF64 => LittleEndian::read_u64(bin),
+        Elf_class::ELF32 => LittleEndian::read_u32(bin) as u64
+    }
+}
+
+fn cstring(bin: &[u8]) -> String {
+    let end = bin.iter().position(|b| *b == 0).unwrap_or(bin.len());
+    return String::from_utf8_lossy(&bin[..end]).into_owned();
+}
+
+fn parse_prstatus(desc: &[u8], arch: Elf_arch) -> Option<Thread> {
+    if desc.len() < PR_REG {
+        return None;
+    }
+    let time = |at: usize| LittleEndian::read_u64(&desc[at..at + 8]) * 1000000 + LittleEndian::read_u64(&desc[at + 8..at + 16]);
+
+    let registers = match arch {
+        Elf_arch::AMD64 => Registers::AMD64(Amd64Registers::parse(&desc[PR_REG..])?),
+        Elf_arch::AARCH64 => Registers::AARCH64(Aarch64Registers::parse(&desc[PR_REG..])?),
+        _ => Registers::OTHER(desc[PR_REG..].to_vec())
+    };
+
+    Some(Thread {
+        signal: LittleEndian::read_u16(&desc[0xC..0xE]),
+        sigpend: LittleEndian::read_u64(&desc[0x10..0x18]),
+        sighold: LittleEndian::read_u64(&desc[0x18..0x20]),
+        pid: LittleEndian::read_u32(&desc[0x20..0x24]),
+        ppid: LittleEndian::read_u32(&desc[0x24..0x28]),
+        pgrp: LittleEndian::read_u32(&desc[0x28..0x2C]),
+        sid: LittleEndian::read_u32(&desc[0x2C..0x30]),
+        utime: time(0x30),
+        stime: time(0x40),
+        registers
+    })
+}
+
+fn parse_prpsinfo(desc: &[u8]) -> Option<ProcessInfo> {
+    let desc = desc.get(..0x88)?;
+
+    Some(ProcessInfo {
+        state: desc[0],
+        sname: desc[1] as char,
+        zombie: desc[2] != 0,
+        nice: desc[3] as i8,
+        flags: LittleEndian::read_u64(&desc[0x8..0x10]),
+        uid: LittleEndian::read_u32(&desc[0x10..0x14]),
+        gid: LittleEndian::read_u32(&desc[0x14..0x18]),
+        pid: LittleEndian::read_u32(&desc[0x18..0x1C]),
+        ppid: LittleEndian::read_u32(&desc[0x1C..0x20]),
+        pgrp: LittleEndian::read_u32(&desc[0x20..0x24]),
+        sid: LittleEndian::read_u32(&desc[0x24..0x28]),
+        fname: cstring(&desc[0x28..0x38]),
+        psargs: cstring(&desc[0x38..0x88])
+    })
+}
+
+fn parse_siginfo(desc: &[u8], class: Elf_class) -> Option<SigInfo> {
+    let signo = LittleEndian::read_i32(desc.get(0x0..0x4)?);
+    // the union is aligned to the word size
+    let union = word_size(class);
+    let addr = match signo {
+        4 | 5 | 7 | 8 | 11 => Some(read_word(desc.get(union + 8..union + 8 + word_size(class))?, class)),
+        _ => None
+    };
+
+    Some(SigInfo {
+        signo,
+        errno: LittleEndian::read_i32(desc.get(0x4..0x8)?),
+        code: LittleEndian::read_i32(desc.get(0x8..0xC)?),
+        addr
+    })
+}
+
+fn parse_auxv(desc: &[u8], class: Elf_class) -> Vec<(u64, u64)> {
+    let w = word_size(class);
+    let mut auxv = vec![];
+
+    for pair in desc.chunks_exact(w * 2) {
+        let a_type = read_word(&pair[..w], class);
+        if a_type == AT_NULL {
+            break;
+        }
+        auxv.push((a_type, read_word(&pair[w..], class)));
+    }
+
+    return auxv;
+}
+
+// NT_FILE: a count and the page size, the (start, end, page offset) triples
+// and then the paths
+fn parse_files(desc: &[u8], class: Elf_class) -> Option<Vec<FileMapping>> {
+    let w = word_size(class);
+    let count = read_word(desc.get(0..w)?, class) as usize;
+    let page_size = read_word(desc.get(w..2 * w)?, class);
+
+    let mut paths = desc.get(2 * w + count.checked_mul(3 * w)?..)?.split(|b| *b == 0);
+    let mut files = vec![];
+
+    for i in 0..count {
+        let entry = desc.get(2 * w + i * 3 * w..2 * w + (i + 1) * 3 * w)?;
+        files.push(FileMapping {
+            start: read_word(&entry[..w], class),
+            end: read_word(&entry[w..2 * w], class),
+            offset: read_word(&entry[2 * w..], class) * page_size,
+            path: String::from_utf8_lossy(paths.next()?).into_owned()
+        });
+    }
+
+    return Some(files);
+}
+
+// group the mappings of every file into a module
+fn modules(files: &Vec<FileMapping>) -> Vec<Module> {
+    let mut modules: Vec<Module> = vec![];
+
+    for file in files {
+        match modules.iter_mut().find(|m| m.path == file.path) {
+            Some(module) => {
+                module.start = module.start.min(file.start);
+                module.end = module.end.max(file.end);
+                if file.offset == 0 {
+                    module.base = module.base.min(file.start);
+                }
+            },
+            None => modules.push(Module {
+                path: file.path.clone(),
+                start: file.start,
+                end: file.end,
+                base: file.start.wrapping_sub(file.offset),
+                build_id: None
+            })
+        }
+    }
+
+    modules.sort_by_key(|m| m.start);
+    return modules;
+}
+
+// A view of the address space of the process over the PT_LOAD segments.
+// Only the file backed part of a segment is readable, in core dumps the
+// rest was not dumped.
+pub struct Memory<'a> {
+    elf: &'a Elf
+}
+
+impl<'a> Memory<'a> {
+    // the loaded ranges as (start, end, flags), the part in the file only
+    pub fn regions(&self) -> Vec<(u64, u64, u32)> {
+        let mut regions = vec![];
+        for phdr in &self.elf.phdrs {
+            if let Phdr_type::LOAD = phdr.p_type {
+                if phdr.filesz > 0 {
+                    regions.push((phdr.vaddr, phdr.vaddr + phdr.filesz, phdr.flags));
+                }
+            }
+        }
+        return regions;
+    }
+
+    // `size` bytes at `addr`, the range can span adjacent segments
+    pub fn read(&self, addr: u64, size: u64) -> Option<Vec<u8>> {
+        let mut bin = Vec::with_capacity(size as usize);
+        let mut addr = addr;
+        let end = addr.checked_add(size)?;
+
+        while addr < end {
+            let phdr = self.elf.phdrs.iter().find(|p| match p.p_type {
+                Phdr_type::LOAD => addr >= p.vaddr && addr < p.vaddr + p.filesz,
+                _ => false
+            })?;
+            let len = (phdr.vaddr + phdr.filesz).min(end) - addr;
+            let offset = (phdr.offset + addr - phdr.vaddr) as usize;
+            bin.extend_from_slice(&self.elf.raw.get(offset..offset + len as usize)?);
+            addr += len;
+        }
+
+        return Some(bin);
+    }
+
+    // a pointer sized value
+    pub fn read_word(&self, addr: u64) -> Option<u64> {
+        let class = self.elf.header.e_class;
+        return Some(read_word(&self.read(addr, word_size(class) as u64)?, class));
+    }
+
+    pub fn read_u32(&self, addr: u64) -> Option<u32> {
+        return Some(LittleEndian::read_u32(&self.read(addr, 4)?));
+    }
+
+    // a NUL terminated string, at most `max` bytes long
+    pub fn read_cstr(&self, addr: u64, max: u64) -> Option<String> {
+        let mut s = vec![];
+        for i in 0..max {
+            let b = self.read(addr + i, 1)?[0];
+            if b == 0 {
+                break;
+            }
+            s.push(b);
+        }
+        return Some(String::from_utf8_lossy(&s).into_owned());
+    }
+
+    // The build id of an ELF image mapped at `base`, read from its notes.
+    // The first page of every mapped ELF is dumped by default.
+    pub fn build_id(&self, base: u64) -> Option<String> {
+        let ehdr = self.read(base, 0x40)?;
+        if &ehdr[0..4] != b"\x7fELF" || ehdr[4] != Elf_class::ELF64 as u8 {
+            return None;
+        }
+        // position independent images are linked at 0
+        let bias = if LittleEndian::read_u16(&ehdr[0x10..0x12]) == Elf_type::DYN as u16 { base } else { 0 };
+        let phoff = LittleEndian::read_u64(&ehdr[0x20..0x28]);
+        let phnum = LittleEndian::read_u16(&ehdr[0x38..0x3A]) as u64;
+
+        for i in 0..phnum {
+            let phdr = ProgramHeader::parse(&self.read(base + phoff + i * 0x38, 0x38)?, Elf_class::ELF64).ok()?;
+            if let Phdr_type::NOTE = phdr.p_type {
+                let bin = match self.read(bias + phdr.vaddr, phdr.filesz) {
+                    Some(bin) => bin,
+                    None => continue
+                };
+                if let Some(id) = note::iter_notes(&bin, phdr.p_align).find_map(|n| n.build_id()) {
+                    return Some(id);
+                }
+            }
+        }
+
+        return None;
+    }
+}
+
+impl Elf {
+    pub fn memory(&self) -> Memory<'_> {
+        Memory {
+            elf: self
+        }
+    }
+
+    // The process state of a core dump. The first thread is the one that
+    // received the signal.
+    pub fn core(&self) -> Result<Core> {
+        if let Elf_type::CORE = self.header.e_type {} else {
+            return Err(ParsingError::ParsingError);
+        }
+        let class = self.header.e_class;
+        let arch = self.header.e_arch;
+
+        let mut core = Core {
+            threads: vec![],
+            process: None,
+            siginfo: None,
+            auxv: vec![],
+            files: vec![],
+            modules: vec![]
+        };
+
+        for n in self.notes()? {
+            if n.name != "CORE" {
+                continue;
+            }
+            match n.n_type {
+                NT_PRSTATUS => core.threads.extend(parse_prstatus(&n.desc, arch)),
+                NT_PRPSINFO => core.process = parse_prpsinfo(&n.desc),
+                NT_SIGINFO => core.siginfo = parse_siginfo(&n.desc, class),
+                NT_AUXV => core.auxv = parse_auxv(&n.desc, class),
+                NT_FILE => core.files = parse_files(&n.desc, class).ok_or(ParsingError::ParsingError)?,
+                _ => {}
+            }
+        }
+
+        core.modules = modules(&core.files);
+        let memory = self.memory();
+        for module in &mut core.modules {
+            module.build_id = memory.build_id(module.base);
+        }
+
+        return Ok(core);
+    }
+}
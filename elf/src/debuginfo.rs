@@ -0,0 +1,153 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::shdr;
+
+use byteorder::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Where to look for separate debug files. Every root is searched for the
+// `.build-id/xx/yyyy.debug` layout and for the path of the binary below it,
+// the way gdb does.
+pub struct DebugLookup {
+    pub roots: Vec<String>
+}
+
+impl DebugLookup {
+    pub fn new() -> DebugLookup {
+        DebugLookup {
+            roots: vec![String::from("/usr/lib/debug")]
+        }
+    }
+
+    pub fn add_root(&mut self, root: &str) {
+        self.roots.push(String::from(root));
+    }
+}
+
+// the contents of a .gnu_debuglink section
+pub struct DebugLink {
+    pub file: String,
+    pub crc: u32
+}
+
+// The CRC32 (IEEE) used for .gnu_debuglink, the same as zlib's
+pub fn crc32(bin: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffffffff;
+
+    for byte in bin {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+
+    return !crc;
+}
+
+fn read_elf(path: &Path) -> Option<Elf> {
+    let bin = fs::read(path).ok()?;
+    if !crate::is_elf(&bin) || bin.len() < 0x40 {
+        return None;
+    }
+    return Elf::parse(bin).ok();
+}
+
+impl Elf {
+    pub fn debug_link(&self) -> Option<DebugLink> {
+        let content = self.section(".gnu_debuglink")?.content();
+        let file = String::from(shdr::str_from_u8_nul_utf8(&content).ok()?);
+
+        // the name is NUL terminated and padded to 4 bytes, followed by the crc
+        let crc_offset = (file.len() + 1 + 3) / 4 * 4;
+        let crc = LittleEndian::read_u32(content.get(crc_offset..crc_offset + 4)?);
+
+        return Some(DebugLink {
+            file,
+            crc
+        });
+    }
+
+    // The candidate paths of the separate debug file, in the order they are
+    // tried. `path` is where this binary was loaded from.
+    pub fn debug_file_candidates(&self, path: &str, lookup: &DebugLookup) -> Vec<PathBuf> {
+        let mut candidates = vec![];
+
+        if let Some(id) = self.build_id() {
+            if id.len() > 2 {
+                for root in &lookup.roots {
+                    candidates.push(Path::new(root).join(".build-id").join(&id[..2]).join(format!("{}.debug", &id[2..])));
+                }
+            }
+        }
+
+        if let Some(link) = self.debug_link() {
+            let binary = fs::canonicalize(path).unwrap_or(PathBuf::from(path));
+            let dir = binary.parent().map(|d| d.to_path_buf()).unwrap_or(PathBuf::from("."));
+
+            candidates.push(dir.join(&link.file));
+            candidates.push(dir.join(".debug").join(&link.file));
+            for root in &lookup.roots {
+                let relative = dir.strip_prefix("/").unwrap_or(&dir);
+                candidates.push(Path::new(root).join(relative).join(&link.file));
+            }
+        }
+
+        return candidates;
+    }
+
+    // Find the separate debug file of this binary. A file found through the
+    // build id has to carry the same build id, one found through the debug
+    // link has to match its crc.
+    pub fn find_debug_file(&self, path: &str, lookup: &DebugLookup) -> Option<PathBuf> {
+        let id = self.build_id();
+        let link = self.debug_link();
+        let binary = fs::canonicalize(path).ok();
+
+        for candidate in self.debug_file_candidates(path, lookup) {
+            // the debug link may name the binary itself
+            if binary.is_some() && fs::canonicalize(&candidate).ok() == binary {
+                continue;
+            }
+            let bin = match fs::read(&candidate) {
+                Ok(bin) => bin,
+                Err(_) => continue
+            };
+
+            let by_link = match &link {
+                Some(link) => candidate.file_name().map(|n| n.to_string_lossy() == link.file.as_str()).unwrap_or(false) && crc32(&bin) == link.crc,
+                None => false
+            };
+            let by_id = match (&id, read_elf(&candidate)) {
+                (Some(id), Some(debug)) => debug.build_id().as_ref() == Some(id),
+                _ => false
+            };
+
+            if by_link || by_id {
+                return Some(candidate);
+            }
+        }
+
+        return None;
+    }
+
+    // Look up and attach the separate debug file, after which sections and
+    // symbols missing from this binary are served from the debug file.
+    // Returns the path of the debug file, if one was found.
+    pub fn load_debug_info(&mut self, path: &str, lookup: &DebugLookup) -> Result<Option<PathBuf>> {
+        let found = match self.find_debug_file(path, lookup) {
+            Some(found) => found,
+            None => return Ok(None)
+        };
+
+        let debug = read_elf(&found).ok_or(ParsingError::ParsingError)?;
+        self.attach_debug(debug);
+        return Ok(Some(found));
+    }
+
+    pub fn attach_debug(&mut self, debug: Elf) {
+        self.debug = Some(Box::new(debug));
+    }
+}
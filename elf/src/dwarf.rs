@@ -0,0 +1,963 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::leb128;
+use crate::line::LineProgram;
+use crate::symbol::Sym_type;
+
+use byteorder::*;
+use std::collections::HashMap;
+
+// The parts of DWARF (versions 2 to 5) needed to map addresses to
+// functions and source lines, and to decode types (see types.rs). Only the
+// constants we use are listed.
+
+pub const DW_TAG_array_type: u16 = 0x01;
+pub const DW_TAG_class_type: u16 = 0x02;
+pub const DW_TAG_enumeration_type: u16 = 0x04;
+pub const DW_TAG_formal_parameter: u16 = 0x05;
+pub const DW_TAG_lexical_block: u16 = 0x0b;
+pub const DW_TAG_member: u16 = 0x0d;
+pub const DW_TAG_pointer_type: u16 = 0x0f;
+pub const DW_TAG_reference_type: u16 = 0x10;
+pub const DW_TAG_compile_unit: u16 = 0x11;
+pub const DW_TAG_structure_type: u16 = 0x13;
+pub const DW_TAG_subroutine_type: u16 = 0x15;
+pub const DW_TAG_typedef: u16 = 0x16;
+pub const DW_TAG_union_type: u16 = 0x17;
+pub const DW_TAG_unspecified_parameters: u16 = 0x18;
+pub const DW_TAG_inheritance: u16 = 0x1c;
+pub const DW_TAG_inlined_subroutine: u16 = 0x1d;
+pub const DW_TAG_ptr_to_member_type: u16 = 0x1f;
+pub const DW_TAG_subrange_type: u16 = 0x21;
+pub const DW_TAG_base_type: u16 = 0x24;
+pub const DW_TAG_const_type: u16 = 0x26;
+pub const DW_TAG_subprogram: u16 = 0x2e;
+pub const DW_TAG_volatile_type: u16 = 0x35;
+pub const DW_TAG_restrict_type: u16 = 0x37;
+pub const DW_TAG_namespace: u16 = 0x39;
+pub const DW_TAG_unspecified_type: u16 = 0x3b;
+pub const DW_TAG_partial_unit: u16 = 0x3c;
+pub const DW_TAG_rvalue_reference_type: u16 = 0x42;
+pub const DW_TAG_atomic_type: u16 = 0x47;
+pub const DW_TAG_skeleton_unit: u16 = 0x4a;
+
+pub const DW_AT_name: u16 = 0x03;
+pub const DW_AT_byte_size: u16 = 0x0b;
+pub const DW_AT_bit_offset: u16 = 0x0c;
+pub const DW_AT_bit_size: u16 = 0x0d;
+pub const DW_AT_stmt_list: u16 = 0x10;
+pub const DW_AT_low_pc: u16 = 0x11;
+pub const DW_AT_high_pc: u16 = 0x12;
+pub const DW_AT_comp_dir: u16 = 0x1b;
+pub const DW_AT_lower_bound: u16 = 0x22;
+pub const DW_AT_upper_bound: u16 = 0x2f;
+pub const DW_AT_abstract_origin: u16 = 0x31;
+pub const DW_AT_count: u16 = 0x37;
+pub const DW_AT_data_member_location: u16 = 0x38;
+pub const DW_AT_declaration: u16 = 0x3c;
+pub const DW_AT_external: u16 = 0x3f;
+pub const DW_AT_specification: u16 = 0x47;
+pub const DW_AT_type: u16 = 0x49;
+pub const DW_AT_ranges: u16 = 0x55;
+pub const DW_AT_call_column: u16 = 0x57;
+pub const DW_AT_call_file: u16 = 0x58;
+pub const DW_AT_call_line: u16 = 0x59;
+pub const DW_AT_data_bit_offset: u16 = 0x6b;
+pub const DW_AT_linkage_name: u16 = 0x6e;
+pub const DW_AT_str_offsets_base: u16 = 0x72;
+pub const DW_AT_addr_base: u16 = 0x73;
+pub const DW_AT_rnglists_base: u16 = 0x74;
+pub const DW_AT_MIPS_linkage_name: u16 = 0x2007;
+
+pub const DW_FORM_addr: u16 = 0x01;
+pub const DW_FORM_block2: u16 = 0x03;
+pub const DW_FORM_block4: u16 = 0x04;
+pub const DW_FORM_data2: u16 = 0x05;
+pub const DW_FORM_data4: u16 = 0x06;
+pub const DW_FORM_data8: u16 = 0x07;
+pub const DW_FORM_string: u16 = 0x08;
+pub const DW_FORM_block: u16 = 0x09;
+pub const DW_FORM_block1: u16 = 0x0a;
+pub const DW_FORM_data1: u16 = 0x0b;
+pub const DW_FORM_flag: u16 = 0x0c;
+pub const DW_FORM_sdata: u16 = 0x0d;
+pub const DW_FORM_strp: u16 = 0x0e;
+pub const DW_FORM_udata: u16 = 0x0f;
+pub const DW_FORM_ref_addr: u16 = 0x10;
+pub const DW_FORM_ref1: u16 = 0x11;
+pub const DW_FORM_ref2: u16 = 0x12;
+pub const DW_FORM_ref4: u16 = 0x13;
+pub const DW_FORM_ref8: u16 = 0x14;
+pub const DW_FORM_ref_udata: u16 = 0x15;
+pub const DW_FORM_indirect: u16 = 0x16;
+pub const DW_FORM_sec_offset: u16 = 0x17;
+pub const DW_FORM_exprloc: u16 = 0x18;
+pub const DW_FORM_flag_present: u16 = 0x19;
+pub const DW_FORM_strx: u16 = 0x1a;
+pub const DW_FORM_addrx: u16 = 0x1b;
+pub const DW_FORM_ref_sup4: u16 = 0x1c;
+pub const DW_FORM_strp_sup: u16 = 0x1d;
+pub const DW_FORM_data16: u16 = 0x1e;
+pub const DW_FORM_line_strp: u16 = 0x1f;
+pub const DW_FORM_ref_sig8: u16 = 0x20;
+pub const DW_FORM_implicit_const: u16 = 0x21;
+pub const DW_FORM_loclistx: u16 = 0x22;
+pub const DW_FORM_rnglistx: u16 = 0x23;
+pub const DW_FORM_ref_sup8: u16 = 0x24;
+pub const DW_FORM_strx1: u16 = 0x25;
+pub const DW_FORM_strx2: u16 = 0x26;
+pub const DW_FORM_strx3: u16 = 0x27;
+pub const DW_FORM_strx4: u16 = 0x28;
+pub const DW_FORM_addrx1: u16 = 0x29;
+pub const DW_FORM_addrx2: u16 = 0x2a;
+pub const DW_FORM_addrx3: u16 = 0x2b;
+pub const DW_FORM_addrx4: u16 = 0x2c;
+pub const DW_FORM_GNU_addr_index: u16 = 0x1f01;
+pub const DW_FORM_GNU_str_index: u16 = 0x1f02;
+pub const DW_FORM_GNU_ref_alt: u16 = 0x1f20;
+pub const DW_FORM_GNU_strp_alt: u16 = 0x1f21;
+
+const DW_UT_type: u8 = 0x02;
+const DW_UT_skeleton: u8 = 0x04;
+const DW_UT_split_compile: u8 = 0x05;
+const DW_UT_split_type: u8 = 0x06;
+
+const DW_RLE_end_of_list: u8 = 0x0;
+const DW_RLE_base_addressx: u8 = 0x1;
+const DW_RLE_startx_endx: u8 = 0x2;
+const DW_RLE_startx_length: u8 = 0x3;
+const DW_RLE_offset_pair: u8 = 0x4;
+const DW_RLE_base_address: u8 = 0x5;
+const DW_RLE_start_end: u8 = 0x6;
+const DW_RLE_start_length: u8 = 0x7;
+
+// A cursor over a DWARF section. Reads past the end return None.
+pub struct Reader<'a> {
+    pub bin: &'a [u8],
+    pub pos: usize
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bin: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader {
+            bin,
+            pos
+        }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bin = self.bin.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        return Some(bin);
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        return Some(self.bytes(1)?[0]);
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        return Some(LittleEndian::read_u16(self.bytes(2)?));
+    }
+
+    pub fn u24(&mut self) -> Option<u32> {
+        return Some(LittleEndian::read_u24(self.bytes(3)?));
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        return Some(LittleEndian::read_u32(self.bytes(4)?));
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        return Some(LittleEndian::read_u64(self.bytes(8)?));
+    }
+
+    pub fn uleb(&mut self) -> Option<u64> {
+        return leb128::read_uleb128(self.bin, &mut self.pos);
+    }
+
+    pub fn sleb(&mut self) -> Option<i64> {
+        return leb128::read_sleb128(self.bin, &mut self.pos);
+    }
+
+    // a NUL terminated string
+    pub fn cstr(&mut self) -> Option<String> {
+        let rest = self.bin.get(self.pos..)?;
+        let len = rest.iter().position(|b| *b == 0)?;
+        self.pos += len + 1;
+        return Some(String::from_utf8_lossy(&rest[..len]).into_owned());
+    }
+
+    // an unsigned value of `size` bytes
+    pub fn sized(&mut self, size: u8) -> Option<u64> {
+        return match size {
+            1 => Some(self.u8()? as u64),
+            2 => Some(self.u16()? as u64),
+            4 => Some(self.u32()? as u64),
+            8 => self.u64(),
+            _ => None
+        }
+    }
+
+    // a section offset, 4 bytes in 32-bit DWARF and 8 in 64-bit DWARF
+    pub fn offset(&mut self, format64: bool) -> Option<u64> {
+        return if format64 { self.u64() } else { Some(self.u32()? as u64) };
+    }
+
+    // The initial length of a unit, returns the length and whether the unit
+    // uses the 64-bit DWARF format.
+    pub fn initial_length(&mut self) -> Option<(u64, bool)> {
+        let len = self.u32()?;
+        if len == 0xffffffff {
+            return Some((self.u64()?, true));
+        }
+        return Some((len as u64, false));
+    }
+}
+
+// what is needed to decode attribute values of a unit
+#[derive(Copy, Clone)]
+pub struct Encoding {
+    pub version: u16,
+    pub format64: bool,
+    pub address_size: u8
+}
+
+#[derive(Clone, Debug)]
+pub enum AttrValue {
+    Addr(u64),
+    Udata(u64),
+    Sdata(i64),
+    String(String),
+    // an offset into .debug_info, relative refs are made absolute
+    Ref(u64),
+    Signature(u64),
+    Block(Vec<u8>),
+    Flag(bool),
+    SecOffset(u64),
+    // indices into .debug_str_offsets and .debug_addr, resolved after the unit is read
+    StrIndex(u64),
+    AddrIndex(u64),
+    // DW_FORM_loclistx and DW_FORM_rnglistx
+    ListIndex(u64)
+}
+
+impl AttrValue {
+    pub fn udata(&self) -> Option<u64> {
+        return match self {
+            AttrValue::Udata(val) | AttrValue::SecOffset(val) | AttrValue::Addr(val) => Some(*val),
+            AttrValue::Sdata(val) => Some(*val as u64),
+            AttrValue::Flag(val) => Some(*val as u64),
+            _ => None
+        }
+    }
+
+    pub fn string(&self) -> Option<&str> {
+        return match self {
+            AttrValue::String(s) => Some(s.as_str()),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AttrSpec {
+    pub name: u16,
+    pub form: u16,
+    pub implicit_const: i64
+}
+
+#[derive(Clone)]
+pub struct Abbrev {
+    pub code: u64,
+    pub tag: u16,
+    pub children: bool,
+    pub attrs: Vec<AttrSpec>
+}
+
+// parse the abbreviation table at `offset` in .debug_abbrev
+pub fn parse_abbrevs(bin: &[u8], offset: u64) -> Result<HashMap<u64, Abbrev>> {
+    let mut abbrevs = HashMap::new();
+    let mut r = Reader::new(bin, offset as usize);
+
+    loop {
+        let code = r.uleb().ok_or(ParsingError::ParsingError)?;
+        if code == 0 {
+            break;
+        }
+        let tag = r.uleb().ok_or(ParsingError::ParsingError)? as u16;
+        let children = r.u8().ok_or(ParsingError::ParsingError)? != 0;
+        let mut attrs = vec![];
+
+        loop {
+            let name = r.uleb().ok_or(ParsingError::ParsingError)? as u16;
+            let form = r.uleb().ok_or(ParsingError::ParsingError)? as u16;
+            if name == 0 && form == 0 {
+                break;
+            }
+            let implicit_const = if form == DW_FORM_implicit_const { r.sleb().ok_or(ParsingError::ParsingError)? } else { 0 };
+            attrs.push(AttrSpec {
+                name,
+                form,
+                implicit_const
+            });
+        }
+
+        abbrevs.insert(code, Abbrev {
+            code,
+            tag,
+            children,
+            attrs
+        });
+    }
+
+    return Ok(abbrevs);
+}
+
+#[derive(Clone)]
+pub struct Attribute {
+    pub name: u16,
+    pub form: u16,
+    pub value: AttrValue
+}
+
+// A debugging information entry. The entries of a unit are stored flat in
+// the order they appear, linked by index to their parent and children.
+#[derive(Clone)]
+pub struct Die {
+    pub offset: u64,
+    pub tag: u16,
+    pub attrs: Vec<Attribute>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>
+}
+
+impl Die {
+    pub fn attr(&self, name: u16) -> Option<&AttrValue> {
+        return self.attrs.iter().find(|a| a.name == name).map(|a| &a.value);
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        return self.attr(DW_AT_name)?.string();
+    }
+}
+
+pub struct Unit {
+    // the offset of the unit header in .debug_info
+    pub offset: u64,
+    pub unit_type: u8,
+    pub encoding: Encoding,
+    pub abbrev_offset: u64,
+    pub dies: Vec<Die>,
+    pub str_offsets_base: u64,
+    pub addr_base: u64,
+    pub rnglists_base: u64,
+    pub lines: Option<LineProgram>
+}
+
+impl Unit {
+    // the DW_TAG_compile_unit (or partial/type unit) entry
+    pub fn root(&self) -> Option<&Die> {
+        return self.dies.get(0);
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        return self.root()?.name();
+    }
+
+    pub fn comp_dir(&self) -> Option<&str> {
+        return self.root()?.attr(DW_AT_comp_dir)?.string();
+    }
+
+    // the base address for location and range lists
+    pub fn base_address(&self) -> u64 {
+        return match self.root().and_then(|r| r.attr(DW_AT_low_pc)) {
+            Some(AttrValue::Addr(addr)) => *addr,
+            _ => 0
+        }
+    }
+}
+
+// Read the value of an attribute. `unit_offset` makes unit relative
+// references absolute, strings are looked up as far as possible.
+pub fn read_value(r: &mut Reader, form: u16, implicit_const: i64, enc: Encoding, unit_offset: u64, dwarf: &Dwarf) -> Option<AttrValue> {
+    let value = match form {
+        DW_FORM_addr => AttrValue::Addr(r.sized(enc.address_size)?),
+        DW_FORM_data1 => AttrValue::Udata(r.u8()? as u64),
+        DW_FORM_data2 => AttrValue::Udata(r.u16()? as u64),
+        DW_FORM_data4 => AttrValue::Udata(r.u32()? as u64),
+        DW_FORM_data8 => AttrValue::Udata(r.u64()?),
+        DW_FORM_data16 => AttrValue::Block(r.bytes(16)?.to_vec()),
+        DW_FORM_sdata => AttrValue::Sdata(r.sleb()?),
+        DW_FORM_udata => AttrValue::Udata(r.uleb()?),
+        DW_FORM_implicit_const => AttrValue::Sdata(implicit_const),
+        DW_FORM_string => AttrValue::String(r.cstr()?),
+        DW_FORM_strp => AttrValue::String(dwarf.str_at(&dwarf.str, r.offset(enc.format64)?)),
+        DW_FORM_line_strp => AttrValue::String(dwarf.str_at(&dwarf.line_str, r.offset(enc.format64)?)),
+        DW_FORM_strp_sup | DW_FORM_GNU_strp_alt => { r.offset(enc.format64)?; AttrValue::String(String::new()) },
+        DW_FORM_strx | DW_FORM_GNU_str_index => AttrValue::StrIndex(r.uleb()?),
+        DW_FORM_strx1 => AttrValue::StrIndex(r.u8()? as u64),
+        DW_FORM_strx2 => AttrValue::StrIndex(r.u16()? as u64),
+        DW_FORM_strx3 => AttrValue::StrIndex(r.u24()? as u64),
+        DW_FORM_strx4 => AttrValue::StrIndex(r.u32()? as u64),
+        DW_FORM_addrx | DW_FORM_GNU_addr_index => AttrValue::AddrIndex(r.uleb()?),
+        DW_FORM_addrx1 => AttrValue::AddrIndex(r.u8()? as u64),
+        DW_FORM_addrx2 => AttrValue::AddrIndex(r.u16()? as u64),
+        DW_FORM_addrx3 => AttrValue::AddrIndex(r.u24()? as u64),
+        DW_FORM_addrx4 => AttrValue::AddrIndex(r.u32()? as u64),
+        DW_FORM_ref1 => AttrValue::Ref(unit_offset + r.u8()? as u64),
+        DW_FORM_ref2 => AttrValue::Ref(unit_offset + r.u16()? as u64),
+        DW_FORM_ref4 => AttrValue::Ref(unit_offset + r.u32()? as u64),
+        DW_FORM_ref8 => AttrValue::Ref(unit_offset + r.u64()?),
+        DW_FORM_ref_udata => AttrValue::Ref(unit_offset + r.uleb()?),
+        // DWARF 2 used the address size for DW_FORM_ref_addr
+        DW_FORM_ref_addr if enc.version == 2 => AttrValue::Ref(r.sized(enc.address_size)?),
+        DW_FORM_ref_addr => AttrValue::Ref(r.offset(enc.format64)?),
+        DW_FORM_ref_sig8 => AttrValue::Signature(r.u64()?),
+        DW_FORM_ref_sup4 => AttrValue::Udata(r.u32()? as u64),
+        DW_FORM_ref_sup8 => AttrValue::Udata(r.u64()?),
+        DW_FORM_GNU_ref_alt => AttrValue::Udata(r.offset(enc.format64)?),
+        DW_FORM_sec_offset => AttrValue::SecOffset(r.offset(enc.format64)?),
+        DW_FORM_loclistx | DW_FORM_rnglistx => AttrValue::ListIndex(r.uleb()?),
+        DW_FORM_flag => AttrValue::Flag(r.u8()? != 0),
+        DW_FORM_flag_present => AttrValue::Flag(true),
+        DW_FORM_block1 => { let len = r.u8()? as usize; AttrValue::Block(r.bytes(len)?.to_vec()) },
+        DW_FORM_block2 => { let len = r.u16()? as usize; AttrValue::Block(r.bytes(len)?.to_vec()) },
+        DW_FORM_block4 => { let len = r.u32()? as usize; AttrValue::Block(r.bytes(len)?.to_vec()) },
+        DW_FORM_block | DW_FORM_exprloc => { let len = r.uleb()? as usize; AttrValue::Block(r.bytes(len)?.to_vec()) },
+        DW_FORM_indirect => {
+            let form = r.uleb()? as u16;
+            return read_value(r, form, implicit_const, enc, unit_offset, dwarf);
+        },
+        _ => return None
+    };
+
+    return Some(value);
+}
+
+// The DWARF sections of a binary, decompressed, and the units parsed out of
+// .debug_info. Sections missing from the binary are taken from an attached
+// debug file.
+pub struct Dwarf {
+    pub info: Vec<u8>,
+    pub abbrev: Vec<u8>,
+    pub str: Vec<u8>,
+    pub line_str: Vec<u8>,
+    pub line: Vec<u8>,
+    pub str_offsets: Vec<u8>,
+    pub addr: Vec<u8>,
+    pub ranges: Vec<u8>,
+    pub rnglists: Vec<u8>,
+    pub units: Vec<Unit>,
+    // die offset -> (unit, index of the die)
+    index: HashMap<u64, (usize, usize)>
+}
+
+impl Dwarf {
+    pub fn load(elf: &Elf) -> Result<Dwarf> {
+        let content = |name: &str| elf.section(name).map(|s| s.content()).unwrap_or(vec![]);
+
+        let mut dwarf = Dwarf {
+            info: content(".debug_info"),
+            abbrev: content(".debug_abbrev"),
+            str: content(".debug_str"),
+            line_str: content(".debug_line_str"),
+            line: content(".debug_line"),
+            str_offsets: content(".debug_str_offsets"),
+            addr: content(".debug_addr"),
+            ranges: content(".debug_ranges"),
+            rnglists: content(".debug_rnglists"),
+            units: vec![],
+            index: HashMap::new()
+        };
+
+        dwarf.units = dwarf.parse_units()?;
+        for (u, unit) in dwarf.units.iter().enumerate() {
+            for (i, die) in unit.dies.iter().enumerate() {
+                dwarf.index.insert(die.offset, (u, i));
+            }
+        }
+
+        return Ok(dwarf);
+    }
+
+    // a NUL terminated string in a string section
+    pub fn str_at(&self, section: &[u8], offset: u64) -> String {
+        return match section.get(offset as usize..) {
+            Some(s) => Reader::new(s, 0).cstr().unwrap_or(String::new()),
+            None => String::new()
+        }
+    }
+
+    // an entry of .debug_str_offsets
+    pub fn str_index(&self, base: u64, index: u64, format64: bool) -> Option<String> {
+        let size = if format64 { 8 } else { 4 };
+        let mut r = Reader::new(&self.str_offsets, (base + index * size) as usize);
+        let offset = r.offset(format64)?;
+        return Some(self.str_at(&self.str, offset));
+    }
+
+    // an entry of .debug_addr
+    pub fn addr_index(&self, base: u64, index: u64, address_size: u8) -> Option<u64> {
+        let mut r = Reader::new(&self.addr, (base + index * address_size as u64) as usize);
+        return r.sized(address_size);
+    }
+
+    fn parse_units(&self) -> Result<Vec<Unit>> {
+        let mut units = vec![];
+        let mut pos = 0;
+
+        while pos < self.info.len() {
+            let mut r = Reader::new(&self.info, pos);
+            let (length, format64) = r.initial_length().ok_or(ParsingError::ParsingError)?;
+            let end = r.pos + length as usize;
+            if length == 0 || end > self.info.len() {
+                break;
+            }
+
+            units.push(self.parse_unit(pos as u64, r, format64, end)?);
+            pos = end;
+        }
+
+        return Ok(units);
+    }
+
+    fn parse_unit(&self, offset: u64, mut r: Reader, format64: bool, end: usize) -> Result<Unit> {
+        let version = r.u16().ok_or(ParsingError::ParsingError)?;
+        let unit_type;
+        let abbrev_offset;
+        let address_size;
+
+        // DWARF 5 moved the address size in front of the abbreviation offset
+        if version >= 5 {
+            unit_type = r.u8().ok_or(ParsingError::ParsingError)?;
+            address_size = r.u8().ok_or(ParsingError::ParsingError)?;
+            abbrev_offset = r.offset(format64).ok_or(ParsingError::ParsingError)?;
+            match unit_type {
+                DW_UT_skeleton | DW_UT_split_compile => { r.u64(); },
+                DW_UT_type | DW_UT_split_type => { r.u64(); r.offset(format64); },
+                _ => {}
+            }
+        } else {
+            unit_type = 1;
+            abbrev_offset = r.offset(format64).ok_or(ParsingError::ParsingError)?;
+            address_size = r.u8().ok_or(ParsingError::ParsingError)?;
+        }
+
+        let enc = Encoding {
+            version,
+            format64,
+            address_size
+        };
+        let abbrevs = parse_abbrevs(&self.abbrev, abbrev_offset)?;
+        let mut dies: Vec<Die> = vec![];
+        let mut parents: Vec<usize> = vec![];
+
+        while r.pos < end {
+            let die_offset = r.pos as u64;
+            let code = r.uleb().ok_or(ParsingError::ParsingError)?;
+
+            // a null entry ends the children of the current parent
+            if code == 0 {
+                parents.pop();
+                continue;
+            }
+
+            let abbrev = abbrevs.get(&code).ok_or(ParsingError::ParsingError)?;
+            let mut attrs = vec![];
+            for spec in &abbrev.attrs {
+                let value = read_value(&mut r, spec.form, spec.implicit_const, enc, offset, self).ok_or(ParsingError::ParsingError)?;
+                attrs.push(Attribute {
+                    name: spec.name,
+                    form: spec.form,
+                    value
+                });
+            }
+
+            let index = dies.len();
+            let parent = parents.last().cloned();
+            if let Some(parent) = parent {
+                dies[parent].children.push(index);
+            }
+            dies.push(Die {
+                offset: die_offset,
+                tag: abbrev.tag,
+                attrs,
+                parent,
+                children: vec![]
+            });
+
+            if abbrev.children {
+                parents.push(index);
+            }
+        }
+
+        let mut unit = Unit {
+            offset,
+            unit_type,
+            encoding: enc,
+            abbrev_offset,
+            dies,
+            str_offsets_base: 0,
+            addr_base: 0,
+            rnglists_base: 0,
+            lines: None
+        };
+
+        self.resolve_indices(&mut unit);
+
+        if let Some(root) = unit.root() {
+            if let Some(stmt_list) = root.attr(DW_AT_stmt_list).and_then(|v| v.udata()) {
+                let comp_dir = String::from(unit.comp_dir().unwrap_or(""));
+                let name = String::from(unit.name().unwrap_or(""));
+                unit.lines = LineProgram::parse(self, stmt_list, &comp_dir, &name, &unit).ok();
+            }
+        }
+
+        return Ok(unit);
+    }
+
+    // Resolve DW_FORM_strx and DW_FORM_addrx, which need the bases the root
+    // entry of the unit carries.
+    fn resolve_indices(&self, unit: &mut Unit) {
+        let format64 = unit.encoding.format64;
+        let header = if format64 { 16 } else { 8 };
+
+        if let Some(root) = unit.dies.get(0) {
+            // the bases default to the first table, right after its header
+            let base = |name: u16, default: u64| root.attr(name).and_then(|v| v.udata()).unwrap_or(default);
+            let str_offsets_base = base(DW_AT_str_offsets_base, if self.str_offsets.is_empty() { 0 } else { header });
+            let addr_base = base(DW_AT_addr_base, if self.addr.is_empty() { 0 } else { header });
+            let rnglists_base = base(DW_AT_rnglists_base, if unit.encoding.version >= 5 { header + 4 } else { 0 });
+
+            unit.str_offsets_base = str_offsets_base;
+            unit.addr_base = addr_base;
+            unit.rnglists_base = rnglists_base;
+        }
+
+        let (str_base, addr_base, size) = (unit.str_offsets_base, unit.addr_base, unit.encoding.address_size);
+        for die in unit.dies.iter_mut() {
+            for attr in die.attrs.iter_mut() {
+                match attr.value {
+                    AttrValue::StrIndex(index) => if let Some(s) = self.str_index(str_base, index, format64) {
+                        attr.value = AttrValue::String(s);
+                    },
+                    AttrValue::AddrIndex(index) => if let Some(addr) = self.addr_index(addr_base, index, size) {
+                        attr.value = AttrValue::Addr(addr);
+                    },
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // look up an entry by its offset in .debug_info
+    pub fn die(&self, offset: u64) -> Option<(&Unit, &Die)> {
+        let (u, i) = self.index.get(&offset)?;
+        let unit = self.units.get(*u)?;
+        return Some((unit, unit.dies.get(*i)?));
+    }
+
+    // The name of a function, following DW_AT_abstract_origin (inlined and
+    // out of line instances) and DW_AT_specification (C++ definitions).
+    pub fn die_name(&self, die: &Die) -> Option<String> {
+        let mut die = die;
+
+        // bounded, a malformed file could contain a reference cycle
+        for _ in 0..16 {
+            if let Some(name) = die.name() {
+                return Some(String::from(name));
+            }
+            let target = match die.attr(DW_AT_abstract_origin).or(die.attr(DW_AT_specification)) {
+                Some(AttrValue::Ref(target)) => *target,
+                _ => return None
+            };
+            die = self.die(target)?.1;
+        }
+
+        return None;
+    }
+
+    // the mangled name of a function, if it has one
+    pub fn linkage_name(&self, die: &Die) -> Option<String> {
+        let mut die = die;
+
+        for _ in 0..16 {
+            if let Some(name) = die.attr(DW_AT_linkage_name).or(die.attr(DW_AT_MIPS_linkage_name)).and_then(|v| v.string()) {
+                return Some(String::from(name));
+            }
+            let target = match die.attr(DW_AT_abstract_origin).or(die.attr(DW_AT_specification)) {
+                Some(AttrValue::Ref(target)) => *target,
+                _ => return None
+            };
+            die = self.die(target)?.1;
+        }
+
+        return None;
+    }
+
+    // The address ranges covered by an entry, from DW_AT_low_pc and
+    // DW_AT_high_pc or from a range list.
+    pub fn die_ranges(&self, unit: &Unit, die: &Die) -> Vec<(u64, u64)> {
+        if let Some(ranges) = die.attr(DW_AT_ranges) {
+            let offset = match ranges {
+                AttrValue::ListIndex(index) => {
+                    let size = if unit.encoding.format64 { 8 } else { 4 };
+                    match Reader::new(&self.rnglists, (unit.rnglists_base + index * size) as usize).offset(unit.encoding.format64) {
+                        Some(offset) => unit.rnglists_base + offset,
+                        None => return vec![]
+                    }
+                },
+                other => match other.udata() {
+                    Some(offset) => offset,
+                    None => return vec![]
+                }
+            };
+
+            return if unit.encoding.version >= 5 {
+                self.rnglist(unit, offset).unwrap_or(vec![])
+            } else {
+                self.range_list(unit, offset).unwrap_or(vec![])
+            };
+        }
+
+        let low = match die.attr(DW_AT_low_pc) {
+            Some(AttrValue::Addr(low)) => *low,
+            _ => return vec![]
+        };
+        return match die.attr(DW_AT_high_pc) {
+            // the high pc is either an address or the size of the range
+            Some(AttrValue::Addr(high)) => vec![(low, *high)],
+            Some(size) => match size.udata() {
+                Some(size) => vec![(low, low + size)],
+                None => vec![]
+            },
+            None => vec![(low, low + 1)]
+        }
+    }
+
+    // a DWARF 4 range list in .debug_ranges
+    fn range_list(&self, unit: &Unit, offset: u64) -> Option<Vec<(u64, u64)>> {
+        let size = unit.encoding.address_size;
+        let max = if size == 8 { u64::MAX } else { u32::MAX as u64 };
+        let mut base = unit.base_address();
+        let mut ranges = vec![];
+        let mut r = Reader::new(&self.ranges, offset as usize);
+
+        loop {
+            let start = r.sized(size)?;
+            let end = r.sized(size)?;
+
+            if start == 0 && end == 0 {
+                break;
+            }
+            if start == max {
+                base = end;
+                continue;
+            }
+            if start != end {
+                ranges.push((base + start, base + end));
+            }
+        }
+
+        return Some(ranges);
+    }
+
+    // a DWARF 5 range list in .debug_rnglists
+    fn rnglist(&self, unit: &Unit, offset: u64) -> Option<Vec<(u64, u64)>> {
+        let size = unit.encoding.address_size;
+        let addrx = |index: u64| self.addr_index(unit.addr_base, index, size);
+        let mut base = unit.base_address();
+        let mut ranges = vec![];
+        let mut r = Reader::new(&self.rnglists, offset as usize);
+
+        loop {
+            let range = match r.u8()? {
+                DW_RLE_end_of_list => break,
+                DW_RLE_base_addressx => { base = addrx(r.uleb()?)?; continue; },
+                DW_RLE_base_address => { base = r.sized(size)?; continue; },
+                DW_RLE_startx_endx => (addrx(r.uleb()?)?, addrx(r.uleb()?)?),
+                DW_RLE_startx_length => { let start = addrx(r.uleb()?)?; (start, start + r.uleb()?) },
+                DW_RLE_offset_pair => (base + r.uleb()?, base + r.uleb()?),
+                DW_RLE_start_end => (r.sized(size)?, r.sized(size)?),
+                DW_RLE_start_length => { let start = r.sized(size)?; (start, start + r.uleb()?) },
+                _ => return None
+            };
+
+            if range.0 != range.1 {
+                ranges.push(range);
+            }
+        }
+
+        return Some(ranges);
+    }
+
+    pub fn die_contains(&self, unit: &Unit, die: &Die, addr: u64) -> bool {
+        return self.die_ranges(unit, die).iter().any(|(start, end)| addr >= *start && addr < *end);
+    }
+
+    // the unit whose code covers an address
+    pub fn find_unit(&self, addr: u64) -> Option<&Unit> {
+        for unit in &self.units {
+            if let Some(root) = unit.root() {
+                if self.die_contains(unit, root, addr) {
+                    return Some(unit);
+                }
+            }
+        }
+
+        // units without ranges, fall back on the line tables
+        return self.units.iter().find(|u| u.lines.as_ref().map(|l| l.find(addr).is_some()).unwrap_or(false));
+    }
+
+    // The chain of functions an address is in, from the outermost
+    // subprogram to the innermost inlined subroutine.
+    pub fn function_chain<'a>(&self, unit: &'a Unit, addr: u64) -> Vec<&'a Die> {
+        let mut chain = vec![];
+        let mut current = match unit.root() {
+            Some(root) => root,
+            None => return chain
+        };
+
+        'descend: loop {
+            for child in &current.children {
+                let die = &unit.dies[*child];
+                let ranges = self.die_ranges(unit, die);
+
+                let enter = match die.tag {
+                    DW_TAG_subprogram | DW_TAG_inlined_subroutine | DW_TAG_lexical_block => {
+                        // a lexical block without ranges just groups its children
+                        (ranges.is_empty() && die.tag == DW_TAG_lexical_block) || ranges.iter().any(|(s, e)| addr >= *s && addr < *e)
+                    },
+                    // member functions can be defined inside their class or namespace
+                    DW_TAG_namespace | DW_TAG_class_type | DW_TAG_structure_type | DW_TAG_union_type => ranges.is_empty() && self.subtree_contains(unit, die, addr),
+                    _ => false
+                };
+
+                if enter {
+                    if die.tag == DW_TAG_subprogram || die.tag == DW_TAG_inlined_subroutine {
+                        chain.push(die);
+                    }
+                    current = die;
+                    continue 'descend;
+                }
+            }
+            break;
+        }
+
+        return chain;
+    }
+
+    // whether a function below this entry covers the address
+    fn subtree_contains(&self, unit: &Unit, die: &Die, addr: u64) -> bool {
+        for child in &die.children {
+            let child = &unit.dies[*child];
+            match child.tag {
+                DW_TAG_subprogram => if self.die_contains(unit, child, addr) { return true },
+                DW_TAG_namespace | DW_TAG_class_type | DW_TAG_structure_type | DW_TAG_union_type => if self.subtree_contains(unit, child, addr) { return true },
+                _ => {}
+            }
+        }
+        return false;
+    }
+
+    // Resolve an address to its source location. With inlining there are
+    // several frames, the innermost inlined function comes first and the
+    // function it was inlined into last, like `addr2line -i`.
+    pub fn find_frames(&self, addr: u64) -> Vec<Frame> {
+        let unit = match self.find_unit(addr) {
+            Some(unit) => unit,
+            None => return vec![]
+        };
+        let lines = unit.lines.as_ref();
+
+        let mut location = match lines.and_then(|l| l.find(addr)) {
+            Some(row) => Location {
+                file: lines.and_then(|l| l.file_name(row.file)),
+                line: row.line,
+                column: row.column
+            },
+            None => Location {
+                file: None,
+                line: 0,
+                column: 0
+            }
+        };
+
+        let chain = self.function_chain(unit, addr);
+        if chain.is_empty() {
+            return vec![Frame {
+                function: None,
+                linkage_name: None,
+                location
+            }];
+        }
+
+        let mut frames = vec![];
+        for die in chain.iter().rev() {
+            frames.push(Frame {
+                function: self.die_name(die),
+                linkage_name: self.linkage_name(die),
+                location
+            });
+
+            // the caller continues at the call site of the inlined function
+            location = Location {
+                file: die.attr(DW_AT_call_file).and_then(|f| f.udata()).and_then(|f| lines.and_then(|l| l.file_name(f))),
+                line: die.attr(DW_AT_call_line).and_then(|l| l.udata()).unwrap_or(0),
+                column: die.attr(DW_AT_call_column).and_then(|c| c.udata()).unwrap_or(0)
+            };
+        }
+
+        return frames;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub file: Option<String>,
+    pub line: u64,
+    pub column: u64
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub function: Option<String>,
+    pub linkage_name: Option<String>,
+    pub location: Location
+}
+
+impl Elf {
+    pub fn dwarf(&self) -> Result<Dwarf> {
+        return Dwarf::load(self);
+    }
+
+    // Map an address to its source location, see Dwarf::find_frames. Parses
+    // all debug info on each call, keep a Dwarf around to look up many
+    // addresses. Code without debug info gets its function from the symbols.
+    pub fn addr2line(&self, addr: u64) -> Result<Vec<Frame>> {
+        let mut frames = self.dwarf()?.find_frames(addr);
+
+        if frames.iter().all(|f| f.function.is_none()) {
+            let syms = self.symbols()?;
+            let sym = syms.iter().find(|s| s.sym_type() == Sym_type::FUNC && addr >= s.value && addr < s.value + s.size.max(1));
+
+            if let Some(sym) = sym {
+                match frames.first_mut() {
+                    Some(frame) => frame.function = Some(sym.name.clone()),
+                    None => frames.push(Frame {
+                        function: Some(sym.name.clone()),
+                        linkage_name: None,
+                        location: Location {
+                            file: None,
+                            line: 0,
+                            column: 0
+                        }
+                    })
+                }
+            }
+        }
+
+        return Ok(frames);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_class;
+use crate::phdr;
+
+use byteorder::*;
+
+#[repr(u64)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum Dyn_tag {
+    NULL = 0,
+    NEEDED = 1,
+    PLTRELSZ = 2,
+    PLTGOT = 3,
+    HASH = 4,
+    STRTAB = 5,
+    SYMTAB = 6,
+    RELA = 7,
+    RELASZ = 8,
+    RELAENT = 9,
+    STRSZ = 10,
+    SYMENT = 11,
+    INIT = 12,
+    FINI = 13,
+    SONAME = 14,
+    RPATH = 15,
+    SYMBOLIC = 16,
+    REL = 17,
+    RELSZ = 18,
+    RELENT = 19,
+    PLTREL = 20,
+    DEBUG = 21,
+    TEXTREL = 22,
+    JMPREL = 23,
+    BIND_NOW = 24,
+    INIT_ARRAY = 25,
+    FINI_ARRAY = 26,
+    INIT_ARRAYSZ = 27,
+    FINI_ARRAYSZ = 28,
+    RUNPATH = 29,
+    FLAGS = 30,
+    PREINIT_ARRAY = 32,
+    PREINIT_ARRAYSZ = 33,
+    SYMTAB_SHNDX = 34,
+    RELRSZ = 35,
+    RELR = 36,
+    RELRENT = 37,
+    ANDROID_REL = 0x6000000f,
+    ANDROID_RELSZ = 0x60000010,
+    ANDROID_RELA = 0x60000011,
+    ANDROID_RELASZ = 0x60000012,
+    GNU_HASH = 0x6ffffef5,
+    VERSYM = 0x6ffffff0,
+    RELACOUNT = 0x6ffffff9,
+    RELCOUNT = 0x6ffffffa,
+    FLAGS_1 = 0x6ffffffb,
+    VERDEF = 0x6ffffffc,
+    VERDEFNUM = 0x6ffffffd,
+    VERNEED = 0x6ffffffe,
+    VERNEEDNUM = 0x6fffffff,
+    ANDROID_RELR = 0x6fffe000,
+    ANDROID_RELRSZ = 0x6fffe001,
+    ANDROID_RELRENT = 0x6fffe003,
+}
+
+// A single entry of the dynamic table. The tag is kept as the raw value, as
+// there are plenty of OS and processor specific tags we don't know about.
+#[derive(Copy, Clone)]
+pub struct Dyn {
+    pub d_tag: u64,
+    pub d_val: u64
+}
+
+impl Dyn {
+
+    pub fn parse(dyn_entry: &[u8], class: Elf_class) -> Result<Dyn> {
+        return match class {
+            Elf_class::ELF64 => Ok(Dyn {
+                d_tag: LittleEndian::read_u64(&dyn_entry[0x0..0x8]),
+                d_val: LittleEndian::read_u64(&dyn_entry[0x8..0x10]),
+            }),
+            Elf_class::ELF32 => Ok(Dyn {
+                d_tag: LittleEndian::read_u32(&dyn_entry[0x0..0x4]) as u64,
+                d_val: LittleEndian::read_u32(&dyn_entry[0x4..0x8]) as u64,
+            })
+        }
+    }
+
+    pub fn tag(&self) -> Option<Dyn_tag> {
+        return parse_dyn_tag(self.d_tag);
+    }
+
+    pub fn to_le(&self, class: Elf_class) -> Vec<u8> {
+        let mut bin = vec![];
+
+        match class {
+            Elf_class::ELF64 => {
+                bin.extend_from_slice(&self.d_tag.to_le_bytes());
+                bin.extend_from_slice(&self.d_val.to_le_bytes());
+            },
+            Elf_class::ELF32 => {
+                bin.extend_from_slice(&(self.d_tag as u32).to_le_bytes());
+                bin.extend_from_slice(&(self.d_val as u32).to_le_bytes());
+            }
+        }
+
+        return bin;
+    }
+}
+
+fn parse_dyn_tag(tag: u64) -> Option<Dyn_tag> {
+    return match tag {
+        0 => Some(Dyn_tag::NULL),
+        1 => Some(Dyn_tag::NEEDED),
+        2 => Some(Dyn_tag::PLTRELSZ),
+        3 => Some(Dyn_tag::PLTGOT),
+        4 => Some(Dyn_tag::HASH),
+        5 => Some(Dyn_tag::STRTAB),
+        6 => Some(Dyn_tag::SYMTAB),
+        7 => Some(Dyn_tag::RELA),
+        8 => Some(Dyn_tag::RELASZ),
+        9 => Some(Dyn_tag::RELAENT),
+        10 => Some(Dyn_tag::STRSZ),
+        11 => Some(Dyn_tag::SYMENT),
+        12 => Some(Dyn_tag::INIT),
+        13 => Some(Dyn_tag::FINI),
+        14 => Some(Dyn_tag::SONAME),
+        15 => Some(Dyn_tag::RPATH),
+        16 => Some(Dyn_tag::SYMBOLIC),
+        17 => Some(Dyn_tag::REL),
+        18 => Some(Dyn_tag::RELSZ),
+        19 => Some(Dyn_tag::RELENT),
+        20 => Some(Dyn_tag::PLTREL),
+        21 => Some(Dyn_tag::DEBUG),
+        22 => Some(Dyn_tag::TEXTREL),
+        23 => Some(Dyn_tag::JMPREL),
+        24 => Some(Dyn_tag::BIND_NOW),
+        25 => Some(Dyn_tag::INIT_ARRAY),
+        26 => Some(Dyn_tag::FINI_ARRAY),
+        27 => Some(Dyn_tag::INIT_ARRAYSZ),
+        28 => Some(Dyn_tag::FINI_ARRAYSZ),
+        29 => Some(Dyn_tag::RUNPATH),
+        30 => Some(Dyn_tag::FLAGS),
+        32 => Some(Dyn_tag::PREINIT_ARRAY),
+        33 => Some(Dyn_tag::PREINIT_ARRAYSZ),
+        34 => Some(Dyn_tag::SYMTAB_SHNDX),
+        35 => Some(Dyn_tag::RELRSZ),
+        36 => Some(Dyn_tag::RELR),
+        37 => Some(Dyn_tag::RELRENT),
+        0x6000000f => Some(Dyn_tag::ANDROID_REL),
+        0x60000010 => Some(Dyn_tag::ANDROID_RELSZ),
+        0x60000011 => Some(Dyn_tag::ANDROID_RELA),
+        0x60000012 => Some(Dyn_tag::ANDROID_RELASZ),
+        0x6ffffef5 => Some(Dyn_tag::GNU_HASH),
+        0x6ffffff0 => Some(Dyn_tag::VERSYM),
+        0x6ffffff9 => Some(Dyn_tag::RELACOUNT),
+        0x6ffffffa => Some(Dyn_tag::RELCOUNT),
+        0x6ffffffb => Some(Dyn_tag::FLAGS_1),
+        0x6ffffffc => Some(Dyn_tag::VERDEF),
+        0x6ffffffd => Some(Dyn_tag::VERDEFNUM),
+        0x6ffffffe => Some(Dyn_tag::VERNEED),
+        0x6fffffff => Some(Dyn_tag::VERNEEDNUM),
+        0x6fffe000 => Some(Dyn_tag::ANDROID_RELR),
+        0x6fffe001 => Some(Dyn_tag::ANDROID_RELRSZ),
+        0x6fffe003 => Some(Dyn_tag::ANDROID_RELRENT),
+        _ => None
+    }
+}
+
+pub fn entry_size(class: Elf_class) -> usize {
+    return match class {
+        Elf_class::ELF64 => 0x10,
+        Elf_class::ELF32 => 0x8
+    }
+}
+
+// parse the dynamic table up to and including the terminating DT_NULL entry
+pub fn parse_dynamic(bin: &[u8], class: Elf_class) -> Result<Vec<Dyn>> {
+    let size = entry_size(class);
+    let mut dyns = vec![];
+
+    for entry in bin.chunks_exact(size) {
+        let d = Dyn::parse(entry, class)?;
+        dyns.push(d);
+
+        if d.d_tag == Dyn_tag::NULL as u64 {
+            break;
+        }
+    }
+
+    return Ok(dyns);
+}
+
+impl Elf {
+    // the file range of the dynamic table, taken from PT_DYNAMIC or the .dynamic section
+    pub fn dynamic_range(&self) -> Option<(u64, u64)> {
+        for phdr in &self.phdrs {
+            if let phdr::Phdr_type::DYNAMIC = phdr.p_type {
+                return Some((phdr.offset, phdr.filesz));
+            }
+        }
+
+        let shdr = self.section_header(".dynamic")?;
+        return Some((shdr.offset, shdr.size));
+    }
+
+    // parse the dynamic table, an executable without one returns an empty table
+    pub fn dynamic(&self) -> Result<Vec<Dyn>> {
+        let (offset, size) = match self.dynamic_range() {
+            Some(range) => range,
+            None => return Ok(vec![])
+        };
+
+        let bin = self.raw.get(offset as usize..(offset+size) as usize).ok_or(ParsingError::ParsingError)?;
+        return parse_dynamic(&bin, self.header.e_class);
+    }
+
+    // get the value of the first dynamic entry with the given tag
+    pub fn dynamic_value(&self, tag: Dyn_tag) -> Option<u64> {
+        let dyns = self.dynamic().ok()?;
+        return dyns.iter().find(|d| d.d_tag == tag as u64).map(|d| d.d_val);
+    }
+
+    // overwrite the value of an existing dynamic entry in the raw binary
+    pub fn set_dynamic_value(&mut self, tag: Dyn_tag, val: u64) -> Result<()> {
+        let (offset, _) = self.dynamic_range().ok_or(ParsingError::ParsingError)?;
+        let class = self.header.e_class;
+        let size = entry_size(class);
+        let index = self.dynamic()?.iter().position(|d| d.d_tag == tag as u64).ok_or(ParsingError::ParsingError)?;
+        let start = offset as usize + index*size;
+
+        let entry = Dyn { d_tag: tag as u64, d_val: val };
+        self.raw.splice(start..start+size, entry.to_le(class));
+        return Ok(());
+    }
+}
@@ -0,0 +1,760 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_arch;
+use crate::Elf_class;
+use crate::dwarf::Reader;
+use crate::phdr::Phdr_type;
+
+use std::collections::HashMap;
+
+// Call frame information from .eh_frame (or .debug_frame): the CIEs and FDEs,
+// the .eh_frame_hdr lookup table and the evaluation of the CFA and register
+// rules at an address, which is what an unwinder needs.
+
+// pointer encodings, the low nibble is the format and the high one how to apply it
+const DW_EH_PE_absptr: u8 = 0x00;
+const DW_EH_PE_uleb128: u8 = 0x01;
+const DW_EH_PE_udata2: u8 = 0x02;
+const DW_EH_PE_udata4: u8 = 0x03;
+const DW_EH_PE_udata8: u8 = 0x04;
+const DW_EH_PE_sleb128: u8 = 0x09;
+const DW_EH_PE_sdata2: u8 = 0x0a;
+const DW_EH_PE_sdata4: u8 = 0x0b;
+const DW_EH_PE_sdata8: u8 = 0x0c;
+const DW_EH_PE_pcrel: u8 = 0x10;
+const DW_EH_PE_textrel: u8 = 0x20;
+const DW_EH_PE_datarel: u8 = 0x30;
+const DW_EH_PE_funcrel: u8 = 0x40;
+const DW_EH_PE_aligned: u8 = 0x50;
+const DW_EH_PE_omit: u8 = 0xff;
+
+const DW_CFA_advance_loc: u8 = 0x40;
+const DW_CFA_offset: u8 = 0x80;
+const DW_CFA_restore: u8 = 0xc0;
+const DW_CFA_nop: u8 = 0x00;
+const DW_CFA_set_loc: u8 = 0x01;
+const DW_CFA_advance_loc1: u8 = 0x02;
+const DW_CFA_advance_loc2: u8 = 0x03;
+const DW_CFA_advance_loc4: u8 = 0x04;
+const DW_CFA_offset_extended: u8 = 0x05;
+const DW_CFA_restore_extended: u8 = 0x06;
+const DW_CFA_undefined: u8 = 0x07;
+const DW_CFA_same_value: u8 = 0x08;
+const DW_CFA_register: u8 = 0x09;
+const DW_CFA_remember_state: u8 = 0x0a;
+const DW_CFA_restore_state: u8 = 0x0b;
+const DW_CFA_def_cfa: u8 = 0x0c;
+const DW_CFA_def_cfa_register: u8 = 0x0d;
+const DW_CFA_def_cfa_offset: u8 = 0x0e;
+const DW_CFA_def_cfa_expression: u8 = 0x0f;
+const DW_CFA_expression: u8 = 0x10;
+const DW_CFA_offset_extended_sf: u8 = 0x11;
+const DW_CFA_def_cfa_sf: u8 = 0x12;
+const DW_CFA_def_cfa_offset_sf: u8 = 0x13;
+const DW_CFA_val_offset: u8 = 0x14;
+const DW_CFA_val_offset_sf: u8 = 0x15;
+const DW_CFA_val_expression: u8 = 0x16;
+const DW_CFA_AARCH64_negate_ra_state: u8 = 0x2d;
+const DW_CFA_GNU_args_size: u8 = 0x2e;
+const DW_CFA_GNU_negative_offset_extended: u8 = 0x2f;
+
+#[derive(Clone, Debug)]
+pub struct Cie {
+    pub offset: u64,
+    pub version: u8,
+    pub augmentation: String,
+    pub address_size: u8,
+    pub code_align: u64,
+    pub data_align: i64,
+    pub ra_register: u16,
+    pub fde_encoding: u8,
+    pub lsda_encoding: u8,
+    pub personality: Option<u64>,
+    pub signal_frame: bool,
+    pub instructions: Vec<u8>
+}
+
+#[derive(Clone, Debug)]
+pub struct Fde {
+    pub offset: u64,
+    // the offset of the CIE in the section
+    pub cie: u64,
+    pub pc_begin: u64,
+    pub pc_range: u64,
+    pub lsda: Option<u64>,
+    pub instructions: Vec<u8>
+}
+
+impl Fde {
+    pub fn contains(&self, pc: u64) -> bool {
+        return pc >= self.pc_begin && pc < self.pc_begin.wrapping_add(self.pc_range);
+    }
+}
+
+// how to compute the canonical frame address
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cfa_rule {
+    REG_OFFSET(u16, i64),
+    EXPRESSION(Vec<u8>)
+}
+
+// where the caller's value of a register is
+#[derive(Clone, Debug, PartialEq)]
+pub enum Register_rule {
+    UNDEFINED,
+    SAME_VALUE,
+    // saved at CFA + n
+    OFFSET(i64),
+    // the value is CFA + n
+    VAL_OFFSET(i64),
+    // held by another register
+    REGISTER(u16),
+    // saved at the address the expression computes
+    EXPRESSION(Vec<u8>),
+    // the value is what the expression computes
+    VAL_EXPRESSION(Vec<u8>)
+}
+
+// The rules at one address. Registers without a rule keep their value.
+#[derive(Clone, Debug)]
+pub struct UnwindRow {
+    pub start: u64,
+    pub end: u64,
+    pub cfa: Cfa_rule,
+    pub registers: Vec<(u16, Register_rule)>,
+    pub ra_register: u16,
+    pub signal_frame: bool
+}
+
+impl UnwindRow {
+    pub fn register(&self, reg: u16) -> Register_rule {
+        return match self.registers.iter().find(|(r, _)| *r == reg) {
+            Some((_, rule)) => rule.clone(),
+            None => Register_rule::SAME_VALUE
+        }
+    }
+
+    fn set(&mut self, reg: u16, rule: Register_rule) {
+        match self.registers.iter_mut().find(|(r, _)| *r == reg) {
+            Some(entry) => entry.1 = rule,
+            None => self.registers.push((reg, rule))
+        }
+    }
+
+    // Compute the registers of the caller from those of this frame.
+    // `read` reads a word of memory. The stack pointer of the caller is the
+    // CFA. Returns the return address and the caller's registers.
+    pub fn step(&self, regs: &HashMap<u16, u64>, sp_register: u16, read: &dyn Fn(u64) -> Option<u64>) -> Option<(u64, HashMap<u16, u64>)> {
+        let cfa = match &self.cfa {
+            Cfa_rule::REG_OFFSET(reg, offset) => regs.get(reg)?.wrapping_add(*offset as u64),
+            Cfa_rule::EXPRESSION(expr) => evaluate(expr, regs, None, read)?
+        };
+
+        let mut caller = regs.clone();
+        caller.insert(sp_register, cfa);
+
+        for (reg, rule) in &self.registers {
+            let value = match rule {
+                Register_rule::UNDEFINED => { caller.remove(reg); continue; },
+                Register_rule::SAME_VALUE => continue,
+                Register_rule::OFFSET(n) => read(cfa.wrapping_add(*n as u64)),
+                Register_rule::VAL_OFFSET(n) => Some(cfa.wrapping_add(*n as u64)),
+                Register_rule::REGISTER(other) => regs.get(other).cloned(),
+                Register_rule::EXPRESSION(expr) => evaluate(expr, regs, Some(cfa), read).and_then(|addr| read(addr)),
+                Register_rule::VAL_EXPRESSION(expr) => evaluate(expr, regs, Some(cfa), read)
+            };
+            match value {
+                Some(value) => { caller.insert(*reg, value); },
+                None => { caller.remove(reg); }
+            }
+        }
+
+        let ra = *caller.get(&self.ra_register)?;
+        return Some((ra, caller));
+    }
+}
+
+// Evaluate a DWARF expression as used by CFI. `cfa` is pushed first for
+// register rules. Only the operations that show up in CFI are supported.
+pub fn evaluate(expr: &[u8], regs: &HashMap<u16, u64>, cfa: Option<u64>, read: &dyn Fn(u64) -> Option<u64>) -> Option<u64> {
+    let mut stack: Vec<u64> = vec![];
+    if let Some(cfa) = cfa {
+        stack.push(cfa);
+    }
+    let mut r = Reader::new(expr, 0);
+
+    while r.pos < expr.len() {
+        let op = r.u8()?;
+        match op {
+            0x03 => stack.push(r.u64()?),                                   // DW_OP_addr
+            0x06 => { let addr = stack.pop()?; stack.push(read(addr)?) },   // DW_OP_deref
+            0x08 => stack.push(r.u8()? as u64),                             // DW_OP_const1u
+            0x09 => stack.push(r.u8()? as i8 as u64),                       // DW_OP_const1s
+            0x0a => stack.push(r.u16()? as u64),                            // DW_OP_const2u
+            0x0b => stack.push(r.u16()? as i16 as u64),                     // DW_OP_const2s
+            0x0c => stack.push(r.u32()? as u64),                            // DW_OP_const4u
+            0x0d => stack.push(r.u32()? as i32 as u64),                     // DW_OP_const4s
+            0x0e | 0x0f => stack.push(r.u64()?),                            // DW_OP_const8u/s
+            0x10 => stack.push(r.uleb()?),                                  // DW_OP_constu
+            0x11 => stack.push(r.sleb()? as u64),                           // DW_OP_consts
+            0x12 => stack.push(*stack.last()?),                             // DW_OP_dup
+            0x13 => { stack.pop()?; },                                      // DW_OP_drop
+            0x14 => stack.push(*stack.get(stack.len().checked_sub(2)?)?),   // DW_OP_over
+            0x15 => { let i = r.u8()? as usize; stack.push(*stack.get(stack.len().checked_sub(i + 1)?)?) },
+            0x16 => { let a = stack.pop()?; let b = stack.pop()?; stack.push(a); stack.push(b) },
+            0x17 => { let a = stack.pop()?; let b = stack.pop()?; let c = stack.pop()?; stack.push(a); stack.push(c); stack.push(b) },
+            0x19 => { let a = stack.pop()? as i64; stack.push(a.unsigned_abs()) },
+            0x1a => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b & a) },
+            0x1b => { let a = stack.pop()? as i64; let b = stack.pop()? as i64; stack.push(b.checked_div(a)? as u64) },
+            0x1c => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b.wrapping_sub(a)) },
+            0x1d => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b.checked_rem(a)?) },
+            0x1e => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b.wrapping_mul(a)) },
+            0x1f => { let a = stack.pop()? as i64; stack.push(a.wrapping_neg() as u64) },
+            0x20 => { let a = stack.pop()?; stack.push(!a) },
+            0x21 => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b | a) },
+            0x22 => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b.wrapping_add(a)) },
+            0x23 => { let a = stack.pop()?; stack.push(a.wrapping_add(r.uleb()?)) },
+            0x24 => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b.checked_shl(a as u32).unwrap_or(0)) },
+            0x25 => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b.checked_shr(a as u32).unwrap_or(0)) },
+            0x26 => { let a = stack.pop()?; let b = stack.pop()? as i64; stack.push((b >> a.min(63)) as u64) },
+            0x27 => { let a = stack.pop()?; let b = stack.pop()?; stack.push(b ^ a) },
+            // DW_OP_eq, ge, gt, le, lt, ne compare signed
+            0x29..=0x2e => {
+                let a = stack.pop()? as i64;
+                let b = stack.pop()? as i64;
+                let res = match op {
+                    0x29 => b == a,
+                    0x2a => b >= a,
+                    0x2b => b > a,
+                    0x2c => b <= a,
+                    0x2d => b < a,
+                    _ => b != a
+                };
+                stack.push(res as u64);
+            },
+            // DW_OP_skip and DW_OP_bra
+            0x2f | 0x28 => {
+                let offset = r.u16()? as i16 as i64;
+                if op == 0x2f || stack.pop()? != 0 {
+                    r.pos = (r.pos as i64 + offset) as usize;
+                }
+            },
+            0x30..=0x4f => stack.push((op - 0x30) as u64),                  // DW_OP_lit0..31
+            0x50..=0x6f => stack.push(*regs.get(&((op - 0x50) as u16))?),   // DW_OP_reg0..31
+            0x70..=0x8f => {                                                // DW_OP_breg0..31
+                let reg = *regs.get(&((op - 0x70) as u16))?;
+                stack.push(reg.wrapping_add(r.sleb()? as u64));
+            },
+            0x90 => stack.push(*regs.get(&(r.uleb()? as u16))?),            // DW_OP_regx
+            0x92 => {                                                       // DW_OP_bregx
+                let reg = *regs.get(&(r.uleb()? as u16))?;
+                stack.push(reg.wrapping_add(r.sleb()? as u64));
+            },
+            0x94 => {                                                       // DW_OP_deref_size
+                let size = r.u8()?;
+                let addr = stack.pop()?;
+                let mask = if size >= 8 { u64::MAX } else { (1u64 << (size * 8)) - 1 };
+                stack.push(read(addr)? & mask);
+            },
+            0x96 => {},                                                     // DW_OP_nop
+            _ => return None
+        }
+    }
+
+    return stack.pop();
+}
+
+// Read a pointer in the given encoding. `field` is the address the pointer
+// is read from, used for pc relative pointers, `data` the base of datarel ones.
+fn read_pointer(r: &mut Reader, encoding: u8, address_size: u8, field: u64, data: u64) -> Option<u64> {
+    if encoding == DW_EH_PE_omit {
+        return None;
+    }
+
+    let value = match encoding & 0x0f {
+        DW_EH_PE_absptr => r.sized(address_size)?,
+        DW_EH_PE_uleb128 => r.uleb()?,
+        DW_EH_PE_udata2 => r.u16()? as u64,
+        DW_EH_PE_udata4 => r.u32()? as u64,
+        DW_EH_PE_udata8 => r.u64()?,
+        DW_EH_PE_sleb128 => r.sleb()? as u64,
+        DW_EH_PE_sdata2 => r.u16()? as i16 as u64,
+        DW_EH_PE_sdata4 => r.u32()? as i32 as u64,
+        DW_EH_PE_sdata8 => r.u64()?,
+        _ => return None
+    };
+
+    let base = match encoding & 0x70 {
+        DW_EH_PE_pcrel => field,
+        DW_EH_PE_datarel => data,
+        // we don't know the text or function base and don't align
+        DW_EH_PE_textrel | DW_EH_PE_funcrel | DW_EH_PE_aligned => return None,
+        _ => 0
+    };
+
+    // indirect pointers point at the real value, we leave that to the caller
+    return Some(base.wrapping_add(value));
+}
+
+fn address_size(class: Elf_class) -> u8 {
+    return match class {
+        Elf_class::ELF64 => 8,
+        Elf_class::ELF32 => 4
+    }
+}
+
+// The parsed call frame information of a binary, with the FDEs sorted on
+// their start address.
+pub struct EhFrame {
+    // the address the section is loaded at, 0 for .debug_frame
+    pub vaddr: u64,
+    pub cies: HashMap<u64, Cie>,
+    pub fdes: Vec<Fde>,
+    // the binary search table of .eh_frame_hdr: (initial location, FDE address)
+    pub table: Vec<(u64, u64)>
+}
+
+// Parse .eh_frame, or .debug_frame when `debug` is set, loaded at `vaddr`.
+pub fn parse_frames(bin: &[u8], vaddr: u64, class: Elf_class, debug: bool) -> Result<EhFrame> {
+    let mut cies = HashMap::new();
+    let mut fdes = vec![];
+    let mut pos = 0;
+    let addr_size = address_size(class);
+
+    while pos + 4 <= bin.len() {
+        let mut r = Reader::new(bin, pos);
+        let (length, format64) = r.initial_length().ok_or(ParsingError::ParsingError)?;
+        if length == 0 {
+            // a zero terminator ends .eh_frame, anywhere else it is padding
+            if debug { pos = r.pos; continue; }
+            break;
+        }
+        let end = r.pos.checked_add(length as usize).filter(|e| *e <= bin.len()).ok_or(ParsingError::ParsingError)?;
+        let id_pos = r.pos;
+        let id = r.offset(format64).ok_or(ParsingError::ParsingError)?;
+
+        let is_cie = if debug { id == if format64 { u64::MAX } else { 0xffffffff } } else { id == 0 };
+        if is_cie {
+            let cie = parse_cie(&mut r, pos as u64, end, vaddr, addr_size).ok_or(ParsingError::ParsingError)?;
+            cies.insert(pos as u64, cie);
+        } else {
+            // .eh_frame refers to the CIE relative to the id field
+            let cie_offset = if debug { id } else { (id_pos as u64).wrapping_sub(id) };
+            let cie = match cies.get(&cie_offset) {
+                Some(cie) => cie.clone(),
+                None => {
+                    let mut cr = Reader::new(bin, cie_offset as usize);
+                    let (cie_length, _) = cr.initial_length().ok_or(ParsingError::ParsingError)?;
+                    let cie_end = cr.pos + cie_length as usize;
+                    cr.offset(format64).ok_or(ParsingError::ParsingError)?;
+                    let cie = parse_cie(&mut cr, cie_offset, cie_end, vaddr, addr_size).ok_or(ParsingError::ParsingError)?;
+                    cies.insert(cie_offset, cie.clone());
+                    cie
+                }
+            };
+            fdes.push(parse_fde(&mut r, pos as u64, cie_offset, &cie, end, vaddr).ok_or(ParsingError::ParsingError)?);
+        }
+
+        pos = end;
+    }
+
+    fdes.sort_by_key(|f| f.pc_begin);
+
+    Ok(EhFrame {
+        vaddr,
+        cies,
+        fdes,
+        table: vec![]
+    })
+}
+
+fn parse_cie(r: &mut Reader, offset: u64, end: usize, vaddr: u64, addr_size: u8) -> Option<Cie> {
+    let version = r.u8()?;
+    let augmentation = r.cstr()?;
+    let mut address_size = addr_size;
+
+    if augmentation.contains("eh") {
+        r.sized(addr_size)?;
+    }
+    if version >= 4 {
+        address_size = r.u8()?;
+        let _segment_size = r.u8()?;
+    }
+
+    let code_align = r.uleb()?;
+    let data_align = r.sleb()?;
+    let ra_register = if version == 1 { r.u8()? as u16 } else { r.uleb()? as u16 };
+
+    let mut cie = Cie {
+        offset,
+        version,
+        augmentation: augmentation.clone(),
+        address_size,
+        code_align,
+        data_align,
+        ra_register,
+        fde_encoding: DW_EH_PE_absptr,
+        lsda_encoding: DW_EH_PE_omit,
+        personality: None,
+        signal_frame: false,
+        instructions: vec![]
+    };
+
+    if augmentation.starts_with('z') {
+        let len = r.uleb()? as usize;
+        let data_end = r.pos + len;
+
+        for c in augmentation.chars().skip(1) {
+            match c {
+                'L' => cie.lsda_encoding = r.u8()?,
+                'R' => cie.fde_encoding = r.u8()?,
+                'P' => {
+                    let encoding = r.u8()?;
+                    let field = vaddr + r.pos as u64;
+                    cie.personality = read_pointer(r, encoding, address_size, field, 0);
+                },
+                'S' => cie.signal_frame = true,
+                _ => {}
+            }
+        }
+        r.pos = data_end;
+    }
+
+    cie.instructions = r.bin.get(r.pos..end)?.to_vec();
+    return Some(cie);
+}
+
+fn parse_fde(r: &mut Reader, offset: u64, cie_offset: u64, cie: &Cie, end: usize, vaddr: u64) -> Option<Fde> {
+    let field = vaddr + r.pos as u64;
+    let pc_begin = read_pointer(r, cie.fde_encoding, cie.address_size, field, 0)?;
+    // the range is a plain number in the same format
+    let pc_range = read_pointer(r, cie.fde_encoding & 0x0f, cie.address_size, 0, 0)?;
+    let mut lsda = None;
+
+    if cie.augmentation.starts_with('z') {
+        let len = r.uleb()? as usize;
+        let data_end = r.pos + len;
+        if cie.lsda_encoding != DW_EH_PE_omit && len > 0 {
+            let field = vaddr + r.pos as u64;
+            lsda = read_pointer(r, cie.lsda_encoding, cie.address_size, field, 0);
+        }
+        r.pos = data_end;
+    }
+
+    Some(Fde {
+        offset,
+        cie: cie_offset,
+        pc_begin,
+        pc_range,
+        lsda,
+        instructions: r.bin.get(r.pos..end)?.to_vec()
+    })
+}
+
+// Parse .eh_frame_hdr loaded at `vaddr`. Returns the address of .eh_frame
+// and the sorted lookup table.
+pub fn parse_eh_frame_hdr(bin: &[u8], vaddr: u64, class: Elf_class) -> Option<(u64, Vec<(u64, u64)>)> {
+    let mut r = Reader::new(bin, 0);
+    let addr_size = address_size(class);
+
+    let version = r.u8()?;
+    if version != 1 {
+        return None;
+    }
+    let eh_frame_ptr_enc = r.u8()?;
+    let fde_count_enc = r.u8()?;
+    let table_enc = r.u8()?;
+
+    let field = vaddr + r.pos as u64;
+    let eh_frame = read_pointer(&mut r, eh_frame_ptr_enc, addr_size, field, vaddr)?;
+    let field = vaddr + r.pos as u64;
+    let count = read_pointer(&mut r, fde_count_enc, addr_size, field, vaddr).unwrap_or(0);
+
+    let mut table = vec![];
+    for _ in 0..count {
+        let field = vaddr + r.pos as u64;
+        let loc = read_pointer(&mut r, table_enc, addr_size, field, vaddr)?;
+        let field = vaddr + r.pos as u64;
+        let fde = read_pointer(&mut r, table_enc, addr_size, field, vaddr)?;
+        table.push((loc, fde));
+    }
+
+    return Some((eh_frame, table));
+}
+
+impl EhFrame {
+    // the FDE covering an address
+    pub fn find_fde(&self, pc: u64) -> Option<&Fde> {
+        let i = match self.fdes.binary_search_by_key(&pc, |f| f.pc_begin) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1
+        };
+        return self.fdes.get(i).filter(|f| f.contains(pc));
+    }
+
+    // the start and end address of every FDE, sorted
+    pub fn function_ranges(&self) -> Vec<(u64, u64)> {
+        return self.fdes.iter().filter(|f| f.pc_range > 0).map(|f| (f.pc_begin, f.pc_begin + f.pc_range)).collect();
+    }
+
+    // The CFA and register rules in effect at an address, by running the
+    // instructions of the CIE and FDE up to it.
+    pub fn unwind_row(&self, pc: u64) -> Option<UnwindRow> {
+        let fde = self.find_fde(pc)?;
+        let cie = self.cies.get(&fde.cie)?;
+
+        let mut row = UnwindRow {
+            start: fde.pc_begin,
+            end: fde.pc_begin + fde.pc_range,
+            cfa: Cfa_rule::REG_OFFSET(0, 0),
+            registers: vec![],
+            ra_register: cie.ra_register,
+            signal_frame: cie.signal_frame
+        };
+
+        // the initial instructions set the rules DW_CFA_restore goes back to
+        execute(&cie.instructions, cie, &mut row, None, u64::MAX)?;
+        let initial = row.clone();
+        execute(&fde.instructions, cie, &mut row, Some(&initial), pc)?;
+
+        return Some(row);
+    }
+}
+
+// Run call frame instructions until the location passes `pc`, the row ends
+// up covering [start, end) around it.
+fn execute(insns: &[u8], cie: &Cie, row: &mut UnwindRow, initial: Option<&UnwindRow>, pc: u64) -> Option<()> {
+    let mut r = Reader::new(insns, 0);
+    let mut stack: Vec<UnwindRow> = vec![];
+    let mut loc = row.start;
+    let data_align = cie.data_align;
+
+    // stop when the next row starts past the address we look for
+    let advance = |row: &mut UnwindRow, next: u64| -> bool {
+        if next > pc {
+            row.end = next;
+            return true;
+        }
+        row.start = next;
+        return false;
+    };
+
+    while r.pos < insns.len() {
+        let op = r.u8()?;
+        let low = op & 0x3f;
+
+        match op & 0xc0 {
+            DW_CFA_advance_loc => {
+                loc += low as u64 * cie.code_align;
+                if advance(row, loc) { return Some(()); }
+                continue;
+            },
+            DW_CFA_offset => {
+                let offset = r.uleb()? as i64 * data_align;
+                row.set(low as u16, Register_rule::OFFSET(offset));
+                continue;
+            },
+            DW_CFA_restore => {
+                let rule = initial.map(|i| i.register(low as u16)).unwrap_or(Register_rule::SAME_VALUE);
+                row.set(low as u16, rule);
+                continue;
+            },
+            _ => {}
+        }
+
+        match op {
+            DW_CFA_nop => {},
+            DW_CFA_set_loc => {
+                loc = read_pointer(&mut r, cie.fde_encoding, cie.address_size, 0, 0)?;
+                if advance(row, loc) { return Some(()); }
+            },
+            DW_CFA_advance_loc1 | DW_CFA_advance_loc2 | DW_CFA_advance_loc4 => {
+                let delta = match op {
+                    DW_CFA_advance_loc1 => r.u8()? as u64,
+                    DW_CFA_advance_loc2 => r.u16()? as u64,
+                    _ => r.u32()? as u64
+                };
+                loc += delta * cie.code_align;
+                if advance(row, loc) { return Some(()); }
+            },
+            DW_CFA_offset_extended => {
+                let reg = r.uleb()? as u16;
+                let offset = r.uleb()? as i64 * data_align;
+                row.set(reg, Register_rule::OFFSET(offset));
+            },
+            DW_CFA_offset_extended_sf => {
+                let reg = r.uleb()? as u16;
+                let offset = r.sleb()? * data_align;
+                row.set(reg, Register_rule::OFFSET(offset));
+            },
+            DW_CFA_GNU_negative_offset_extended => {
+                let reg = r.uleb()? as u16;
+                let offset = -(r.uleb()? as i64) * data_align;
+                row.set(reg, Register_rule::OFFSET(offset));
+            },
+            DW_CFA_val_offset => {
+                let reg = r.uleb()? as u16;
+                let offset = r.uleb()? as i64 * data_align;
+                row.set(reg, Register_rule::VAL_OFFSET(offset));
+            },
+            DW_CFA_val_offset_sf => {
+                let reg = r.uleb()? as u16;
+                let offset = r.sleb()? * data_align;
+                row.set(reg, Register_rule::VAL_OFFSET(offset));
+            },
+            DW_CFA_restore_extended => {
+                let reg = r.uleb()? as u16;
+                let rule = initial.map(|i| i.register(reg)).unwrap_or(Register_rule::SAME_VALUE);
+                row.set(reg, rule);
+            },
+            DW_CFA_undefined => { let reg = r.uleb()? as u16; row.set(reg, Register_rule::UNDEFINED); },
+            DW_CFA_same_value => { let reg = r.uleb()? as u16; row.set(reg, Register_rule::SAME_VALUE); },
+            DW_CFA_register => {
+                let reg = r.uleb()? as u16;
+                let other = r.uleb()? as u16;
+                row.set(reg, Register_rule::REGISTER(other));
+            },
+            DW_CFA_remember_state => stack.push(row.clone()),
+            DW_CFA_restore_state => {
+                // the location is not part of the saved state
+                let saved = stack.pop()?;
+                row.cfa = saved.cfa;
+                row.registers = saved.registers;
+            },
+            DW_CFA_def_cfa => {
+                let reg = r.uleb()? as u16;
+                let offset = r.uleb()? as i64;
+                row.cfa = Cfa_rule::REG_OFFSET(reg, offset);
+            },
+            DW_CFA_def_cfa_sf => {
+                let reg = r.uleb()? as u16;
+                let offset = r.sleb()? * data_align;
+                row.cfa = Cfa_rule::REG_OFFSET(reg, offset);
+            },
+            DW_CFA_def_cfa_register => {
+                let reg = r.uleb()? as u16;
+                if let Cfa_rule::REG_OFFSET(_, offset) = row.cfa {
+                    row.cfa = Cfa_rule::REG_OFFSET(reg, offset);
+                }
+            },
+            DW_CFA_def_cfa_offset => {
+                let offset = r.uleb()? as i64;
+                if let Cfa_rule::REG_OFFSET(reg, _) = row.cfa {
+                    row.cfa = Cfa_rule::REG_OFFSET(reg, offset);
+                }
+            },
+            DW_CFA_def_cfa_offset_sf => {
+                let offset = r.sleb()? * data_align;
+                if let Cfa_rule::REG_OFFSET(reg, _) = row.cfa {
+                    row.cfa = Cfa_rule::REG_OFFSET(reg, offset);
+                }
+            },
+            DW_CFA_def_cfa_expression => {
+                let len = r.uleb()? as usize;
+                row.cfa = Cfa_rule::EXPRESSION(r.bytes(len)?.to_vec());
+            },
+            DW_CFA_expression | DW_CFA_val_expression => {
+                let reg = r.uleb()? as u16;
+                let len = r.uleb()? as usize;
+                let expr = r.bytes(len)?.to_vec();
+                row.set(reg, if op == DW_CFA_expression { Register_rule::EXPRESSION(expr) } else { Register_rule::VAL_EXPRESSION(expr) });
+            },
+            DW_CFA_GNU_args_size => { r.uleb()?; },
+            // pointer authentication state of the return address, nothing to track here
+            DW_CFA_AARCH64_negate_ra_state => {},
+            _ => return None
+        }
+    }
+
+    return Some(());
+}
+
+// the DWARF number of the stack pointer
+pub fn sp_register(arch: Elf_arch) -> Option<u16> {
+    return match arch {
+        Elf_arch::AMD64 => Some(7),
+        Elf_arch::X86 => Some(4),
+        Elf_arch::AARCH64 => Some(31),
+        Elf_arch::ARM => Some(13),
+        Elf_arch::RISCV => Some(2),
+        _ => None
+    }
+}
+
+impl Elf {
+    // The call frame information of the binary. .eh_frame is found through
+    // its section or, when the section headers are missing, through the
+    // PT_GNU_EH_FRAME segment. Falls back on .debug_frame.
+    pub fn eh_frame(&self) -> Result<EhFrame> {
+        let class = self.header.e_class;
+
+        let mut table = vec![];
+        let mut eh_frame_addr = None;
+        if let Some(hdr) = self.phdrs.iter().find(|p| if let Phdr_type::GNU_EH_FRAME = p.p_type { true } else { false }) {
+            let bin = self.raw.get(hdr.offset as usize..(hdr.offset + hdr.filesz) as usize).ok_or(ParsingError::ParsingError)?;
+            if let Some((addr, entries)) = parse_eh_frame_hdr(&bin, hdr.vaddr, class) {
+                eh_frame_addr = Some(addr);
+                table = entries;
+            }
+        }
+
+        let mut frames = if let Some(section) = self.section(".eh_frame") {
+            parse_frames(&section.content(), section.hdr.addr, class, false)?
+        } else if let Some(addr) = eh_frame_addr {
+            // without a size, read up to the end of the load segment, .eh_frame ends with a terminator
+            let offset = self.vaddr_to_offset(addr).ok_or(ParsingError::ParsingError)?;
+            let load = self.phdrs.iter().find(|p| if let Phdr_type::LOAD = p.p_type { offset >= p.offset && offset < p.offset + p.filesz } else { false }).ok_or(ParsingError::ParsingError)?;
+            let bin = self.raw.get(offset as usize..(load.offset + load.filesz) as usize).ok_or(ParsingError::ParsingError)?;
+            parse_frames(&bin, addr, class, false)?
+        } else if let Some(section) = self.section(".debug_frame") {
+            parse_frames(&section.content(), 0, class, true)?
+        } else {
+            return Err(ParsingError::ParsingError);
+        };
+
+        frames.table = table;
+        return Ok(frames);
+    }
+
+    // the function boundaries described by the call frame information
+    pub fn function_ranges(&self) -> Result<Vec<(u64, u64)>> {
+        return Ok(self.eh_frame()?.function_ranges());
+    }
+
+    // Walk the stack from a register state, returning the return addresses.
+    // `regs` is keyed on DWARF register numbers and must include the stack
+    // pointer and the return address register, `read` reads a word of memory.
+    pub fn backtrace(&self, pc: u64, regs: &HashMap<u16, u64>, read: &dyn Fn(u64) -> Option<u64>) -> Result<Vec<u64>> {
+        let frames = self.eh_frame()?;
+        let sp = sp_register(self.header.e_arch).ok_or(ParsingError::ParsingError)?;
+        let mut regs = regs.clone();
+        let mut pcs = vec![pc];
+        let mut pc = pc;
+        let mut signal = true;
+
+        while pcs.len() < 256 {
+            // a return address points after the call, look up the call itself
+            let lookup = if signal { pc } else { pc - 1 };
+            let row = match frames.unwind_row(lookup) {
+                Some(row) => row,
+                None => break
+            };
+            let (ra, caller) = match row.step(&regs, sp, read) {
+                Some(next) => next,
+                None => break
+            };
+            if ra == 0 || (ra == pc && caller.get(&sp) == regs.get(&sp)) {
+                break;
+            }
+
+            signal = row.signal_frame;
+            pcs.push(ra);
+            pc = ra;
+            regs = caller;
+        }
+
+        return Ok(pcs);
+    }
+}
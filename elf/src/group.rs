@@ -0,0 +1,231 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::shdr;
+use crate::shdr::Shdr_type;
+use crate::shdr::Shdr_flags;
+use crate::symbol::Sym_type;
+
+use byteorder::*;
+
+// Section groups (SHT_GROUP) of relocatable objects. The content of a group
+// section is a flags word followed by the indices of its member sections, all
+// 4 byte words in both classes. sh_link is the symbol table and sh_info the
+// symbol whose name is the signature of the group. The linker keeps only one
+// COMDAT group per signature.
+
+pub const GRP_COMDAT: u32 = 0x1;
+
+#[derive(Clone, Debug)]
+pub struct Group {
+    // the index of the SHT_GROUP section
+    pub index: usize,
+    pub flags: u32,
+    pub signature: String,
+    pub symtab: u32,
+    pub sym_index: u32,
+    pub members: Vec<u32>
+}
+
+impl Group {
+    pub fn is_comdat(&self) -> bool {
+        return self.flags & GRP_COMDAT != 0;
+    }
+
+    pub fn to_le(&self) -> Vec<u8> {
+        let mut bin = vec![];
+        bin.extend_from_slice(&self.flags.to_le_bytes());
+        for member in &self.members {
+            bin.extend_from_slice(&member.to_le_bytes());
+        }
+        return bin;
+    }
+}
+
+// split the content of a group section into its flags and members
+pub fn parse_group(bin: &[u8]) -> Result<(u32, Vec<u32>)> {
+    if bin.len() < 4 || bin.len() % 4 != 0 {
+        return Err(ParsingError::ParsingError);
+    }
+    let words: Vec<u32> = bin.chunks_exact(4).map(|w| LittleEndian::read_u32(w)).collect();
+    return Ok((words[0], words[1..].to_vec()));
+}
+
+impl Elf {
+    pub fn groups(&self) -> Result<Vec<Group>> {
+        let mut groups = vec![];
+
+        for (index, shdr) in self.shdrs.iter().enumerate() {
+            if shdr.sh_type != Shdr_type::GROUP {
+                continue;
+            }
+            let bin = self.raw.get(shdr.offset as usize..(shdr.offset + shdr.size) as usize).ok_or(ParsingError::ParsingError)?;
+            let (flags, members) = parse_group(&bin)?;
+
+            groups.push(Group {
+                index,
+                flags,
+                signature: self.group_signature(shdr)?,
+                symtab: shdr.link,
+                sym_index: shdr.info,
+                members
+            });
+        }
+
+        return Ok(groups);
+    }
+
+    // The name of the signature symbol. Assemblers use the section symbol of
+    // the group section when there is no symbol by that name, the signature
+    // is then the name of that section.
+    fn group_signature(&self, shdr: &shdr::SectionHeader) -> Result<String> {
+        let symtab = self.shdrs.get(shdr.link as usize).ok_or(ParsingError::ParsingError)?;
+        let syms = self.section_symbols(symtab)?;
+        let sym = syms.get(shdr.info as usize).ok_or(ParsingError::ParsingError)?;
+
+        if sym.sym_type() == Sym_type::SECTION {
+            return Ok(self.shdrs.get(sym.shndx as usize).map(|s| s.name.clone()).unwrap_or(String::new()));
+        }
+        return Ok(sym.name.clone());
+    }
+
+    // the group a section is a member of
+    pub fn section_group(&self, index: usize) -> Result<Option<Group>> {
+        return Ok(self.groups()?.into_iter().find(|g| g.members.contains(&(index as u32))));
+    }
+
+    // write the members and flags of a group back into its section
+    pub fn write_group(&mut self, group: &Group) -> Result<()> {
+        match self.shdrs.get(group.index) {
+            Some(shdr) if shdr.sh_type == Shdr_type::GROUP => {},
+            _ => return Err(ParsingError::ParsingError)
+        }
+        self.shdrs[group.index].link = group.symtab;
+        self.shdrs[group.index].info = group.sym_index;
+        return self.set_section_content(group.index, group.to_le());
+    }
+
+    // Take a section out of its group, before it gets removed. The linker
+    // rejects empty groups, so a group without members left is made an
+    // inactive SHT_NULL section and its index returned to be removed too.
+    pub fn remove_from_group(&mut self, index: usize) -> Result<Option<usize>> {
+        let mut group = match self.section_group(index)? {
+            Some(group) => group,
+            None => return Ok(None)
+        };
+
+        group.members.retain(|m| *m != index as u32);
+        self.write_group(&group)?;
+        self.shdrs[index].flags &= !(Shdr_flags::GROUP as u64);
+
+        if group.members.is_empty() {
+            self.set_section_content(group.index, vec![])?;
+            let shdr = &mut self.shdrs[group.index];
+            shdr.sh_type = Shdr_type::NULL;
+            shdr.link = 0;
+            shdr.info = 0;
+            shdr.entsize = 0;
+            shdr.flags = 0;
+            return Ok(Some(group.index));
+        }
+        return Ok(None);
+    }
+
+    // Fix up the members of all groups after section `removed` was taken out
+    // of the section header table, the sections after it move down by one.
+    // The sh_link of the groups has to be renumbered already, it is needed
+    // to find their signatures.
+    pub fn renumber_groups(&mut self, removed: usize) -> Result<()> {
+        for mut group in self.groups()? {
+            group.members.retain(|m| *m != removed as u32);
+            for member in &mut group.members {
+                if *member > removed as u32 {
+                    *member -= 1;
+                }
+            }
+            self.write_group(&group)?;
+        }
+        return Ok(());
+    }
+
+    // Give a section a new name, added to the section name table unless it
+    // is already in there. Groups refer to their members by index, so
+    // they stay intact, but a section whose name is the signature of a group
+    // can't be renamed as that would change the signature.
+    pub fn rename_section(&mut self, index: usize, name: &str) -> Result<()> {
+        if index >= self.shdrs.len() || index == 0 {
+            return Err(ParsingError::Refused(format!("there is no section with index {} to rename", index)));
+        }
+
+        for group in self.groups()? {
+            let shdr = &self.shdrs[group.index];
+            let symtab = self.shdrs.get(shdr.link as usize).ok_or(ParsingError::Refused(format!("the group {} has no symbol table", group.index)))?;
+            let sym = self.section_symbols(symtab)?.get(shdr.info as usize).cloned();
+            let sym = sym.ok_or(ParsingError::Refused(format!("the signature symbol {} of the group {} isn't in the symbol table", shdr.info, group.index)))?;
+            if sym.sym_type() == Sym_type::SECTION && sym.shndx as usize == index {
+                return Err(ParsingError::Refused(format!("the name of {} is the signature of the group {}, it can't be renamed", self.shdrs[index].name, group.index)));
+            }
+        }
+
+        let mut table = self.shstrtab()?;
+        let size = table.len();
+        let offset = table.add(name);
+        if table.len() != size {
+            self.set_section_content(self.header.shstrndx as usize, table.to_le())?;
+        }
+
+        self.shdrs[index].shstrndx_offset = offset as u32;
+        self.shdrs[index].name = String::from(name);
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::ElfBuilder;
+    use crate::{Elf, Elf_class, Elf_arch, Elf_type, ParsingError};
+    use crate::shdr::{Shdr_type, Shdr_flags};
+    use crate::symbol::{Sym_type, Sym_bind};
+
+    // an object with a COMDAT group named after its member, as assemblers
+    // write them when there is no symbol with the name of the group
+    fn object() -> Elf {
+        let mut b = ElfBuilder::new(Elf_class::ELF64, Elf_arch::AMD64, Elf_type::REL);
+        b.section(".group", Shdr_type::GROUP, 0, &[1, 0, 0, 0, 2, 0, 0, 0])
+         .section(".text.inline", Shdr_type::PROGBITS, Shdr_flags::ALLOC as u64 | Shdr_flags::EXECINSTR as u64 | Shdr_flags::GROUP as u64, &[0xc3])
+         .symbol("", ".text.inline", 0, 0, Sym_type::SECTION, Sym_bind::LOCAL);
+        let mut elf = b.build().unwrap();
+        let symtab = elf.section_index(".symtab").unwrap();
+        elf.shdrs[1].link = symtab as u32;
+        elf.shdrs[1].info = 1;
+        elf.shdrs[1].entsize = 4;
+        return Elf::parse(elf.to_le()).unwrap();
+    }
+
+    #[test]
+    fn groups() {
+        let elf = object();
+        let groups = elf.groups().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].is_comdat());
+        assert_eq!(groups[0].signature, ".text.inline");
+        assert_eq!(groups[0].members, vec![2]);
+        assert_eq!(elf.section_group(2).unwrap().unwrap().index, 1);
+    }
+
+    #[test]
+    fn rename_section() {
+        let mut elf = object();
+        for index in [0, 2, 10] {
+            match elf.rename_section(index, ".text.other") {
+                Err(ParsingError::Refused(_)) => {},
+                _ => panic!("section {} was renamed", index)
+            }
+        }
+
+        elf.rename_section(1, ".group.inline").unwrap();
+        let elf = Elf::parse(elf.to_le()).unwrap();
+        assert_eq!(elf.shdrs[1].name, ".group.inline");
+        assert_eq!(elf.groups().unwrap()[0].signature, ".text.inline");
+    }
+}
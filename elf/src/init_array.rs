@@ -0,0 +1,339 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_class;
+use crate::Elf_type;
+use crate::shdr::Shdr_type;
+use crate::phdr::Phdr_flags;
+use crate::dynamic::Dyn_tag;
+use crate::reloc;
+use crate::reloc::Relocation;
+use crate::reloc::Reloc_format;
+use crate::packed;
+use crate::symbol::Sym_type;
+
+use byteorder::*;
+
+// The arrays of function pointers the loader calls around main
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Init_kind {
+    PRE_INIT_ARRAY,
+    INIT_ARRAY,
+    FINI_ARRAY
+}
+
+#[derive(Clone, Debug)]
+pub struct InitEntry {
+    // the address of the pointer in the array
+    pub slot: u64,
+    // the function called, after relocation
+    pub target: u64,
+    pub symbol: Option<String>
+}
+
+fn tags(kind: Init_kind) -> (Dyn_tag, Dyn_tag, Shdr_type) {
+    return match kind {
+        Init_kind::PRE_INIT_ARRAY => (Dyn_tag::PREINIT_ARRAY, Dyn_tag::PREINIT_ARRAYSZ, Shdr_type::PRE_INIT_ARRAY),
+        Init_kind::INIT_ARRAY => (Dyn_tag::INIT_ARRAY, Dyn_tag::INIT_ARRAYSZ, Shdr_type::INIT_ARRAY),
+        Init_kind::FINI_ARRAY => (Dyn_tag::FINI_ARRAY, Dyn_tag::FINI_ARRAYSZ, Shdr_type::FINI_ARRAY)
+    }
+}
+
+fn word_size(class: Elf_class) -> u64 {
+    return match class {
+        Elf_class::ELF64 => 8,
+        Elf_class::ELF32 => 4
+    }
+}
+
+fn read_word(bin: &[u8], class: Elf_class) -> u64 {
+    return match class {
+        Elf_class::ELF64 => LittleEndian::read_u64(bin),
+        Elf_class::ELF32 => LittleEndian::read_u32(bin) as u64
+    }
+}
+
+fn word(val: u64, class: Elf_class) -> Vec<u8> {
+    return match class {
+        Elf_class::ELF64 => val.to_le_bytes().to_vec(),
+        Elf_class::ELF32 => (val as u32).to_le_bytes().to_vec()
+    }
+}
+
+// A relocation of an array slot with its addend made explicit, REL and RELR
+// keep it in the slot itself.
+struct SlotReloc {
+    index: u64,
+    r_type: u32,
+    sym: u32,
+    symbol: String,
+    addend: i64
+}
+
+impl Elf {
+    // the address and size of an array, from the dynamic table or else its section
+    pub fn init_array_range(&self, kind: Init_kind) -> Option<(u64, u64)> {
+        let (addr_tag, size_tag, sh_type) = tags(kind);
+
+        if let (Some(addr), Some(size)) = (self.dynamic_value(addr_tag), self.dynamic_value(size_tag)) {
+            return Some((addr, size));
+        }
+
+        let shdr = self.shdrs.iter().find(|s| s.sh_type == sh_type)?;
+        return Some((shdr.addr, shdr.size));
+    }
+
+    // the dynamic relocations applied to the slots of an array
+    fn slot_relocations(&self, addr: u64, size: u64) -> Result<Vec<SlotReloc>> {
+        let class = self.header.e_class;
+        let w = word_size(class);
+        let mut relocs = vec![];
+
+        for table in self.dynamic_relocations()? {
+            if table.name == "DT_JMPREL" {
+                continue;
+            }
+            for entry in &table.entries {
+                if entry.offset < addr || entry.offset >= addr + size {
+                    continue;
+                }
+                let implicit = self.read_vaddr(entry.offset, w).map(|b| read_word(&b, class)).unwrap_or(0);
+                relocs.push(SlotReloc {
+                    index: (entry.offset - addr) / w,
+                    r_type: entry.r_type,
+                    sym: entry.sym,
+                    symbol: entry.symbol.clone(),
+                    addend: if table.rela { entry.addend } else { implicit as i64 }
+                });
+            }
+        }
+
+        return Ok(relocs);
+    }
+
+    // The functions of an init, fini or preinit array. In position
+    // independent binaries the slots are resolved through their relocations.
+    pub fn init_functions(&self, kind: Init_kind) -> Result<Vec<InitEntry>> {
+        let class = self.header.e_class;
+        let w = word_size(class);
+        let (addr, size) = match self.init_array_range(kind) {
+            Some(range) => range,
+            None => return Ok(vec![])
+        };
+
+        let bin = self.read_vaddr(addr, size).ok_or(ParsingError::ParsingError)?;
+        let relocs = self.slot_relocations(addr, size)?;
+
+        let mut syms = self.symbols()?;
+        syms.extend(self.dynamic_symbols()?);
+        let relative = reloc::relative_type(self.header.e_arch);
+
+        let mut entries = vec![];
+        for (i, slot) in bin.chunks_exact(w as usize).enumerate() {
+            let mut target = read_word(slot, class);
+            let mut symbol = None;
+
+            if let Some(r) = relocs.iter().find(|r| r.index == i as u64) {
+                if r.r_type == relative || r.sym == 0 {
+                    target = r.addend as u64;
+                } else {
+                    target = self.symbol_address(&r.symbol).unwrap_or(0).wrapping_add(r.addend as u64);
+                    symbol = Some(r.symbol.clone());
+                }
+            }
+
+            if symbol.is_none() {
+                symbol = syms.iter().find(|s| s.value == target && target != 0 && s.sym_type() == Sym_type::FUNC).map(|s| s.name.clone());
+            }
+
+            entries.push(InitEntry {
+                slot: addr + i as u64 * w,
+                target,
+                symbol
+            });
+        }
+
+        return Ok(entries);
+    }
+
+    pub fn init_array(&self) -> Result<Vec<InitEntry>> {
+        return self.init_functions(Init_kind::INIT_ARRAY);
+    }
+
+    pub fn fini_array(&self) -> Result<Vec<InitEntry>> {
+        return self.init_functions(Init_kind::FINI_ARRAY);
+    }
+
+    pub fn preinit_array(&self) -> Result<Vec<InitEntry>> {
+        return self.init_functions(Init_kind::PRE_INIT_ARRAY);
+    }
+
+    // Run a function at startup, after the existing constructors. The
+    // array can't grow in place, so a copy with the new entry is injected in
+    // a new RW segment and DT_INIT_ARRAY(SZ) point to it. Position independent
+    // binaries also get a copy of DT_RELA (or DT_REL) there with relocations
+    // for the new slots, or of DT_RELR when that is the only table and the
+    // slots only need relative relocations. Returns the address of the new
+    // array.
+    pub fn add_constructor(&mut self, vaddr: u64) -> Result<u64> {
+        let class = self.header.e_class;
+        let w = word_size(class);
+        let (addr, size) = match (self.dynamic_value(Dyn_tag::INIT_ARRAY), self.dynamic_value(Dyn_tag::INIT_ARRAYSZ)) {
+            (Some(addr), Some(size)) => (addr, size),
+            _ => return Err(ParsingError::Refused(String::from("no DT_INIT_ARRAY to add a constructor to")))
+        };
+
+        let old = self.read_vaddr(addr, size).ok_or(ParsingError::Refused(format!("DT_INIT_ARRAY at {:#x} isn't mapped from the file", addr)))?.to_vec();
+        let mut relocs = self.slot_relocations(addr, size)?;
+        let pie = if let Elf_type::DYN = self.header.e_type { true } else { false };
+        let relative = reloc::relative_type(self.header.e_arch);
+
+        if pie {
+            relocs.push(SlotReloc {
+                index: size / w,
+                r_type: relative,
+                sym: 0,
+                symbol: String::new(),
+                addend: vaddr as i64
+            });
+        }
+
+        // the relocation table the new relocations are added to
+        let table = if relocs.is_empty() {
+            None
+        } else {
+            let only_relative = relocs.iter().all(|r| r.sym == 0 && r.r_type == relative);
+            let mut tables = self.dynamic_relocations()?;
+            let index = tables.iter().position(|t| t.name == "DT_RELA" || t.name == "DT_REL")
+                .or(tables.iter().position(|t| t.name == "DT_RELR" && only_relative));
+            let index = index.ok_or(ParsingError::Refused(String::from("no DT_RELA/DT_REL table to extend")))?;
+            Some(tables.remove(index))
+        };
+        let relr = table.as_ref().map(|t| t.format == Reloc_format::RELR).unwrap_or(false);
+
+        let array_size = size + w;
+        let table_start = (array_size + 7) / 8 * 8;
+        let added = match &table {
+            // an address entry and the bitmaps for the slots after it at most
+            Some(_) if relr => (1 + (relocs.len() as u64 + w * 8 - 3) / (w * 8 - 1)) * w,
+            Some(table) => relocs.len() as u64 * reloc::entry_size(class, table.rela) as u64,
+            None => 0
+        };
+        let total = table_start + table.as_ref().map(|t| t.size + added).unwrap_or(0);
+
+        let base = self.inject(&vec![0; total as usize], Phdr_flags::R as u32 | Phdr_flags::W as u32)?;
+
+        let mut array = old.clone();
+        array.extend(word(vaddr, class));
+
+        if let Some(table) = &table {
+            let table_addr = base + table_start;
+            let mut bin = if relr { vec![] } else { self.raw.get(table.offset as usize..(table.offset + table.size) as usize).ok_or(ParsingError::ParsingError)?.to_vec() };
+            let mut offsets: Vec<u64> = table.entries.iter().map(|r| r.offset).collect();
+
+            for r in &relocs {
+                let entry = Relocation {
+                    offset: base + r.index * w,
+                    r_type: r.r_type,
+                    sym: r.sym,
+                    addend: if table.rela { r.addend } else { 0 },
+                    symbol: r.symbol.clone()
+                };
+                if relr {
+                    offsets.push(entry.offset);
+                } else {
+                    bin.extend(entry.to_le(class, table.rela));
+                }
+
+                // REL and RELR keep the addend in the slot
+                if !table.rela {
+                    let at = (r.index * w) as usize;
+                    array.splice(at..at + w as usize, word(r.addend as u64, class));
+                }
+            }
+
+            if relr {
+                bin = packed::encode_relr(&offsets, class)?;
+                if bin.len() as u64 > table.size + added {
+                    return Err(ParsingError::ParsingError);
+                }
+                while (bin.len() as u64) < table.size + added {
+                    bin.extend(packed::relr_padding(class));
+                }
+            }
+            self.write_vaddr(table_addr, &bin)?;
+
+            let (addr_tag, size_tag, sh_type) = match table.format {
+                Reloc_format::RELR => (Dyn_tag::RELR, Dyn_tag::RELRSZ, Shdr_type::RELR),
+                _ if table.rela => (Dyn_tag::RELA, Dyn_tag::RELASZ, Shdr_type::RELA),
+                _ => (Dyn_tag::REL, Dyn_tag::RELSZ, Shdr_type::REL)
+            };
+            let old_table = self.dynamic_value(addr_tag).ok_or(ParsingError::Refused(format!("{} has no dynamic tag to point to its copy", table.name)))?;
+            self.set_dynamic_value(addr_tag, table_addr)?;
+            self.set_dynamic_value(size_tag, table.size + added)?;
+            self.move_section_header(sh_type, old_table, table_addr, table.size + added);
+        }
+
+        self.write_vaddr(base, &array)?;
+        self.set_dynamic_value(Dyn_tag::INIT_ARRAY, base)?;
+        self.set_dynamic_value(Dyn_tag::INIT_ARRAYSZ, array_size)?;
+        self.move_section_header(Shdr_type::INIT_ARRAY, addr, base, array_size);
+
+        return Ok(base);
+    }
+
+    // point the section header of a moved table to its new place
+    fn move_section_header(&mut self, sh_type: Shdr_type, old: u64, new: u64, size: u64) {
+        let offset = match self.vaddr_to_offset(new) {
+            Some(offset) => offset,
+            None => return
+        };
+
+        for shdr in &mut self.shdrs {
+            if shdr.addr == old && shdr.sh_type == sh_type {
+                shdr.addr = new;
+                shdr.offset = offset;
+                shdr.size = size;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic::Dyn_tag;
+    use crate::ParsingError;
+
+    #[test]
+    fn add_constructor() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let before = elf.init_array().unwrap();
+        let relasz = elf.dynamic_value(Dyn_tag::RELASZ).unwrap();
+        let main = elf.symbol_address("main").unwrap();
+        let array = elf.add_constructor(main).unwrap();
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        let after = elf.init_array().unwrap();
+        assert_eq!(after.len(), before.len() + 1);
+        assert_eq!(after[0].slot, array);
+        for (old, new) in before.iter().zip(after.iter()) {
+            assert_eq!((old.target, &old.symbol), (new.target, &new.symbol));
+        }
+        assert_eq!(after.last().unwrap().target, main);
+        assert_eq!(after.last().unwrap().symbol.as_deref(), Some("main"));
+
+        // every slot is relocated by the copy of DT_RELA
+        assert_eq!(elf.dynamic_value(Dyn_tag::RELASZ), Some(relasz + after.len() as u64 * 0x18));
+    }
+
+    #[test]
+    fn add_constructor_refused() {
+        // a static executable without a dynamic table
+        let bin = crate::builder::ElfBuilder::new(crate::Elf_class::ELF64, crate::Elf_arch::AMD64, crate::Elf_type::EXEC).build().unwrap().to_le();
+        let mut elf = crate::Elf::parse(bin).unwrap();
+        match elf.add_constructor(0x401000) {
+            Err(ParsingError::Refused(_)) => {},
+            _ => panic!("a constructor was added without DT_INIT_ARRAY")
+        }
+    }
+}
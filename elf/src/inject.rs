@@ -0,0 +1,307 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_arch;
+use crate::Elf_class;
+use crate::Elf_type;
+use crate::phdr;
+use crate::phdr::{ProgramHeader, Phdr_type};
+use crate::reloc;
+use crate::reloc::Reloc_format;
+
+use byteorder::*;
+
+// What a hooked import should be redirected to
+pub enum Hook {
+    // position independent code, injected into a new executable segment
+    Code(Vec<u8>),
+    // a virtual address already present in the binary
+    Address(u64),
+    // a function defined in the binary, looked up in .symtab and .dynsym
+    Function(String)
+}
+
+pub struct HookInfo {
+    // the address calls to the import now land on
+    pub target: u64,
+    // The JUMP_SLOT the loader still fills with the original function, so the
+    // replacement can call through to it.
+    pub original: Option<u64>,
+    // the PLT stubs that were patched
+    pub stubs: Vec<u64>
+}
+
+impl Elf {
+    // the alignment of the load segments, which new segments have to honour as well
+    pub fn load_align(&self) -> u64 {
+        let mut align = 0x1000;
+        for phdr in &self.phdrs {
+            if let Phdr_type::LOAD = phdr.p_type {
+                align = align.max(phdr.p_align);
+            }
+        }
+        return align;
+    }
+
+    // Pick a PT_NOTE we can turn into a load segment. Only a note whose
+    // range is also described by another PT_NOTE or by PT_GNU_PROPERTY is
+    // unused, the notes stay reachable through that one. GNU ld writes the
+    // .note.gnu.property note twice like that.
+    fn spare_note(&self) -> Option<usize> {
+        let is_note = |p: &ProgramHeader| if let Phdr_type::NOTE = p.p_type { true } else { false };
+        let covers = |p: &ProgramHeader| match p.p_type { Phdr_type::NOTE | Phdr_type::GNU_PROPERTY => true, _ => false };
+
+        return (0..self.phdrs.len()).find(|i| {
+            let note = &self.phdrs[*i];
+            is_note(note) && self.phdrs.iter().enumerate().any(|(j, p)| j != *i && covers(p) && p.offset <= note.offset && note.offset + note.filesz <= p.offset + p.filesz)
+        });
+    }
+
+    // Map new content into memory by appending it to the file in a new load
+    // segment. Returns the virtual address of the content.
+    pub fn inject(&mut self, content: &[u8], flags: u32) -> Result<u64> {
+        let align = self.load_align();
+        return self.add_segment(content, flags, align);
+    }
+
+    // Append a PT_LOAD mapping `content` above everything else in memory and
+    // return the virtual address of the content. The program header table
+    // needs an entry for it: a spare PT_NOTE is turned into the load segment,
+    // without one the table is moved to a place with room for it. `align` is
+    // raised to the alignment of the other load segments, the loader maps
+    // whole pages.
+    pub fn add_segment(&mut self, content: &[u8], flags: u32, align: u64) -> Result<u64> {
+        let note = self.spare_note();
+        match note {
+            Some(note) => { self.phdrs.remove(note); },
+            None if self.phdrs.len() >= self.header.phdr_num as usize => self.relocate_phdrs(1),
+            None => {}
+        }
+
+        let align = align.max(self.load_align());
+        let round = |v: u64| (v + align - 1) / align * align;
+        let end = self.phdrs.iter().filter(|p| if let Phdr_type::LOAD = p.p_type { true } else { false }).map(|p| p.vaddr + p.memsz).max().unwrap_or(0);
+        let vaddr = round(end);
+        let offset = round(self.raw.len() as u64);
+
+        self.raw.resize(offset as usize, 0);
+        self.raw.extend_from_slice(content);
+
+        // loaders expect the load segments sorted on their address, the new
+        // one has the highest so it goes right after the last of them
+        let load = ProgramHeader::new(Phdr_type::LOAD, flags, offset, vaddr, content.len() as u64, content.len() as u64, align);
+        let last_load = self.phdrs.iter().rposition(|p| if let Phdr_type::LOAD = p.p_type { true } else { false }).map(|i| i + 1).unwrap_or(self.phdrs.len());
+        self.phdrs.insert(last_load, load);
+
+        return Ok(vaddr);
+    }
+
+    // write bytes to the file content mapped at a virtual address
+    pub fn write_vaddr(&mut self, vaddr: u64, bin: &[u8]) -> Result<()> {
+        let offset = self.vaddr_to_offset(vaddr).ok_or(ParsingError::ParsingError)? as usize;
+        if offset + bin.len() > self.raw.len() {
+            return Err(ParsingError::ParsingError);
+        }
+        self.raw.splice(offset..offset + bin.len(), bin.iter().cloned());
+        return Ok(());
+    }
+
+    // the address of a symbol defined in this binary
+    pub fn symbol_address(&self, name: &str) -> Option<u64> {
+        let mut syms = self.symbols().ok()?;
+        syms.extend(self.dynamic_symbols().ok()?);
+        return syms.iter().find(|s| s.name == name && !s.is_undefined() && s.value != 0).map(|s| s.value);
+    }
+
+    // Redirect an imported function. Every PLT stub of the import is patched
+    // to jump to the replacement, and GOT slots used for direct calls
+    // (GLOB_DAT, e.g. from -fno-plt) are relocated to the replacement instead of
+    // the import. The JUMP_SLOT is left alone, so the original stays reachable.
+    pub fn hook(&mut self, symbol: &str, hook: Hook) -> Result<HookInfo> {
+        let plt = self.plt_entries()?;
+
+        // There has to be a stub or a slot to redirect, before anything is
+        // injected. A JUMP_SLOT alone doesn't count as it is left alone.
+        let slots = self.dynamic_relocations()?.iter().any(|t| t.format != Reloc_format::RELR && t.name != "DT_JMPREL" && t.entries.iter().any(|r| r.symbol == symbol));
+        if !plt.iter().any(|e| e.symbol == symbol) && !slots {
+            return Err(ParsingError::Refused(format!("no PLT stub or GOT slot of {} could be redirected", symbol)));
+        }
+
+        let target = match hook {
+            Hook::Code(code) => self.inject(&code, phdr::Phdr_flags::R as u32 | phdr::Phdr_flags::X as u32)?,
+            Hook::Address(addr) => addr,
+            Hook::Function(name) => self.symbol_address(&name).ok_or(ParsingError::Refused(format!("no symbol {}", name)))?
+        };
+
+        // patch the stubs to jump straight to the target
+        let mut stubs = vec![];
+        for entry in plt.iter().filter(|e| e.symbol == symbol) {
+            let patch = jump_stub(self.header.e_arch, entry.addr, target, self.read_vaddr(entry.addr, 4).as_deref())?;
+            self.write_vaddr(patch.0, &patch.1)?;
+            stubs.push(entry.addr);
+        }
+
+        // relocate the GOT slots that are not lazily bound
+        let class = self.header.e_class;
+        let relative = reloc::relative_type(self.header.e_arch);
+        let mut original = None;
+
+        for mut table in self.dynamic_relocations()? {
+            if table.format == Reloc_format::RELR {
+                continue;
+            }
+            if table.name == "DT_JMPREL" {
+                original = original.or(table.entries.iter().find(|r| r.symbol == symbol).map(|r| r.offset));
+                continue;
+            }
+
+            let mut changed = false;
+            for entry in table.entries.iter_mut().filter(|r| r.symbol == symbol) {
+                entry.sym = 0;
+                entry.symbol = String::new();
+                changed = true;
+
+                // a position dependent executable is loaded at its link address,
+                // so the slot can simply hold the target
+                if let Elf_type::EXEC = self.header.e_type {
+                    entry.r_type = 0;
+                    entry.addend = 0;
+                    self.write_vaddr(entry.offset, &word(target, class))?;
+                } else if table.rela {
+                    entry.r_type = relative;
+                    entry.addend = target as i64;
+                } else {
+                    entry.r_type = relative;
+                    self.write_vaddr(entry.offset, &word(target, class))?;
+                }
+            }
+
+            if changed {
+                self.write_relocations(&table)?;
+            }
+        }
+
+        return Ok(HookInfo {
+            target,
+            original,
+            stubs
+        });
+    }
+}
+
+fn word(val: u64, class: Elf_class) -> Vec<u8> {
+    return match class {
+        Elf_class::ELF64 => val.to_le_bytes().to_vec(),
+        Elf_class::ELF32 => (val as u32).to_le_bytes().to_vec()
+    }
+}
+
+// Assemble a jump from a PLT stub to the target, leaving a leading landing
+// pad (endbr64 or bti c) in place. Returns where to write and what.
+fn jump_stub(arch: Elf_arch, addr: u64, target: u64, head: Option<&[u8]>) -> Result<(u64, Vec<u8>)> {
+    let head = head.ok_or(ParsingError::ParsingError)?;
+
+    return match arch {
+        Elf_arch::AMD64 => {
+            let at = if head == [0xf3, 0x0f, 0x1e, 0xfa] { addr + 4 } else { addr };
+            let rel = target.wrapping_sub(at + 5) as i64;
+            if rel < i32::MIN as i64 || rel > i32::MAX as i64 {
+                return Err(ParsingError::Refused(format!("{:#x} is out of reach of a jump from the PLT stub at {:#x}", target, addr)));
+            }
+
+            let mut bin = vec![0xe9];
+            bin.extend_from_slice(&(rel as i32).to_le_bytes());
+            Ok((at, bin))
+        },
+        Elf_arch::AARCH64 => {
+            let at = if LittleEndian::read_u32(head) == 0xd503245f { addr + 4 } else { addr };
+            let rel = target.wrapping_sub(at) as i64;
+            if rel < -(1 << 27) || rel >= (1 << 27) {
+                return Err(ParsingError::Refused(format!("{:#x} is out of reach of a branch from the PLT stub at {:#x}", target, addr)));
+            }
+
+            let insn = 0x14000000 | ((rel >> 2) as u32 & 0x03ffffff);
+            Ok((at, insn.to_le_bytes().to_vec()))
+        },
+        _ => Err(ParsingError::Refused(format!("PLT stubs of machine {:#x} can't be patched", arch as u16)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::phdr::{Phdr_type, Phdr_flags};
+    use crate::inject::Hook;
+    use crate::ParsingError;
+    use byteorder::*;
+
+    #[test]
+    fn add_segment() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let phdr_num = elf.header.phdr_num;
+        // the first takes the place of the duplicate note, the second needs a bigger table
+        let a = elf.add_segment(b"first segment", Phdr_flags::R as u32, 0x1000).unwrap();
+        assert_eq!(elf.header.phdr_num, phdr_num);
+        let b = elf.add_segment(&[0xc3; 0x20], Phdr_flags::R as u32 | Phdr_flags::X as u32, 0x1000).unwrap();
+        assert!(b > a);
+
+        let bin = elf.to_le();
+        crate::verify::verify(&bin).unwrap();
+        let elf = crate::Elf::parse(bin).unwrap();
+        assert_eq!(elf.read_vaddr(a, 13).unwrap().to_vec(), b"first segment".to_vec());
+        assert_eq!(elf.read_vaddr(b, 0x20).unwrap().to_vec(), vec![0xc3; 0x20]);
+
+        let load = elf.phdrs.iter().find(|p| p.vaddr == b).unwrap();
+        assert!(if let Phdr_type::LOAD = load.p_type { true } else { false });
+        assert_eq!(load.flags, Phdr_flags::R as u32 | Phdr_flags::X as u32);
+        assert_eq!(load.offset % 0x1000, b % 0x1000);
+    }
+
+    // where the jump patched into a PLT stub goes, after an endbr64
+    fn jump_target(elf: &crate::Elf, stub: u64) -> u64 {
+        let at = if elf.read_vaddr(stub, 4).unwrap().to_vec() == [0xf3, 0x0f, 0x1e, 0xfa] { stub + 4 } else { stub };
+        let bin = elf.read_vaddr(at, 5).unwrap().to_vec();
+        assert_eq!(bin[0], 0xe9);
+        return (at + 5).wrapping_add(LittleEndian::read_i32(&bin[1..]) as i64 as u64);
+    }
+
+    #[test]
+    fn hook() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let main = elf.symbol_address("main").unwrap();
+        let puts = elf.hook("puts", Hook::Function(String::from("main"))).unwrap();
+        assert_eq!(puts.target, main);
+        assert!(!puts.stubs.is_empty());
+        assert!(puts.original.is_some());
+
+        let sleep = elf.hook("sleep", Hook::Code(vec![0x31, 0xc0, 0xc3])).unwrap();
+        // a GOT slot bound at load time is relocated to the target
+        elf.hook("__cxa_finalize", Hook::Address(main)).unwrap();
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        for stub in &puts.stubs {
+            assert_eq!(jump_target(&elf, *stub), main);
+        }
+        for stub in &sleep.stubs {
+            assert_eq!(jump_target(&elf, *stub), sleep.target);
+        }
+        assert_eq!(elf.read_vaddr(sleep.target, 3).unwrap().to_vec(), vec![0x31, 0xc0, 0xc3]);
+
+        let table = elf.dynamic_relocations().unwrap().into_iter().find(|t| t.name == "DT_RELA").unwrap();
+        assert!(!table.entries.iter().any(|r| r.symbol == "__cxa_finalize"));
+        assert!(table.entries.iter().any(|r| r.sym == 0 && r.addend == main as i64));
+    }
+
+    #[test]
+    fn hook_refused() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let hooks = [("printf", Hook::Address(0x1000)), ("puts", Hook::Function(String::from("missing")))];
+        for (symbol, hook) in hooks {
+            match elf.hook(symbol, hook) {
+                Err(ParsingError::Refused(_)) => {},
+                _ => panic!("{} was hooked", symbol)
+            }
+        }
+        // nothing was injected for the refused hooks
+        assert_eq!(elf.to_le(), std::fs::read("../test/testBin").unwrap());
+    }
+}
@@ -0,0 +1,418 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::shdr;
+use crate::phdr::Phdr_type;
+use crate::phdr::Phdr_flags;
+use crate::phdr::ProgramHeader;
+
+impl Elf {
+    // the end of the file content that is described by the program headers,
+    // which can't be moved without changing the memory image
+    pub fn fixed_end(&self) -> u64 {
+        let mut end = 0x40;
+        end = end.max(self.header.phdr_offset + self.header.phdr_num as u64 * self.header.phdr_size as u64);
+
+        for phdr in &self.phdrs {
+            end = end.max(phdr.offset + phdr.filesz);
+        }
+        return end;
+    }
+
+    // The sections after the fixed part of the file, sorted on their offset.
+    // These can be moved around freely.
+    fn tail_sections(&self) -> Vec<usize> {
+        let fixed = self.fixed_end();
+        let mut tail: Vec<usize> = (1..self.shdrs.len()).filter(|i| {
+            let shdr = &self.shdrs[*i];
+            shdr.sh_type != shdr::Shdr_type::NOBITS && shdr.offset >= fixed
+        }).collect();
+
+        tail.sort_by_key(|i| self.shdrs[*i].offset);
+        return tail;
+    }
+
+    // Lay out the tail of the file again: the movable sections are packed
+    // after the fixed part, honouring their alignment, followed by the section
+    // header table. `replace` swaps in new content for one section.
+    fn pack_tail(&mut self, replace: Option<(usize, Vec<u8>)>) -> Result<()> {
+        let fixed = self.fixed_end() as usize;
+        let tail = self.tail_sections();
+        let mut bin = self.raw.get(..fixed).ok_or(ParsingError::ParsingError)?.to_vec();
+        let mut replace = replace;
+
+        for i in tail {
+            let shdr = &self.shdrs[i];
+            let data = match &mut replace {
+                Some((index, content)) if *index == i => std::mem::replace(content, vec![]),
+                _ => self.raw.get(shdr.offset as usize..(shdr.offset + shdr.size) as usize).ok_or(ParsingError::ParsingError)?.to_vec()
+            };
+
+            let align = shdr.addralign.max(1) as usize;
+            bin.resize((bin.len() + align - 1) / align * align, 0);
+
+            self.shdrs[i].offset = bin.len() as u64;
+            self.shdrs[i].size = data.len() as u64;
+            bin.extend(data);
+        }
+
+        if self.header.shdr_offset as usize >= fixed {
+            bin.resize((bin.len() + 7) / 8 * 8, 0);
+            self.header.shdr_offset = bin.len() as u64;
+            bin.resize(bin.len() + self.shdrs.len() * self.header.shdr_size as usize, 0);
+        }
+
+        self.raw.replace(bin);
+        return Ok(());
+    }
+
+    // Replace the content of a section. A section in memory has to keep its
+    // size, as moving it would change the memory image. Other sections are
+    // moved: sections in the tail of the file are packed again, a section
+    // elsewhere gets its new content placed at the end of the file.
+    pub fn set_section_content(&mut self, index: usize, content: Vec<u8>) -> Result<()> {
+        let shdr = self.shdrs.get(index).ok_or(ParsingError::ParsingError)?.clone();
+
+        if shdr.sh_type == shdr::Shdr_type::NOBITS {
+            return Err(ParsingError::ParsingError);
+        }
+
+        if shdr.flags & shdr::Shdr_flags::ALLOC as u64 != 0 {
+            if content.len() as u64 != shdr.size {
+                return Err(ParsingError::ParsingError);
+            }
+            self.raw.splice(shdr.offset as usize..(shdr.offset + shdr.size) as usize, content);
+            return Ok(());
+        }
+
+        if self.tail_sections().contains(&index) {
+            return self.pack_tail(Some((index, content)));
+        }
+
+        // wipe the old content and append the new one
+        let start = shdr.offset as usize;
+        let end = ((shdr.offset + shdr.size) as usize).min(self.raw.len());
+        for byte in self.raw.bytes_mut().get_mut(start..end).unwrap_or(&mut []) {
+            *byte = 0;
+        }
+
+        let align = shdr.addralign.max(1) as usize;
+        let offset = (self.raw.len() + align - 1) / align * align;
+        self.raw.resize(offset, 0);
+        self.raw.extend_from_slice(&content);
+
+        self.shdrs[index].offset = offset as u64;
+        self.shdrs[index].size = self.raw.len() as u64 - offset as u64;
+        return Ok(());
+    }
+
+    // Move the program header table to the end of the file, into a load
+    // segment of its own above everything else in memory, with room for
+    // at least `extra` more entries. The table can't grow where it is, the
+    // content after it is mapped. The unused entries are written as PT_NULL.
+    // PT_PHDR follows, the loader hands its address to the program as AT_PHDR.
+    // A table that was moved before is in a load segment of its own already,
+    // that segment is taken over by the new table.
+    pub fn relocate_phdrs(&mut self, extra: usize) {
+        let page = self.load_align();
+        let round = |v: u64| (v + page - 1) / page * page;
+
+        let table_size = self.header.phdr_num as u64 * self.header.phdr_size as u64;
+        let phdr_offset = self.header.phdr_offset;
+        if let Some(i) = self.phdrs.iter().position(|p| if let Phdr_type::LOAD = p.p_type { p.offset == phdr_offset && p.filesz == table_size } else { false }) {
+            self.phdrs.remove(i);
+        }
+
+        let loads: Vec<&ProgramHeader> = self.phdrs.iter().filter(|p| if let Phdr_type::LOAD = p.p_type { true } else { false }).collect();
+        let end = loads.iter().map(|p| p.vaddr + p.memsz).max().unwrap_or(0);
+        let shdrs_end = self.header.shdr_offset + self.shdrs.len() as u64 * self.header.shdr_size as u64;
+        let mut vaddr = round(end);
+        let mut offset = round((self.raw.len() as u64).max(shdrs_end));
+
+        // Kernels before 5.18 find the table at the address of the first
+        // load segment plus e_phoff, so the new segment keeps the distance
+        // between file offset and address of the first one.
+        if let Some(first) = loads.iter().min_by_key(|p| p.vaddr) {
+            let base = first.vaddr.wrapping_sub(first.offset);
+            if base % page == 0 {
+                offset = offset.max(round(vaddr.wrapping_sub(base)));
+                vaddr = base.wrapping_add(offset);
+            }
+        }
+
+        // some spare entries, so adding a few more doesn't move it again
+        let slots = (self.phdrs.len() + 1 + extra + 7) / 8 * 8;
+        let size = slots as u64 * self.header.phdr_size as u64;
+        // the table is filled in when the file is written
+        self.raw.resize((offset + size) as usize, 0);
+
+        self.header.phdr_offset = offset;
+        self.header.phdr_num = slots as u16;
+        for phdr in &mut self.phdrs {
+            if let Phdr_type::PHDR = phdr.p_type {
+                phdr.offset = offset;
+                phdr.vaddr = vaddr;
+                phdr.paddr = vaddr;
+                phdr.filesz = size;
+                phdr.memsz = size;
+            }
+        }
+
+        let load = ProgramHeader::new(Phdr_type::LOAD, Phdr_flags::R as u32, offset, vaddr, size, size, page);
+        let last_load = self.phdrs.iter().rposition(|p| if let Phdr_type::LOAD = p.p_type { true } else { false }).map(|i| i + 1).unwrap_or(self.phdrs.len());
+        self.phdrs.insert(last_load, load);
+    }
+
+    pub fn section_index(&self, name: &str) -> Option<usize> {
+        return self.shdrs.iter().position(|shdr| shdr.name == name);
+    }
+}
+
+// A piece of the file that is moved as a whole: the file image of the load
+// segments with everything in them, or content outside of them. `residue`
+// is what the new offset has to be modulo `align`.
+struct Block {
+    start: u64,
+    end: u64,
+    align: u64,
+    residue: u64,
+    load: bool,
+    new_start: u64
+}
+
+impl Block {
+    fn new(start: u64, end: u64, align: u64, residue: u64, load: bool) -> Block {
+        let align = align.max(1);
+        Block {
+            start,
+            end,
+            align,
+            residue: residue % align,
+            load,
+            new_start: start
+        }
+    }
+
+    // Take in an overlapping block. The alignment of the merged block comes
+    // from the one with the largest, load segments first.
+    fn merge(&mut self, other: Block) {
+        let start = self.start.min(other.start);
+        let (mine, theirs) = ((self.load, self.align), (other.load, other.align));
+        let (align, residue, from) = if theirs > mine { (other.align, other.residue, other.start) } else { (self.align, self.residue, self.start) };
+
+        self.residue = (residue + align - (from - start) % align) % align;
+        self.align = align;
+        self.load |= other.load;
+        self.start = start;
+        self.end = self.end.max(other.end);
+    }
+
+    fn contains(&self, offset: u64, size: u64) -> bool {
+        return offset >= self.start && offset + size <= self.end;
+    }
+}
+
+// the first offset at or after `pos` that is `residue` modulo `align`
+fn congruent(pos: u64, align: u64, residue: u64) -> u64 {
+    let base = pos - pos % align + residue;
+    return if base < pos { base + align } else { base };
+}
+
+// the new place of a range, from the block it lies in
+fn remap(blocks: &[Block], offset: u64, size: u64) -> Option<u64> {
+    let block = blocks.iter().find(|b| b.contains(offset, size))?;
+    return Some(block.new_start + offset - block.start);
+}
+
+impl Elf {
+    // Lay out the whole file again from the program and section headers.
+    // The load segments keep their content together and are placed so that
+    // p_offset is p_vaddr modulo p_align, sections outside of them honour
+    // sh_addralign. Gaps are dropped, all offsets in the headers are
+    // updated and the section header table is placed at the end.
+    pub fn layout(&mut self) -> Result<()> {
+        if self.phdrs.len() > self.header.phdr_num as usize {
+            self.relocate_phdrs(0);
+        }
+        // spare entries of the table are kept for the next segments
+        self.header.phdr_num = self.header.phdr_num.max(self.phdrs.len() as u16);
+        let header = &self.header;
+        let table_size = header.phdr_num as u64 * header.phdr_size as u64;
+
+        let mut parts = vec![Block::new(0, header.size as u64, 1, 0, false)];
+        if !self.phdrs.is_empty() {
+            parts.push(Block::new(header.phdr_offset, header.phdr_offset + table_size, 8, header.phdr_offset, false));
+        }
+        for phdr in &self.phdrs {
+            if phdr.filesz == 0 {
+                continue;
+            }
+            match phdr.p_type {
+                Phdr_type::LOAD => parts.push(Block::new(phdr.offset, phdr.offset + phdr.filesz, phdr.p_align, phdr.vaddr, true)),
+                _ => parts.push(Block::new(phdr.offset, phdr.offset + phdr.filesz, phdr.p_align, phdr.offset, false))
+            }
+        }
+        for shdr in self.shdrs.iter().skip(1) {
+            if shdr.sh_type != shdr::Shdr_type::NOBITS && shdr.size > 0 {
+                parts.push(Block::new(shdr.offset, shdr.offset + shdr.size, shdr.addralign, shdr.offset, false));
+            }
+        }
+
+        // join the overlapping parts
+        parts.sort_by_key(|b| b.start);
+        let mut blocks: Vec<Block> = vec![];
+        for part in parts {
+            match blocks.last_mut() {
+                Some(last) if part.start < last.end => last.merge(part),
+                _ => blocks.push(part)
+            }
+        }
+
+        // Kernels before 5.18 take AT_PHDR to be the address of the first
+        // load segment plus e_phoff. A table in a load segment of its own has
+        // to stay as far from the first load segment in the file as it is in
+        // memory, see relocate_phdrs.
+        let is_load = |p: &&ProgramHeader| if let Phdr_type::LOAD = p.p_type { true } else { false };
+        let first = self.phdrs.iter().filter(is_load).min_by_key(|p| p.vaddr).cloned();
+        let table = self.phdrs.iter().filter(is_load)
+            .find(|p| self.header.phdr_offset >= p.offset && self.header.phdr_offset + table_size <= p.offset + p.filesz)
+            .map(|p| p.vaddr + self.header.phdr_offset - p.offset);
+
+        let mut bin = vec![];
+        for i in 0..blocks.len() {
+            let block = &blocks[i];
+            let mut start = if block.start == 0 { 0 } else { congruent(bin.len() as u64, block.align, block.residue) };
+
+            if let (Some(first), Some(vaddr)) = (&first, table) {
+                let phdr_offset = self.header.phdr_offset;
+                if block.contains(phdr_offset, table_size) && !block.contains(first.offset, first.filesz) {
+                    let base = remap(&blocks[..i], first.offset, first.filesz).map(|o| first.vaddr.wrapping_sub(o));
+                    if let Some(base) = base {
+                        let pinned = vaddr.wrapping_sub(base).wrapping_sub(phdr_offset - block.start);
+                        if pinned >= bin.len() as u64 && pinned % block.align == block.residue {
+                            start = pinned;
+                        }
+                    }
+                }
+            }
+
+            bin.resize(start as usize, 0);
+            bin.extend_from_slice(&self.raw.get(block.start as usize..block.end as usize).ok_or(ParsingError::ParsingError)?);
+            blocks[i].new_start = start;
+        }
+
+        for phdr in &mut self.phdrs {
+            if let Some(offset) = remap(&blocks, phdr.offset, phdr.filesz) {
+                phdr.offset = offset;
+            } else if phdr.filesz == 0 {
+                // nothing in the file, keep it in range
+                phdr.offset = phdr.offset.min(bin.len() as u64);
+            } else {
+                return Err(ParsingError::ParsingError);
+            }
+        }
+        for shdr in self.shdrs.iter_mut().skip(1) {
+            let size = if shdr.sh_type == shdr::Shdr_type::NOBITS { 0 } else { shdr.size };
+            if let Some(offset) = remap(&blocks, shdr.offset, size) {
+                shdr.offset = offset;
+            } else if size == 0 {
+                shdr.offset = shdr.offset.min(bin.len() as u64);
+            } else {
+                return Err(ParsingError::ParsingError);
+            }
+        }
+
+        if !self.phdrs.is_empty() {
+            self.header.phdr_offset = remap(&blocks, self.header.phdr_offset, 0).ok_or(ParsingError::ParsingError)?;
+        } else {
+            self.header.phdr_offset = 0;
+        }
+
+        // PT_PHDR describes the table itself
+        let (phdr_offset, phdr_size) = (self.header.phdr_offset, table_size);
+        for phdr in &mut self.phdrs {
+            if let Phdr_type::PHDR = phdr.p_type {
+                phdr.offset = phdr_offset;
+                phdr.filesz = phdr_size;
+                phdr.memsz = phdr_size;
+            }
+        }
+
+        if self.shdrs.is_empty() {
+            self.header.shdr_offset = 0;
+            self.header.shdr_num = 0;
+            self.header.shstrndx = 0;
+        } else {
+            bin.resize((bin.len() + 7) / 8 * 8, 0);
+            self.header.shdr_offset = bin.len() as u64;
+            self.header.shdr_num = self.shdrs.len() as u16;
+            bin.resize(bin.len() + self.shdrs.len() * self.header.shdr_size as usize, 0);
+
+            if self.header.shstrndx as usize >= self.shdrs.len() {
+                self.header.shstrndx = self.section_index(".shstrtab").unwrap_or(0) as u16;
+            }
+        }
+
+        self.raw.replace(bin);
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::phdr::Phdr_type;
+    use crate::shdr::{Shdr_type, Shdr_flags};
+
+    fn section_contents(elf: &crate::Elf) -> Vec<(String, u64, Vec<u8>)> {
+        return elf.shdrs.iter().map(|s| (s.name.clone(), s.addr, elf.section(&s.name).map(|s| s.content()).unwrap_or(vec![]))).collect();
+    }
+
+    #[test]
+    fn layout_keeps_content() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let before = section_contents(&elf);
+        elf.layout().unwrap();
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        assert_eq!(section_contents(&elf), before);
+        for phdr in &elf.phdrs {
+            if let Phdr_type::LOAD = phdr.p_type {
+                assert_eq!(phdr.offset % phdr.p_align, phdr.vaddr % phdr.p_align);
+            }
+        }
+    }
+
+    #[test]
+    fn set_section_content() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let symtab = elf.section(".symtab").unwrap().content();
+        let comment = elf.section_index(".comment").unwrap();
+        elf.set_section_content(comment, b"a longer comment than the compiler wrote there\0".to_vec()).unwrap();
+
+        // allocated sections keep their size
+        let text = elf.section_index(".text").unwrap();
+        assert!(elf.set_section_content(text, vec![0x90; 4]).is_err());
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        assert_eq!(elf.section(".comment").unwrap().content(), b"a longer comment than the compiler wrote there\0".to_vec());
+        assert_eq!(elf.section(".symtab").unwrap().content(), symtab);
+    }
+
+    #[test]
+    fn relocate_phdrs_once() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        for i in 0..4 {
+            elf.add_section(&format!(".extra{}", i), Shdr_type::PROGBITS, Shdr_flags::ALLOC as u64, b"extra").unwrap();
+        }
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        let table_size = elf.header.phdr_num as u64 * elf.header.phdr_size as u64;
+        let is_load = |p: &&crate::phdr::ProgramHeader| if let Phdr_type::LOAD = p.p_type { true } else { false };
+        let tables: Vec<_> = elf.phdrs.iter().filter(is_load).filter(|p| p.offset == elf.header.phdr_offset && p.filesz == table_size).collect();
+        assert_eq!(tables.len(), 1);
+
+        // AT_PHDR for older kernels is the first load segment plus e_phoff
+        let first = elf.phdrs.iter().filter(is_load).min_by_key(|p| p.vaddr).unwrap();
+        assert_eq!(tables[0].vaddr, first.vaddr - first.offset + elf.header.phdr_offset);
+    }
+}
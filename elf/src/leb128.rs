@@ -0,0 +1,71 @@
+// LEB128 variable length integers, used by packed relocations and DWARF.
+// The readers take a cursor into the buffer and advance it past the value.
+
+pub fn read_uleb128(bin: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut result: u64 = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = *bin.get(*pos)?;
+        *pos += 1;
+
+        if shift < 64 {
+            result |= ((byte & 0x7f) as u64) << shift;
+        }
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+}
+
+pub fn read_sleb128(bin: &[u8], pos: &mut usize) -> Option<i64> {
+    let mut result: i64 = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = *bin.get(*pos)?;
+        *pos += 1;
+
+        if shift < 64 {
+            result |= ((byte & 0x7f) as i64) << shift;
+        }
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            // sign extend
+            if shift < 64 && (byte & 0x40) != 0 {
+                result |= -1i64 << shift;
+            }
+            return Some(result);
+        }
+    }
+}
+
+pub fn write_uleb128(bin: &mut Vec<u8>, mut val: u64) {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+
+        if val == 0 {
+            bin.push(byte);
+            return;
+        }
+        bin.push(byte | 0x80);
+    }
+}
+
+pub fn write_sleb128(bin: &mut Vec<u8>, mut val: i64) {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+
+        let done = (val == 0 && byte & 0x40 == 0) || (val == -1 && byte & 0x40 != 0);
+        if done {
+            bin.push(byte);
+            return;
+        }
+        bin.push(byte | 0x80);
+    }
+}
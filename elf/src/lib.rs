@@ -4,6 +4,10 @@ use std::fs;
 use std::error;
 use std::fmt; 
 use std::io::Cursor; 
+use std::io::Write; 
+use std::io::Seek; 
+use std::io::SeekFrom; 
+use std::sync::Arc; 
 use byteorder::*; 
 use std::slice::SliceIndex; 
 use std::convert::TryInto; 
@@ -12,14 +16,42 @@ pub mod phdr;
 pub mod shdr; 
 pub mod segment; 
 pub mod section; 
+pub mod dynamic; 
+pub mod symbol; 
+pub mod reloc; 
+pub mod packed; 
+pub mod leb128; 
+pub mod plt; 
+pub mod inject; 
+pub mod note; 
+pub mod debuginfo; 
+pub mod compress; 
+pub mod layout; 
+pub mod dwarf; 
+pub mod line; 
+pub mod types; 
+pub mod eh_frame; 
+pub mod init_array; 
+pub mod coredump; 
+pub mod group; 
+pub mod strtab; 
+pub mod buffer; 
+pub mod builder; 
+pub mod verify; 
 
 use segment::Segment;
 use section::Section;
+use buffer::Buffer;
+use std::cell::Ref;
 
 #[derive(Debug, Clone)]
 pub enum ParsingError {
     NotElf,
-    ParsingError
+    ParsingError,
+    // an edit that would break the file, and why
+    Refused(String),
+    // reading or writing the file failed
+    Io(Arc<std::io::Error>)
 }
 
 type Result<T> = std::result::Result<T, ParsingError>; 
@@ -27,14 +59,28 @@ type Result<T> = std::result::Result<T, ParsingError>;
 
 impl fmt::Display for ParsingError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "invalid first item to double")
+        match self {
+            ParsingError::Refused(reason) => write!(f, "{}", reason),
+            ParsingError::Io(e) => write!(f, "{}", e),
+            ParsingError::NotElf => write!(f, "not an ELF file"),
+            ParsingError::ParsingError => write!(f, "the file is malformed or uses a feature that isn't supported")
+        }
     }
 }
 
 impl error::Error for ParsingError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         // Generic error, underlying cause isn't tracked.
-        None
+        match self {
+            ParsingError::Io(e) => Some(e.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl From<std::io::Error> for ParsingError {
+    fn from(e: std::io::Error) -> ParsingError {
+        ParsingError::Io(Arc::new(e))
     }
 }
 
@@ -51,7 +97,7 @@ pub enum Elf_type {
     HIPROC = 0xffff
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum Elf_class {
     ELF32 = 1,
     ELF64 = 2
@@ -63,7 +109,7 @@ pub enum Elf_endiannes {
     BigEndian = 2
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum Elf_arch {
     NONE    = 0x0,
     SPARC   = 0x2,
@@ -101,18 +147,19 @@ pub enum Elf_abi {
     OpenVOS = 0x12
 }
 
+#[derive(Clone)]
 pub struct Elf_header {
     e_ident: [u8;4],
-    e_class: Elf_class, 
+    pub e_class: Elf_class, 
     e_endianness: Elf_endiannes,
     ei_version: u8, 
     e_abi: Elf_abi,
     e_abi_version: u8,
     e_padding: [u8;7],
     pub e_type: Elf_type,
-    e_arch: Elf_arch,
+    pub e_arch: Elf_arch,
     e_version: u32,
-    e_entry: u64,
+    pub e_entry: u64,
     e_flags: u32, 
     size: u16,
     pub phdr_offset: u64,
@@ -121,28 +168,49 @@ pub struct Elf_header {
     pub shdr_offset: u64,
     pub shdr_size: u16,
     pub shdr_num: u16,
-    pub shstrndx: u16
+    pub shstrndx: u16,
+    // The values as read from the file. The enums fold values they don't
+    // know into one variant, these are written instead unless the field was
+    // changed, so an unmodified header is written back as it was read.
+    raw_class: u8,
+    raw_endianness: u8,
+    raw_abi: u8,
+    raw_type: u16,
+    raw_arch: u16
 }
 
 impl Elf_header {
 
-    pub fn to_le(self) -> Vec<u8> {
+    pub fn to_le(&self) -> Vec<u8> {
         let mut bin = vec![]; 
 
+        let class = if elf_class(self.raw_class) == self.e_class { self.raw_class } else { self.e_class as u8 };
+        let endianness = if elf_endianness(self.raw_endianness) as u8 == self.e_endianness as u8 { self.raw_endianness } else { self.e_endianness as u8 };
+        let abi = if elf_abi(self.raw_abi) as u8 == self.e_abi as u8 { self.raw_abi } else { self.e_abi as u8 };
+        let e_type = if elf_type(self.raw_type) as u16 == self.e_type as u16 { self.raw_type } else { self.e_type as u16 };
+        let arch = if elf_arch(self.raw_arch) == self.e_arch { self.raw_arch } else { self.e_arch as u16 };
+
         // ASSEMBLE THE ELF HEADER 
         bin.extend_from_slice(&self.e_ident);  
-        bin.extend_from_slice(&(self.e_class as u8).to_le_bytes()); 
-        bin.extend_from_slice(&(self.e_endianness as u8).to_le_bytes()); 
+        bin.push(class); 
+        bin.push(endianness); 
         bin.extend_from_slice(&self.ei_version.to_le_bytes()); 
-        bin.extend_from_slice(&(self.e_abi as u8).to_le_bytes()); 
+        bin.push(abi); 
         bin.extend_from_slice(&[self.e_abi_version]);
         bin.extend_from_slice(&self.e_padding);
-        bin.extend_from_slice(&(self.e_type as u16).to_le_bytes()); 
-        bin.extend_from_slice(&(self.e_arch as u16).to_le_bytes()); 
+        bin.extend_from_slice(&e_type.to_le_bytes()); 
+        bin.extend_from_slice(&arch.to_le_bytes()); 
         bin.extend_from_slice(&self.e_version.to_le_bytes()); 
-        bin.extend_from_slice(&self.e_entry.to_le_bytes()); 
-        bin.extend_from_slice(&self.phdr_offset.to_le_bytes()); 
-        bin.extend_from_slice(&self.shdr_offset.to_le_bytes()); 
+        // the addresses and offsets are 4 bytes in 32-bit files
+        if let Elf_class::ELF32 = self.e_class {
+            bin.extend_from_slice(&(self.e_entry as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.phdr_offset as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.shdr_offset as u32).to_le_bytes()); 
+        } else {
+            bin.extend_from_slice(&self.e_entry.to_le_bytes()); 
+            bin.extend_from_slice(&self.phdr_offset.to_le_bytes()); 
+            bin.extend_from_slice(&self.shdr_offset.to_le_bytes()); 
+        }
         bin.extend_from_slice(&self.e_flags.to_le_bytes()); 
         bin.extend_from_slice(&self.size.to_le_bytes()); 
         bin.extend_from_slice(&self.phdr_size.to_le_bytes()); 
@@ -161,77 +229,150 @@ pub struct Elf {
     pub header: Elf_header,    // pub program_hdrs: Vec<phdr::ProgramHeader>,
     pub phdrs: Vec<phdr::ProgramHeader>,
     pub shdrs: Vec<shdr::SectionHeader>,
-    pub raw: Vec<u8>
+    pub raw: Buffer,
+    // a separate debug file, see debuginfo.rs
+    pub debug: Option<Box<Elf>>,
+    // sections to store compressed or uncompressed when writing, see compress.rs
+    pub write_compression: Vec<(String, compress::Compression)>
     // pub segments: Vec<Segment>,
     // pub section_hdrs: Vec<shdr::SectionHeader>,
 }
 
 impl Elf {
-    // return the elf as a binary file
-    pub fn to_le(mut self) -> Vec<u8> {
-        // let mut bin = vec![];
+    // Write the file to `w`, from its current position. The content stays
+    // where it is, `layout` places it again after structural edits. It goes
+    // out as it is and the header tables are written over it afterwards.
+    // The Elf is left untouched, so it can be edited and written again.
+    pub fn write_to<W: Write + Seek>(&self, w: &mut W) -> std::io::Result<()> {
+        // sections to compress and a full program header table that has to
+        // move, that is done on a copy
+        if self.phdrs.len() > self.header.phdr_num as usize || !self.write_compression.is_empty() {
+            let mut elf = Elf {
+                header: self.header.clone(),
+                phdrs: self.phdrs.clone(),
+                shdrs: self.shdrs.clone(),
+                raw: Buffer::new(self.raw.to_vec()),
+                debug: None,
+                write_compression: self.write_compression.clone()
+            };
+            elf.apply_write_compression().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            if elf.phdrs.len() > elf.header.phdr_num as usize {
+                elf.relocate_phdrs(0);
+            }
+            return elf.write_to(w);
+        }
 
-        // // bin.resize(segment::get_segments_size(&self.segments) as usize, 0);
+        let mut header = self.header.clone(); 
+        let class = header.e_class; 
+        let mut tables = vec![]; 
 
-        
-        // // We need to create a new shstrndx using the segments 
+        // add section headers
+        if header.shdr_offset != 0 {
+            header.shdr_num = self.shdrs.len() as u16; 
+            tables.push((header.shdr_offset, shdr::to_le(&self.shdrs, class))); 
+        }
 
+        // add elf header
+        tables.push((0, header.to_le())); 
 
-        // // get segment blob
-        // // TODO: We need to take nested segments into account.
-        // let segment_blob = segment::get_segments_blob(&self.segments);  
-        
-        // // TODO: calculate the elf header size, program header and section headers.
-        // let ehdr_offset = 0x0; 
-        // let phdr_offset = 0x40; 
-        // let segment_offset = phdr_offset+segment::phdrs_size(&self.segments);
-        // let shdr_offset = segment_offset+segment_blob.len(); 
-
-        // // TODO: Set the offsets to be file offsets instead of local offsets
-        // self.header.phdr_offset = phdr_offset as u64; 
-        // self.header.phdr_num = self.segments.len() as u16; 
-        
-        // self.header.shdr_offset = shdr_offset as u64; 
-        // self.header.shdr_num = segment::shdrs_len(&self.segments) as u16; 
+        // add program headers 
+        // the unused entries of a table with room to spare are PT_NULL
+        let mut phdrs = self.phdrs.clone();
+        phdrs.resize(header.phdr_num as usize, phdr::ProgramHeader::new(phdr::Phdr_type::NULL, 0, 0, 0, 0, 0, 0));
+        tables.push((header.phdr_offset, phdr::to_le(phdrs, class))); 
+
+        let start = w.seek(SeekFrom::Current(0))?; 
+        let raw = self.raw.bytes(); 
+        w.write_all(&raw)?; 
+
+        // a table past the end of the content makes the file longer
+        let size = tables.iter().map(|(offset, bin)| *offset + bin.len() as u64).fold(raw.len() as u64, u64::max); 
+        if size > raw.len() as u64 {
+            w.write_all(&vec![0; (size - raw.len() as u64) as usize])?; 
+        }
 
+        for (offset, bin) in tables {
+            w.seek(SeekFrom::Start(start + offset))?; 
+            w.write_all(&bin)?; 
+        }
+        w.seek(SeekFrom::Start(start + size))?; 
+        return Ok(()); 
+    }
 
-        // // - we need to have dynamic program header offsets as well -- implemented?
+    // return the elf as a binary file
+    pub fn to_le(&self) -> Vec<u8> {
+        let mut bin = Cursor::new(vec![]); 
+        self.write_to(&mut bin).expect("Failed to write to memory"); 
+        return bin.into_inner(); 
+    }
 
-        
-        
-        // // alterations to the elf_headers offsets of section headers and program headers should be made before getting the blob 
-        // // - change phdrs offset -- implemented
-        // // - change shdrs offset -- implemented
-        // let phdrs_blob = segment::get_phdrs_blob(&self.segments, segment_offset);         
-        // let shdrs_blob = segment::get_shdrs_blob(&self.segments);         
-        // let ehdr_blob = self.header.to_le(); 
+    pub fn write_file(&self, path: &str) -> Result<()> {
+        let mut file = std::io::BufWriter::new(fs::File::create(path)?); 
+        self.write_to(&mut file)?; 
+        file.flush()?; 
+        return Ok(()); 
+    }    
+}
 
+impl Elf {
+    // find a section header by its name
+    pub fn section_header(&self, name: &str) -> Option<&shdr::SectionHeader> {
+        return self.shdrs.iter().find(|shdr| shdr.name == name); 
+    }
+
+    // get a section including its content. Sections that are missing, or
+    // have no content in this file, are taken from the debug file if one is attached.
+    pub fn section(&self, name: &str) -> Option<Section> {
+        // .debug_* may be stored as a legacy compressed .zdebug_* section
+        let hdr = match (self.section_header(name), name.strip_prefix(".debug")) {
+            (None, Some(rest)) => self.section_header(&format!(".zdebug{}", rest)),
+            (hdr, _) => hdr
+        }; 
         
+        if let Some(hdr) = hdr {
+            if let shdr::Shdr_type::NOBITS = hdr.sh_type {} else {
+                return Some(Section::from(hdr.clone(), &self.raw)); 
+            }
+        }
 
-        // bin.extend(ehdr_blob); 
-        // bin.extend(phdrs_blob); 
-        // bin.extend(segment_blob); 
-        // bin.extend(shdrs_blob); 
-        let phdrt_start = self.header.phdr_offset; 
-        let phdrt_end = phdrt_start + (self.header.phdr_num*self.header.phdr_size) as u64; 
-        let phdrt_size = phdrt_end-phdrt_start; 
-    
-        // add elf header
-        self.raw.splice(0..0x40, self.header.to_le()); 
+        if let Some(debug) = &self.debug {
+            if let Some(section) = debug.section(name) {
+                return Some(section); 
+            }
+        }
 
-        // add program headers 
-        self.raw.splice(phdrt_start as usize..phdrt_end as usize, phdr::to_le(self.phdrs) ); 
+        return Some(Section::from(hdr?.clone(), &self.raw)); 
+    }
 
-        return self.raw;
+    // translate a virtual address into a file offset using the load segments
+    pub fn vaddr_to_offset(&self, vaddr: u64) -> Option<u64> {
+        for phdr in &self.phdrs {
+            if let phdr::Phdr_type::LOAD = phdr.p_type {
+                if vaddr >= phdr.vaddr && vaddr < phdr.vaddr + phdr.filesz {
+                    return Some(vaddr - phdr.vaddr + phdr.offset); 
+                }
+            }
+        }
+        return None; 
     }
 
-    pub fn write_file(self, path: &str) -> Result<()> {
-        let bin = self.to_le(); 
-        match fs::write(path, bin) {
-            Ok(res) => Ok(res),
-            Err(_) => return Err(ParsingError::ParsingError)
+    // translate a file offset into a virtual address using the load segments
+    pub fn offset_to_vaddr(&self, offset: u64) -> Option<u64> {
+        for phdr in &self.phdrs {
+            if let phdr::Phdr_type::LOAD = phdr.p_type {
+                if offset >= phdr.offset && offset < phdr.offset + phdr.filesz {
+                    return Some(offset - phdr.offset + phdr.vaddr); 
+                }
+            }
         }
-    }    
+        return None; 
+    }
+
+    // get `size` bytes of file content mapped at `vaddr`
+    pub fn read_vaddr(&self, vaddr: u64, size: u64) -> Option<Ref<'_, [u8]>> {
+        let offset = self.vaddr_to_offset(vaddr)? as usize; 
+        return self.raw.get(offset..offset + size as usize); 
+    }
 }
 
 fn pad(size: u32) -> Vec<u8> {
@@ -240,15 +381,17 @@ fn pad(size: u32) -> Vec<u8> {
 
 impl Elf {
     fn parse(bin: Vec<u8>) -> Result<Elf> {
-        let shstrndx = LittleEndian::read_u16(&bin[0x3E..0x40]); 
+        let header = Elf_header::parse(&bin)?; 
         let program_hdrs = phdr::parse_program_header(&bin)?;
-        let section_hdrs = shdr::parse_section_header(&bin, shstrndx)?; 
+        let section_hdrs = shdr::parse_section_header(&bin, header.shstrndx)?; 
 
         return Ok(Elf {
-            header: Elf_header::parse(&bin)?,
+            header,
             phdrs:program_hdrs,
             shdrs: section_hdrs,
-            raw: bin
+            raw: Buffer::new(bin),
+            debug: None,
+            write_compression: vec![]
         })
     }
 }
@@ -256,6 +399,42 @@ impl Elf {
 
 
 impl Elf_header {
+    // a header for a new little endian file, the tables are filled in later
+    pub fn new(e_class: Elf_class, e_arch: Elf_arch, e_type: Elf_type, e_entry: u64) -> Elf_header {
+        // the sizes of the ELF header, a program header and a section header
+        let (size, phdr_size, shdr_size) = match e_class {
+            Elf_class::ELF64 => (0x40, 0x38, 0x40),
+            Elf_class::ELF32 => (0x34, 0x20, 0x28)
+        };
+        Elf_header {
+            e_ident: [0x7F, 0x45, 0x4C, 0x46],
+            e_class,
+            e_endianness: Elf_endiannes::LittleEndian,
+            ei_version: 1,
+            e_abi: Elf_abi::NONE,
+            e_abi_version: 0,
+            e_padding: [0; 7],
+            e_type,
+            e_arch,
+            e_version: 1,
+            e_entry,
+            e_flags: 0,
+            size,
+            phdr_offset: 0,
+            phdr_size,
+            phdr_num: 0,
+            shdr_offset: 0,
+            shdr_size,
+            shdr_num: 0,
+            shstrndx: 0,
+            raw_class: e_class as u8,
+            raw_endianness: Elf_endiannes::LittleEndian as u8,
+            raw_abi: Elf_abi::NONE as u8,
+            raw_type: e_type as u16,
+            raw_arch: e_arch as u16
+        }
+    }
+
     fn parse(bin: &Vec<u8>) -> Result<Elf_header> {
         
         if !is_elf(&bin) {
@@ -273,18 +452,19 @@ impl Elf_header {
         let e_version = LittleEndian::read_u32(&bin[0x14..0x18]);
         let e_arch = parse_arch(&bin);
         let e_type = parse_type(&bin);
-        let e_entry = parse_entry64(&bin);
-        let shstrndx = LittleEndian::read_u16(&bin[0x3E..0x40]); 
-        let e_flags = LittleEndian::read_u32(&bin[0x30..0x34]);
-        let size = LittleEndian::read_u16(&bin[0x34..0x36]);
-        let phdr_offset = LittleEndian::read_u64(&bin[0x20..0x28]);
-        let phdr_size = LittleEndian::read_u16(&bin[0x36..0x38]);
-        let phdr_num = LittleEndian::read_u16(&bin[0x38..0x3A]);
-        let shdr_offset = LittleEndian::read_u64(&bin[0x28..0x30]);
-        let shdr_size = LittleEndian::read_u16(&bin[0x3A..0x3C]);
-        let shdr_num = LittleEndian::read_u16(&bin[0x3C..0x3E]);
-        let section_hdrs = shdr::parse_section_header(&bin, shstrndx)?;
-        // let sections = section::parse_sections(bin,&section_hdrs); 
+
+        // the 32-bit header has 4 byte addresses, everything after them moves up
+        let (e_entry, phdr_offset, shdr_offset, rest) = match e_class {
+            Elf_class::ELF64 => (parse_entry64(&bin), LittleEndian::read_u64(&bin[0x20..0x28]), LittleEndian::read_u64(&bin[0x28..0x30]), 0x30),
+            Elf_class::ELF32 => (LittleEndian::read_u32(&bin[0x18..0x1C]) as u64, LittleEndian::read_u32(&bin[0x1C..0x20]) as u64, LittleEndian::read_u32(&bin[0x20..0x24]) as u64, 0x24)
+        };
+        let e_flags = LittleEndian::read_u32(&bin[rest..rest+0x4]);
+        let size = LittleEndian::read_u16(&bin[rest+0x4..rest+0x6]);
+        let phdr_size = LittleEndian::read_u16(&bin[rest+0x6..rest+0x8]);
+        let phdr_num = LittleEndian::read_u16(&bin[rest+0x8..rest+0xA]);
+        let shdr_size = LittleEndian::read_u16(&bin[rest+0xA..rest+0xC]);
+        let shdr_num = LittleEndian::read_u16(&bin[rest+0xC..rest+0xE]);
+        let shstrndx = LittleEndian::read_u16(&bin[rest+0xE..rest+0x10]); 
         
         return Ok(Elf_header{
             e_ident,
@@ -306,8 +486,12 @@ impl Elf_header {
             shdr_offset,
             shdr_size,
             shdr_num,
-            shstrndx
-            // Add sections to           
+            shstrndx,
+            raw_class: bin[4],
+            raw_endianness: bin[5],
+            raw_abi: bin[7],
+            raw_type: LittleEndian::read_u16(&bin[0x10..0x12]),
+            raw_arch: LittleEndian::read_u16(&bin[0x12..0x14])
         });   
     }
 }
@@ -332,80 +516,100 @@ fn parse_entry64(bin: &Vec<u8>) -> u64 {
 }
 
 fn parse_type(bin: &Vec<u8>) -> Elf_type {
-    return match LittleEndian::read_u16(&bin[0x10..0x12]) {
-        0x0 => return Elf_type::NONE,
-        0x1 => return Elf_type::REL,
-        0x2 => return Elf_type::EXEC,
-        0x3 => return Elf_type::DYN,
-        0x4 => return Elf_type::CORE,
-        0xFE00 => return Elf_type::LOOS,
-        0xFEFF => return Elf_type::HIOS,
-        0xFF00 => return Elf_type::LOPROC,
-        0xFFFF => return Elf_type::HIPROC,
+    return elf_type(LittleEndian::read_u16(&bin[0x10..0x12])); 
+}
+
+fn elf_type(e_type: u16) -> Elf_type {
+    return match e_type {
+        0x0 => Elf_type::NONE,
+        0x1 => Elf_type::REL,
+        0x2 => Elf_type::EXEC,
+        0x3 => Elf_type::DYN,
+        0x4 => Elf_type::CORE,
+        0xFE00 => Elf_type::LOOS,
+        0xFEFF => Elf_type::HIOS,
+        0xFF00 => Elf_type::LOPROC,
+        0xFFFF => Elf_type::HIPROC,
         
-        _ => return Elf_type::NONE,
+        _ => Elf_type::NONE,
     }
 }
 
 
 
 fn parse_arch(bin: &Vec<u8>) -> Elf_arch {
-    return match LittleEndian::read_u16(&bin[0x12..0x14]) {
-        0x0 => return Elf_arch::NONE,
-        0x2 => return Elf_arch::SPARC,
-        0x3 => return Elf_arch::X86,
-        0x8 => return Elf_arch::MIPS,
-        0x14 => return Elf_arch::POWERPC,
-        0x16 => return Elf_arch::S390,
-        0x28 => return Elf_arch::ARM,
-        0x2A => return Elf_arch::SUPERH,
-        0x32 => return Elf_arch::IA64,
-        0x3E => return Elf_arch::AMD64,
-        0xB7 => return Elf_arch::AARCH64,
-        0xF3 => return Elf_arch::RISCV,
-        _ => return Elf_arch::NONE,
+    return elf_arch(LittleEndian::read_u16(&bin[0x12..0x14])); 
+}
+
+fn elf_arch(arch: u16) -> Elf_arch {
+    return match arch {
+        0x0 => Elf_arch::NONE,
+        0x2 => Elf_arch::SPARC,
+        0x3 => Elf_arch::X86,
+        0x8 => Elf_arch::MIPS,
+        0x14 => Elf_arch::POWERPC,
+        0x16 => Elf_arch::S390,
+        0x28 => Elf_arch::ARM,
+        0x2A => Elf_arch::SUPERH,
+        0x32 => Elf_arch::IA64,
+        0x3E => Elf_arch::AMD64,
+        0xB7 => Elf_arch::AARCH64,
+        0xF3 => Elf_arch::RISCV,
+        _ => Elf_arch::NONE,
     }
 }
 
 fn parse_class(bin: &Vec<u8>) -> Elf_class {
-    return match bin[4] {
-        1 => return Elf_class::ELF32,
-        _ => return Elf_class::ELF64
+    return elf_class(bin[4]); 
+}
+
+fn elf_class(class: u8) -> Elf_class {
+    return match class {
+        1 => Elf_class::ELF32,
+        _ => Elf_class::ELF64
     }
 }
 
 fn parse_abi(bin: &Vec<u8>) -> Elf_abi {
-    return match bin[7] {
-        0x0 => return Elf_abi::NONE,
-        0x1 => return Elf_abi::HPUX,
-        0x2 => return Elf_abi::NetBSD,
-        0x3 => return Elf_abi::Linux,
-        0x4 => return Elf_abi::GNUHurd,
-        0x6 => return Elf_abi::Solaris,
-        0x7 => return Elf_abi::AIX,
-        0x8 => return Elf_abi::IRIX,
-        0x9 => return Elf_abi::FreeBSD,
-        0x0A => return Elf_abi::Tru64,
-        0x0B => return Elf_abi::NovellModesto,
-        0x0C => return Elf_abi::OpenBSD,
-        0x0D => return Elf_abi::OpenVMS,
-        0x0E => return Elf_abi::NonStopKernel,
-        0x0F => return Elf_abi::AROS,
-        0x10 => return Elf_abi::FenixOS,
-        0x11 => return Elf_abi::CloudABI,
-        0x12 => return Elf_abi::OpenVOS,
-        _ => return Elf_abi::NONE
+    return elf_abi(bin[7]); 
+}
+
+fn elf_abi(abi: u8) -> Elf_abi {
+    return match abi {
+        0x0 => Elf_abi::NONE,
+        0x1 => Elf_abi::HPUX,
+        0x2 => Elf_abi::NetBSD,
+        0x3 => Elf_abi::Linux,
+        0x4 => Elf_abi::GNUHurd,
+        0x6 => Elf_abi::Solaris,
+        0x7 => Elf_abi::AIX,
+        0x8 => Elf_abi::IRIX,
+        0x9 => Elf_abi::FreeBSD,
+        0x0A => Elf_abi::Tru64,
+        0x0B => Elf_abi::NovellModesto,
+        0x0C => Elf_abi::OpenBSD,
+        0x0D => Elf_abi::OpenVMS,
+        0x0E => Elf_abi::NonStopKernel,
+        0x0F => Elf_abi::AROS,
+        0x10 => Elf_abi::FenixOS,
+        0x11 => Elf_abi::CloudABI,
+        0x12 => Elf_abi::OpenVOS,
+        _ => Elf_abi::NONE
     }
 }
 
 fn parse_endianness(bin: &Vec<u8>) -> Elf_endiannes {
-    return match bin[5] {
-        1 => return Elf_endiannes::LittleEndian,
-        _ => return Elf_endiannes::BigEndian
+    return elf_endianness(bin[5]); 
+}
+
+fn elf_endianness(endianness: u8) -> Elf_endiannes {
+    return match endianness {
+        1 => Elf_endiannes::LittleEndian,
+        _ => Elf_endiannes::BigEndian
     }
 }
 
 pub fn from_file(path: &str) -> Result<Elf> {
-    let bin = fs::read(path).expect("Failed to read path"); 
+    let bin = fs::read(path)?; 
     Elf::parse(bin) 
 }
\ No newline at end of file
@@ -0,0 +1,278 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::dwarf;
+use crate::dwarf::{AttrValue, Dwarf, Encoding, Reader, Unit};
+
+// The DWARF line number program of a unit (.debug_line), run into a table
+// of rows mapping addresses to source positions.
+
+const DW_LNS_copy: u8 = 1;
+const DW_LNS_advance_pc: u8 = 2;
+const DW_LNS_advance_line: u8 = 3;
+const DW_LNS_set_file: u8 = 4;
+const DW_LNS_set_column: u8 = 5;
+const DW_LNS_negate_stmt: u8 = 6;
+const DW_LNS_set_basic_block: u8 = 7;
+const DW_LNS_const_add_pc: u8 = 8;
+const DW_LNS_fixed_advance_pc: u8 = 9;
+const DW_LNS_set_prologue_end: u8 = 10;
+const DW_LNS_set_epilogue_begin: u8 = 11;
+const DW_LNS_set_isa: u8 = 12;
+
+const DW_LNE_end_sequence: u8 = 1;
+const DW_LNE_set_address: u8 = 2;
+const DW_LNE_define_file: u8 = 3;
+const DW_LNE_set_discriminator: u8 = 4;
+
+const DW_LNCT_path: u64 = 1;
+const DW_LNCT_directory_index: u64 = 2;
+
+#[derive(Clone, Debug)]
+pub struct LineRow {
+    pub address: u64,
+    pub file: u64,
+    pub line: u64,
+    pub column: u64,
+    pub is_stmt: bool,
+    pub end_sequence: bool
+}
+
+#[derive(Clone, Debug)]
+pub struct FileEntry {
+    pub path: String,
+    pub dir: u64
+}
+
+pub struct LineProgram {
+    pub version: u16,
+    pub dirs: Vec<String>,
+    // indexed by the file numbers of the program, DWARF 4 and older count
+    // from 1 so entry 0 is the primary source file of the unit
+    pub files: Vec<FileEntry>,
+    pub rows: Vec<LineRow>
+}
+
+// Read the directory or file table of a DWARF 5 header, every entry is
+// described by a list of (content type, form) pairs.
+fn entry_table(r: &mut Reader, enc: Encoding, unit: &Unit, dwarf: &Dwarf) -> Option<Vec<FileEntry>> {
+    let format_count = r.u8()?;
+    let mut formats = vec![];
+    for _ in 0..format_count {
+        formats.push((r.uleb()?, r.uleb()? as u16));
+    }
+
+    let count = r.uleb()?;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let mut entry = FileEntry {
+            path: String::new(),
+            dir: 0
+        };
+
+        for (content, form) in &formats {
+            let value = match dwarf::read_value(r, *form, 0, enc, 0, dwarf)? {
+                AttrValue::StrIndex(index) => AttrValue::String(dwarf.str_index(unit.str_offsets_base, index, enc.format64).unwrap_or(String::new())),
+                value => value
+            };
+
+            match *content {
+                DW_LNCT_path => entry.path = String::from(value.string().unwrap_or("")),
+                DW_LNCT_directory_index => entry.dir = value.udata().unwrap_or(0),
+                _ => {}
+            }
+        }
+        entries.push(entry);
+    }
+
+    return Some(entries);
+}
+
+impl LineProgram {
+    // parse the line program at `offset` in .debug_line
+    pub fn parse(dwarf: &Dwarf, offset: u64, comp_dir: &str, name: &str, unit: &Unit) -> Result<LineProgram> {
+        return LineProgram::read(dwarf, offset, comp_dir, name, unit).ok_or(ParsingError::ParsingError);
+    }
+
+    fn read(dwarf: &Dwarf, offset: u64, comp_dir: &str, name: &str, unit: &Unit) -> Option<LineProgram> {
+        let mut r = Reader::new(&dwarf.line, offset as usize);
+        let (length, format64) = r.initial_length()?;
+        let end = r.pos.checked_add(length as usize)?;
+        let version = r.u16()?;
+
+        let mut address_size = unit.encoding.address_size;
+        if version >= 5 {
+            address_size = r.u8()?;
+            let _segment_selector_size = r.u8()?;
+        }
+
+        let header_length = r.offset(format64)?;
+        let program = r.pos + header_length as usize;
+        let min_inst_length = r.u8()? as u64;
+        if version >= 4 {
+            let _max_ops_per_inst = r.u8()?;
+        }
+        let default_is_stmt = r.u8()? != 0;
+        let line_base = r.u8()? as i8 as i64;
+        let line_range = r.u8()? as u64;
+        let opcode_base = r.u8()?;
+        let opcode_lengths = r.bytes(opcode_base.saturating_sub(1) as usize)?.to_vec();
+
+        let enc = Encoding {
+            version,
+            format64,
+            address_size
+        };
+
+        let mut dirs = vec![];
+        let mut files = vec![];
+
+        if version >= 5 {
+            dirs = entry_table(&mut r, enc, unit, dwarf)?.into_iter().map(|d| d.path).collect();
+            files = entry_table(&mut r, enc, unit, dwarf)?;
+        } else {
+            // directory 0 is the compilation directory, file 0 the unit itself
+            dirs.push(String::from(comp_dir));
+            files.push(FileEntry {
+                path: String::from(name),
+                dir: 0
+            });
+
+            loop {
+                let dir = r.cstr()?;
+                if dir.is_empty() {
+                    break;
+                }
+                dirs.push(dir);
+            }
+            loop {
+                let path = r.cstr()?;
+                if path.is_empty() {
+                    break;
+                }
+                let dir = r.uleb()?;
+                let _mtime = r.uleb()?;
+                let _size = r.uleb()?;
+                files.push(FileEntry {
+                    path,
+                    dir
+                });
+            }
+        }
+
+        // run the line number program
+        let mut rows = vec![];
+        r.pos = program;
+        let start_file = if version >= 5 { 0 } else { 1 };
+        let mut row = LineRow {
+            address: 0,
+            file: start_file,
+            line: 1,
+            column: 0,
+            is_stmt: default_is_stmt,
+            end_sequence: false
+        };
+
+        while r.pos < end {
+            let opcode = r.u8()?;
+
+            if opcode >= opcode_base {
+                // a special opcode advances both the address and the line
+                let adjusted = (opcode - opcode_base) as u64;
+                row.address = row.address.wrapping_add(adjusted / line_range.max(1) * min_inst_length);
+                row.line = (row.line as i64 + line_base + (adjusted % line_range.max(1)) as i64) as u64;
+                rows.push(row.clone());
+                continue;
+            }
+
+            match opcode {
+                0 => {
+                    let len = r.uleb()? as usize;
+                    let next = r.pos + len;
+                    match r.u8()? {
+                        DW_LNE_end_sequence => {
+                            row.end_sequence = true;
+                            rows.push(row.clone());
+                            row = LineRow {
+                                address: 0,
+                                file: start_file,
+                                line: 1,
+                                column: 0,
+                                is_stmt: default_is_stmt,
+                                end_sequence: false
+                            };
+                        },
+                        DW_LNE_set_address => row.address = r.sized((len - 1) as u8)?,
+                        DW_LNE_set_discriminator => {},
+                        DW_LNE_define_file => {
+                            let path = r.cstr()?;
+                            let dir = r.uleb()?;
+                            files.push(FileEntry {
+                                path,
+                                dir
+                            });
+                        },
+                        _ => {}
+                    }
+                    r.pos = next;
+                },
+                DW_LNS_copy => rows.push(row.clone()),
+                DW_LNS_advance_pc => row.address = row.address.wrapping_add(r.uleb()? * min_inst_length),
+                DW_LNS_advance_line => row.line = (row.line as i64 + r.sleb()?) as u64,
+                DW_LNS_set_file => row.file = r.uleb()?,
+                DW_LNS_set_column => row.column = r.uleb()?,
+                DW_LNS_negate_stmt => row.is_stmt = !row.is_stmt,
+                DW_LNS_const_add_pc => row.address = row.address.wrapping_add((255 - opcode_base) as u64 / line_range.max(1) * min_inst_length),
+                DW_LNS_fixed_advance_pc => row.address = row.address.wrapping_add(r.u16()? as u64),
+                DW_LNS_set_basic_block | DW_LNS_set_prologue_end | DW_LNS_set_epilogue_begin => {},
+                DW_LNS_set_isa => { r.uleb()?; },
+                _ => {
+                    // skip the arguments of opcodes we don't know
+                    for _ in 0..*opcode_lengths.get(opcode as usize - 1)? {
+                        r.uleb()?;
+                    }
+                }
+            }
+        }
+
+        Some(LineProgram {
+            version,
+            dirs,
+            files,
+            rows
+        })
+    }
+
+    // the row covering an address, rows of a sequence are sorted on their address
+    pub fn find(&self, addr: u64) -> Option<&LineRow> {
+        let mut found: Option<&LineRow> = None;
+
+        for pair in self.rows.windows(2) {
+            if pair[0].end_sequence {
+                continue;
+            }
+            if pair[0].address <= addr && addr < pair[1].address {
+                // prefer the last row for the address, like addr2line
+                found = Some(&pair[0]);
+            }
+        }
+
+        return found;
+    }
+
+    // the full path of a file of the program
+    pub fn file_name(&self, file: u64) -> Option<String> {
+        let entry = self.files.get(file as usize)?;
+        if entry.path.starts_with('/') {
+            return Some(entry.path.clone());
+        }
+
+        let dir = self.dirs.get(entry.dir as usize).cloned().unwrap_or(String::new());
+        let comp_dir = self.dirs.get(0).cloned().unwrap_or(String::new());
+
+        let dir = if dir.starts_with('/') || dir.is_empty() || entry.dir == 0 { dir } else { format!("{}/{}", comp_dir, dir) };
+        if dir.is_empty() {
+            return Some(entry.path.clone());
+        }
+        return Some(format!("{}/{}", dir, entry.path));
+    }
+}
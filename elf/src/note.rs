@@ -0,0 +1,311 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_class;
+use crate::shdr;
+use crate::phdr::Phdr_type;
+
+use byteorder::*;
+
+// The notes this crate knows how to decode, identified by owner and type
+#[derive(Copy, Clone, PartialEq)]
+pub enum Note_kind {
+    GNU_ABI_TAG,
+    GNU_BUILD_ID,
+    GNU_PROPERTY_TYPE_0,
+    GO_BUILD_ID,
+    FDO_PACKAGING_METADATA,
+    OTHER
+}
+
+const NT_GNU_ABI_TAG: u32 = 1;
+const NT_GNU_BUILD_ID: u32 = 3;
+const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+const NT_GO_BUILD_ID: u32 = 4;
+const NT_FDO_PACKAGING_METADATA: u32 = 0xcafe1a7e;
+
+const GNU_PROPERTY_STACK_SIZE: u32 = 1;
+const GNU_PROPERTY_NO_COPY_ON_PROTECTED: u32 = 2;
+const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc0000000;
+const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc0000002;
+
+#[derive(Clone)]
+pub struct Note {
+    pub name: String,
+    pub n_type: u32,
+    pub desc: Vec<u8>
+}
+
+pub struct NoteIter<'a> {
+    bin: &'a [u8],
+    pos: usize,
+    align: usize
+}
+
+// Walk the notes in a PT_NOTE segment or SHT_NOTE section. Notes are 4 byte
+// aligned, except in segments and sections with an 8 byte alignment (as used
+// by .note.gnu.property), where the descriptor and next note are 8 aligned.
+pub fn iter_notes(bin: &[u8], align: u64) -> NoteIter<'_> {
+    NoteIter {
+        bin,
+        pos: 0,
+        align: if align == 8 { 8 } else { 4 }
+    }
+}
+
+fn align_up(val: usize, align: usize) -> usize {
+    return (val + align - 1) / align * align;
+}
+
+impl<'a> Iterator for NoteIter<'a> {
+    type Item = Note;
+
+    fn next(&mut self) -> Option<Note> {
+        let hdr = self.bin.get(self.pos..self.pos + 12)?;
+        let namesz = LittleEndian::read_u32(&hdr[0x0..0x4]) as usize;
+        let descsz = LittleEndian::read_u32(&hdr[0x4..0x8]) as usize;
+        let n_type = LittleEndian::read_u32(&hdr[0x8..0xC]);
+
+        let name_start = self.pos + 12;
+        let desc_start = align_up(name_start + namesz, self.align);
+        let name = self.bin.get(name_start..name_start + namesz)?;
+        let desc = self.bin.get(desc_start..desc_start + descsz)?;
+
+        self.pos = align_up(desc_start + descsz, self.align);
+
+        Some(Note {
+            name: String::from(shdr::str_from_u8_nul_utf8(name).ok()?),
+            n_type,
+            desc: desc.to_vec()
+        })
+    }
+}
+
+pub fn parse_notes(bin: &[u8], align: u64) -> Vec<Note> {
+    return iter_notes(bin, align).collect();
+}
+
+// NT_GNU_ABI_TAG, the minimum kernel the binary runs on
+pub struct AbiTag {
+    // 0 = Linux, 1 = Hurd, 2 = Solaris, 3 = FreeBSD
+    pub os: u32,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32
+}
+
+// a single entry of a NT_GNU_PROPERTY_TYPE_0 note
+#[derive(Clone)]
+pub enum Gnu_property {
+    STACK_SIZE(u64),
+    NO_COPY_ON_PROTECTED,
+    // bit 0 = IBT, bit 1 = SHSTK
+    X86_FEATURE_1_AND(u32),
+    // bit 0 = BTI, bit 1 = PAC
+    AARCH64_FEATURE_1_AND(u32),
+    OTHER(u32, Vec<u8>)
+}
+
+impl Note {
+
+    pub fn kind(&self) -> Note_kind {
+        return match (self.name.as_str(), self.n_type) {
+            ("GNU", NT_GNU_ABI_TAG) => Note_kind::GNU_ABI_TAG,
+            ("GNU", NT_GNU_BUILD_ID) => Note_kind::GNU_BUILD_ID,
+            ("GNU", NT_GNU_PROPERTY_TYPE_0) => Note_kind::GNU_PROPERTY_TYPE_0,
+            ("Go", NT_GO_BUILD_ID) => Note_kind::GO_BUILD_ID,
+            ("FDO", NT_FDO_PACKAGING_METADATA) => Note_kind::FDO_PACKAGING_METADATA,
+            _ => Note_kind::OTHER
+        }
+    }
+
+    pub fn abi_tag(&self) -> Option<AbiTag> {
+        if self.kind() != Note_kind::GNU_ABI_TAG || self.desc.len() < 16 {
+            return None;
+        }
+
+        Some(AbiTag {
+            os: LittleEndian::read_u32(&self.desc[0x0..0x4]),
+            major: LittleEndian::read_u32(&self.desc[0x4..0x8]),
+            minor: LittleEndian::read_u32(&self.desc[0x8..0xC]),
+            patch: LittleEndian::read_u32(&self.desc[0xC..0x10]),
+        })
+    }
+
+    // the build id as a lowercase hex string
+    pub fn build_id(&self) -> Option<String> {
+        if self.kind() != Note_kind::GNU_BUILD_ID {
+            return None;
+        }
+        return Some(self.desc.iter().map(|b| format!("{:02x}", b)).collect());
+    }
+
+    // The properties of a NT_GNU_PROPERTY_TYPE_0 note, each entry is padded
+    // to the word size of the class.
+    pub fn gnu_properties(&self, class: Elf_class) -> Option<Vec<Gnu_property>> {
+        if self.kind() != Note_kind::GNU_PROPERTY_TYPE_0 {
+            return None;
+        }
+
+        let align = match class {
+            Elf_class::ELF64 => 8,
+            Elf_class::ELF32 => 4
+        };
+        let mut props = vec![];
+        let mut pos = 0;
+
+        while pos + 8 <= self.desc.len() {
+            let pr_type = LittleEndian::read_u32(&self.desc[pos..pos+4]);
+            let datasz = LittleEndian::read_u32(&self.desc[pos+4..pos+8]) as usize;
+            let data = self.desc.get(pos+8..pos+8+datasz)?;
+
+            props.push(match pr_type {
+                GNU_PROPERTY_STACK_SIZE if datasz == 8 => Gnu_property::STACK_SIZE(LittleEndian::read_u64(data)),
+                GNU_PROPERTY_STACK_SIZE if datasz == 4 => Gnu_property::STACK_SIZE(LittleEndian::read_u32(data) as u64),
+                GNU_PROPERTY_NO_COPY_ON_PROTECTED => Gnu_property::NO_COPY_ON_PROTECTED,
+                GNU_PROPERTY_X86_FEATURE_1_AND if datasz == 4 => Gnu_property::X86_FEATURE_1_AND(LittleEndian::read_u32(data)),
+                GNU_PROPERTY_AARCH64_FEATURE_1_AND if datasz == 4 => Gnu_property::AARCH64_FEATURE_1_AND(LittleEndian::read_u32(data)),
+                _ => Gnu_property::OTHER(pr_type, data.to_vec())
+            });
+
+            pos = align_up(pos + 8 + datasz, align);
+        }
+
+        return Some(props);
+    }
+
+    // the build id the Go toolchain embeds
+    pub fn go_build_id(&self) -> Option<String> {
+        if self.kind() != Note_kind::GO_BUILD_ID {
+            return None;
+        }
+        return Some(String::from(shdr::str_from_u8_nul_utf8(&self.desc).ok()?));
+    }
+
+    // The FDO package metadata, a flat JSON object describing the distro
+    // package the binary was built for. The raw JSON is returned.
+    pub fn package_metadata(&self) -> Option<String> {
+        if self.kind() != Note_kind::FDO_PACKAGING_METADATA {
+            return None;
+        }
+        return Some(String::from(shdr::str_from_u8_nul_utf8(&self.desc).ok()?));
+    }
+
+    // the string fields of the FDO package metadata, e.g. ("type", "rpm")
+    pub fn package_fields(&self) -> Option<Vec<(String, String)>> {
+        let json = self.package_metadata()?;
+        let mut strings = vec![];
+        let mut chars = json.chars();
+
+        // collect the quoted strings, keys and values alternate in a flat object
+        while let Some(c) = chars.next() {
+            if c != '"' {
+                continue;
+            }
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => if let Some(escaped) = chars.next() { s.push(escaped) },
+                    _ => s.push(c)
+                }
+            }
+            strings.push(s);
+        }
+
+        return Some(strings.chunks_exact(2).map(|kv| (kv[0].clone(), kv[1].clone())).collect());
+    }
+}
+
+impl Elf {
+    // All notes of the binary, read through the SHT_NOTE sections. Files
+    // without section headers, like core dumps, are read through PT_NOTE.
+    pub fn notes(&self) -> Result<Vec<Note>> {
+        let mut notes = vec![];
+        let mut found = false;
+
+        for shdr in &self.shdrs {
+            if let shdr::Shdr_type::NOTE = shdr.sh_type {
+                notes.extend(self.section_notes(shdr)?);
+                found = true;
+            }
+        }
+
+        if found {
+            return Ok(notes);
+        }
+
+        for phdr in &self.phdrs {
+            if let Phdr_type::NOTE = phdr.p_type {
+                let bin = self.raw.get(phdr.offset as usize..(phdr.offset+phdr.filesz) as usize).ok_or(ParsingError::ParsingError)?;
+                notes.extend(iter_notes(&bin, phdr.p_align));
+            }
+        }
+
+        return Ok(notes);
+    }
+
+    pub fn section_notes(&self, shdr: &shdr::SectionHeader) -> Result<Vec<Note>> {
+        let bin = self.raw.get(shdr.offset as usize..(shdr.offset+shdr.size) as usize).ok_or(ParsingError::ParsingError)?;
+        return Ok(parse_notes(&bin, shdr.addralign));
+    }
+
+    pub fn build_id(&self) -> Option<String> {
+        return self.notes().ok()?.iter().find_map(|n| n.build_id());
+    }
+
+    pub fn abi_tag(&self) -> Option<AbiTag> {
+        return self.notes().ok()?.iter().find_map(|n| n.abi_tag());
+    }
+
+    pub fn gnu_properties(&self) -> Vec<Gnu_property> {
+        let class = self.header.e_class;
+        return match self.notes() {
+            Ok(notes) => notes.iter().filter_map(|n| n.gnu_properties(class)).flatten().collect(),
+            Err(_) => vec![]
+        }
+    }
+
+    fn feature_bits(&self) -> (u32, u32) {
+        let mut x86 = 0;
+        let mut aarch64 = 0;
+
+        for prop in self.gnu_properties() {
+            match prop {
+                Gnu_property::X86_FEATURE_1_AND(bits) => x86 |= bits,
+                Gnu_property::AARCH64_FEATURE_1_AND(bits) => aarch64 |= bits,
+                _ => {}
+            }
+        }
+
+        return (x86, aarch64);
+    }
+
+    // x86 indirect branch tracking (CET)
+    pub fn has_ibt(&self) -> bool {
+        return self.feature_bits().0 & 0x1 != 0;
+    }
+
+    // x86 shadow stack (CET)
+    pub fn has_shstk(&self) -> bool {
+        return self.feature_bits().0 & 0x2 != 0;
+    }
+
+    // AArch64 branch target identification
+    pub fn has_bti(&self) -> bool {
+        return self.feature_bits().1 & 0x1 != 0;
+    }
+
+    // AArch64 pointer authentication
+    pub fn has_pac(&self) -> bool {
+        return self.feature_bits().1 & 0x2 != 0;
+    }
+
+    pub fn go_build_id(&self) -> Option<String> {
+        return self.notes().ok()?.iter().find_map(|n| n.go_build_id());
+    }
+
+    pub fn package_metadata(&self) -> Option<String> {
+        return self.notes().ok()?.iter().find_map(|n| n.package_metadata());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf_class;
+use crate::leb128;
+use crate::reloc::Relocation;
+
+use byteorder::*;
+
+// Compact relocation encodings. RELR only stores the offsets of relative
+// relocations, while APS2 is Android's SLEB128 delta encoding of a whole
+// REL or RELA table.
+
+const APS2_MAGIC: &[u8] = b"APS2";
+
+const GROUPED_BY_INFO: u64 = 0x1;
+const GROUPED_BY_OFFSET_DELTA: u64 = 0x2;
+const GROUPED_BY_ADDEND: u64 = 0x4;
+const GROUP_HAS_ADDEND: u64 = 0x8;
+
+fn word_size(class: Elf_class) -> u64 {
+    return match class {
+        Elf_class::ELF64 => 8,
+        Elf_class::ELF32 => 4
+    }
+}
+
+fn read_word(bin: &[u8], class: Elf_class) -> u64 {
+    return match class {
+        Elf_class::ELF64 => LittleEndian::read_u64(bin),
+        Elf_class::ELF32 => LittleEndian::read_u32(bin) as u64
+    }
+}
+
+fn write_word(bin: &mut Vec<u8>, word: u64, class: Elf_class) {
+    match class {
+        Elf_class::ELF64 => bin.extend_from_slice(&word.to_le_bytes()),
+        Elf_class::ELF32 => bin.extend_from_slice(&(word as u32).to_le_bytes())
+    }
+}
+
+// expand a RELR table into the offsets it relocates
+pub fn decode_relr(bin: &[u8], class: Elf_class) -> Vec<u64> {
+    let wsize = word_size(class);
+    let bits = wsize*8 - 1;
+    let mut offsets = vec![];
+    let mut base = 0;
+
+    for entry in bin.chunks_exact(wsize as usize) {
+        let word = read_word(entry, class);
+
+        if word & 1 == 0 {
+            // an address entry, relocates a single word and starts a new run
+            offsets.push(word);
+            base = word + wsize;
+        } else {
+            // a bitmap entry, every set bit relocates the word at that index after base
+            for i in 0..bits {
+                if (word >> (i + 1)) & 1 == 1 {
+                    offsets.push(base + i*wsize);
+                }
+            }
+            base += bits*wsize;
+        }
+    }
+
+    return offsets;
+}
+
+// pack a list of relative relocation offsets into a RELR table, every offset
+// has to be word aligned
+pub fn encode_relr(offsets: &Vec<u64>, class: Elf_class) -> Result<Vec<u8>> {
+    let wsize = word_size(class);
+    let bits = wsize*8 - 1;
+    let mut sorted = offsets.clone();
+    sorted.sort();
+    sorted.dedup();
+
+    if sorted.iter().any(|o| o % wsize != 0) {
+        return Err(ParsingError::ParsingError);
+    }
+
+    let mut bin = vec![];
+    let mut i = 0;
+
+    while i < sorted.len() {
+        write_word(&mut bin, sorted[i], class);
+        let mut base = sorted[i] + wsize;
+        i += 1;
+
+        loop {
+            let mut bitmap: u64 = 0;
+
+            while i < sorted.len() {
+                let delta = sorted[i] - base;
+                if delta >= bits*wsize {
+                    break;
+                }
+                bitmap |= 1 << (delta / wsize);
+                i += 1;
+            }
+
+            if bitmap == 0 {
+                break;
+            }
+
+            write_word(&mut bin, (bitmap << 1) | 1, class);
+            base += bits*wsize;
+        }
+    }
+
+    return Ok(bin);
+}
+
+// the word used to pad a RELR table, a bitmap without any bits set relocates nothing
+pub fn relr_padding(class: Elf_class) -> Vec<u8> {
+    let mut bin = vec![];
+    write_word(&mut bin, 1, class);
+    return bin;
+}
+
+fn split_info(info: u64, class: Elf_class) -> (u32, u32) {
+    return match class {
+        Elf_class::ELF64 => (info as u32, (info >> 32) as u32),
+        Elf_class::ELF32 => ((info & 0xff) as u32, (info >> 8) as u32)
+    }
+}
+
+fn join_info(reloc: &Relocation, class: Elf_class) -> u64 {
+    return match class {
+        Elf_class::ELF64 => ((reloc.sym as u64) << 32) | reloc.r_type as u64,
+        Elf_class::ELF32 => ((reloc.sym as u64) << 8) | (reloc.r_type as u64 & 0xff)
+    }
+}
+
+// decode an Android APS2 packed relocation table
+pub fn decode_aps2(bin: &[u8], class: Elf_class, rela: bool) -> Result<Vec<Relocation>> {
+    if !bin.starts_with(APS2_MAGIC) {
+        return Err(ParsingError::ParsingError);
+    }
+
+    let mut pos = APS2_MAGIC.len();
+    let next = |pos: &mut usize| leb128::read_sleb128(bin, pos).ok_or(ParsingError::ParsingError);
+
+    let count = next(&mut pos)? as usize;
+    let mut offset = next(&mut pos)? as u64;
+    let mut info: u64 = 0;
+    let mut addend: i64 = 0;
+    let mut relocs = vec![];
+
+    while relocs.len() < count {
+        let group_size = next(&mut pos)? as usize;
+        let flags = next(&mut pos)? as u64;
+        let mut group_delta = 0;
+
+        if group_size == 0 {
+            return Err(ParsingError::ParsingError);
+        }
+
+        if flags & GROUPED_BY_OFFSET_DELTA != 0 {
+            group_delta = next(&mut pos)? as u64;
+        }
+        if flags & GROUPED_BY_INFO != 0 {
+            info = next(&mut pos)? as u64;
+        }
+        if flags & GROUP_HAS_ADDEND != 0 && flags & GROUPED_BY_ADDEND != 0 {
+            addend = addend.wrapping_add(next(&mut pos)?);
+        } else if flags & GROUP_HAS_ADDEND == 0 {
+            addend = 0;
+        }
+
+        for _ in 0..group_size {
+            if flags & GROUPED_BY_OFFSET_DELTA != 0 {
+                offset = offset.wrapping_add(group_delta);
+            } else {
+                offset = offset.wrapping_add(next(&mut pos)? as u64);
+            }
+            if flags & GROUPED_BY_INFO == 0 {
+                info = next(&mut pos)? as u64;
+            }
+            if rela && flags & GROUP_HAS_ADDEND != 0 && flags & GROUPED_BY_ADDEND == 0 {
+                addend = addend.wrapping_add(next(&mut pos)?);
+            }
+
+            let (r_type, sym) = split_info(info, class);
+            relocs.push(Relocation {
+                offset,
+                r_type,
+                sym,
+                addend: if rela { addend } else { 0 },
+                symbol: String::new()
+            });
+        }
+    }
+
+    return Ok(relocs);
+}
+
+// encode relocations as an APS2 table. Runs of relocations sharing the info,
+// the offset stride and the addend are folded into one group.
+pub fn encode_aps2(relocs: &Vec<Relocation>, class: Elf_class, rela: bool) -> Vec<u8> {
+    let mut bin = APS2_MAGIC.to_vec();
+    leb128::write_sleb128(&mut bin, relocs.len() as i64);
+    leb128::write_sleb128(&mut bin, 0);
+
+    let mut offset: u64 = 0;
+    let mut addend: i64 = 0;
+    let mut i = 0;
+
+    while i < relocs.len() {
+        let info = join_info(&relocs[i], class);
+        let delta = relocs[i].offset.wrapping_sub(offset);
+        let mut j = i + 1;
+
+        while j < relocs.len()
+            && join_info(&relocs[j], class) == info
+            && relocs[j].offset.wrapping_sub(relocs[j-1].offset) == delta
+            && (!rela || relocs[j].addend == relocs[i].addend) {
+            j += 1;
+        }
+
+        let mut flags = GROUPED_BY_INFO | GROUPED_BY_OFFSET_DELTA;
+        if rela {
+            flags |= GROUP_HAS_ADDEND | GROUPED_BY_ADDEND;
+        }
+
+        leb128::write_sleb128(&mut bin, (j - i) as i64);
+        leb128::write_sleb128(&mut bin, flags as i64);
+        leb128::write_sleb128(&mut bin, delta as i64);
+        leb128::write_sleb128(&mut bin, info as i64);
+        if rela {
+            leb128::write_sleb128(&mut bin, relocs[i].addend.wrapping_sub(addend));
+            addend = relocs[i].addend;
+        }
+
+        offset = relocs[j-1].offset;
+        i = j;
+    }
+
+    return bin;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relr_round_trip() {
+        // 0x1000, then a bitmap for 0x1008, 0x1010 and 0x1100
+        let mut bin = vec![];
+        write_word(&mut bin, 0x1000, Elf_class::ELF64);
+        write_word(&mut bin, ((1 | 1 << 1 | 1 << 31) << 1) | 1, Elf_class::ELF64);
+
+        let offsets = decode_relr(&bin, Elf_class::ELF64);
+        assert_eq!(offsets, vec![0x1000, 0x1008, 0x1010, 0x1100]);
+        assert_eq!(encode_relr(&offsets, Elf_class::ELF64).unwrap(), bin);
+    }
+
+    #[test]
+    fn aps2_round_trip() {
+        let mut bin = APS2_MAGIC.to_vec();
+        // 4 relocations starting at offset 0
+        bin.extend_from_slice(&[0x04, 0x00]);
+        // one R_X86_64_RELATIVE at 0x2000 with addend 0x100
+        bin.extend_from_slice(&[0x01, 0x0f, 0x80, 0xc0, 0x00, 0x08, 0x80, 0x02]);
+        // two more 8 bytes apart with the same addend
+        bin.extend_from_slice(&[0x02, 0x0f, 0x08, 0x08, 0x00]);
+        // an R_X86_64_GLOB_DAT of symbol 1 at 0x3000 without addend
+        bin.extend_from_slice(&[0x01, 0x0f, 0xf0, 0x1f, 0x86, 0x80, 0x80, 0x80, 0x10, 0x80, 0x7e]);
+
+        let relocs = decode_aps2(&bin, Elf_class::ELF64, true).unwrap();
+        let decoded: Vec<(u64, u32, u32, i64)> = relocs.iter().map(|r| (r.offset, r.r_type, r.sym, r.addend)).collect();
+        assert_eq!(decoded, vec![(0x2000, 8, 0, 0x100), (0x2008, 8, 0, 0x100), (0x2010, 8, 0, 0x100), (0x3000, 6, 1, 0)]);
+        assert_eq!(encode_aps2(&relocs, Elf_class::ELF64, true), bin);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::Result; 
+use crate::Elf_class; 
 
 use byteorder::*; 
 
@@ -13,60 +14,119 @@ pub enum Phdr_type {
     PHDR = 0x6,
     TLS = 0x7,
     LOOS = 0x60000000,
+    GNU_EH_FRAME = 0x6474e550,
+    GNU_STACK = 0x6474e551,
+    GNU_RELRO = 0x6474e552,
+    GNU_PROPERTY = 0x6474e553,
     HIOS = 0x6FFFFFFF,
     LOPROC = 0x70000000,
     HIPROC = 0x7FFFFFFF,
-    // GNU options missing here 
-    // Currently we are dropping all foreign formats
+    // Currently we are dropping all other foreign formats
     // This might not be optimal.
 }
 
+// segment permissions are a bitmask, these are the bits
+#[repr(u32)]
+#[derive(Copy, Clone)]
+pub enum Phdr_flags {
+    X = 0x1,
+    W = 0x2,
+    R = 0x4
+}
+
+#[derive(Clone)]
 pub struct ProgramHeader {
     pub p_type: Phdr_type,
-    flags: u32,
+    pub flags: u32,
     pub offset: u64,
-    vaddr: u64,
-    paddr: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
     pub filesz: u64,
-    memsz: u64,
-    p_flags: u64,
-    pub p_align: u64
+    pub memsz: u64,
+    pub p_align: u64,
+    // the type as read from the file, so types we don't know survive a write
+    p_type_raw: u32
 }
 
 impl ProgramHeader {
+
+    pub fn new(p_type: Phdr_type, flags: u32, offset: u64, vaddr: u64, filesz: u64, memsz: u64, p_align: u64) -> ProgramHeader {
+        ProgramHeader {
+            p_type,
+            flags,
+            offset,
+            vaddr,
+            paddr: vaddr,
+            filesz,
+            memsz,
+            p_align,
+            p_type_raw: p_type as u32
+        }
+    }
   
-    // Parse programheaders
-    pub fn parse(phdr: &[u8]) -> Result< ProgramHeader > {    
-        Ok(ProgramHeader{
-            p_type: parse_phdr_type(&phdr),
-            flags: LittleEndian::read_u32(&phdr[0x04..0x08]),
-            offset: LittleEndian::read_u64(&phdr[0x8..0x10]),
-            vaddr: LittleEndian::read_u64(&phdr[0x10..0x18]),
-            paddr: LittleEndian::read_u64(&phdr[0x18..0x20]),
-            filesz: LittleEndian::read_u64(&phdr[0x20..0x28]),
-            memsz: LittleEndian::read_u64(&phdr[0x28..0x30]),
-            p_flags: 0,
-            p_align: LittleEndian::read_u64(&phdr[0x30..0x38]),
-        })
+    // Parse programheaders, the 32-bit ones have the flags after the sizes
+    pub fn parse(phdr: &[u8], class: Elf_class) -> Result< ProgramHeader > {    
+        let p_type_raw = LittleEndian::read_u32(&phdr[0x0..0x4]);
+        return match class {
+            Elf_class::ELF64 => Ok(ProgramHeader{
+                p_type: phdr_type(p_type_raw),
+                flags: LittleEndian::read_u32(&phdr[0x04..0x08]),
+                offset: LittleEndian::read_u64(&phdr[0x8..0x10]),
+                vaddr: LittleEndian::read_u64(&phdr[0x10..0x18]),
+                paddr: LittleEndian::read_u64(&phdr[0x18..0x20]),
+                filesz: LittleEndian::read_u64(&phdr[0x20..0x28]),
+                memsz: LittleEndian::read_u64(&phdr[0x28..0x30]),
+                p_align: LittleEndian::read_u64(&phdr[0x30..0x38]),
+                p_type_raw
+            }),
+            Elf_class::ELF32 => Ok(ProgramHeader{
+                p_type: phdr_type(p_type_raw),
+                offset: LittleEndian::read_u32(&phdr[0x4..0x8]) as u64,
+                vaddr: LittleEndian::read_u32(&phdr[0x8..0xC]) as u64,
+                paddr: LittleEndian::read_u32(&phdr[0xC..0x10]) as u64,
+                filesz: LittleEndian::read_u32(&phdr[0x10..0x14]) as u64,
+                memsz: LittleEndian::read_u32(&phdr[0x14..0x18]) as u64,
+                flags: LittleEndian::read_u32(&phdr[0x18..0x1C]),
+                p_align: LittleEndian::read_u32(&phdr[0x1C..0x20]) as u64,
+                p_type_raw
+            })
+        };
     } 
 
-    pub fn to_le(&self) -> Vec<u8> {
-        self.to_le_offset(0)
+    pub fn to_le(&self, class: Elf_class) -> Vec<u8> {
+        self.to_le_offset(0, class)
+    }
+
+    // the type to write, the raw value unless p_type was changed since parsing
+    pub fn type_value(&self) -> u32 {
+        if phdr_type(self.p_type_raw) as u32 == self.p_type as u32 {
+            return self.p_type_raw; 
+        }
+        return self.p_type as u32; 
     }
 
-    pub fn to_le_offset(&self, offset:usize) -> Vec<u8> {
+    pub fn to_le_offset(&self, offset:usize, class: Elf_class) -> Vec<u8> {
         // bin.append([1,2,3].to_vec())
         let mut bin = vec![]; 
         
-        // do i end up owning this data, thus preventing me from using sh_type elsewhere? 
-        bin.extend_from_slice(&(self.p_type as u32).to_le_bytes()); 
+        bin.extend_from_slice(&self.type_value().to_le_bytes()); 
+        if let Elf_class::ELF32 = class {
+            bin.extend_from_slice(&((self.offset + offset as u64) as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.vaddr as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.paddr as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.filesz as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.memsz as u32).to_le_bytes()); 
+            bin.extend_from_slice(&self.flags.to_le_bytes()); 
+            bin.extend_from_slice(&(self.p_align as u32).to_le_bytes()); 
+            return bin; 
+        }
+
         bin.extend_from_slice(&self.flags.to_le_bytes()); 
         bin.extend_from_slice(&(self.offset + offset as u64).to_le_bytes()); 
         bin.extend_from_slice(&self.vaddr.to_le_bytes()); 
         bin.extend_from_slice(&self.paddr.to_le_bytes()); 
         bin.extend_from_slice(&self.filesz.to_le_bytes()); 
         bin.extend_from_slice(&self.memsz.to_le_bytes()); 
-        // bin.extend_from_slice(&self.p_flags.to_le_bytes()); used in 32-bit
         bin.extend_from_slice(&self.p_align.to_le_bytes()); 
         
         // ProgramHeader::add_padding(40, &mut bin);  
@@ -82,8 +142,8 @@ impl ProgramHeader {
 }
 
 
-fn parse_phdr_type(phdr: &[u8]) -> Phdr_type {
-    return match LittleEndian::read_u32(&phdr[0x0..0x4]) {
+fn phdr_type(p_type: u32) -> Phdr_type {
+    return match p_type {
         0x0 => return Phdr_type::NULL,
         0x1 => return Phdr_type::LOAD,
         0x2 => return Phdr_type::DYNAMIC,
@@ -93,6 +153,10 @@ fn parse_phdr_type(phdr: &[u8]) -> Phdr_type {
         0x6 => return Phdr_type::PHDR,
         0x7 => return Phdr_type::TLS,
         0x60000000 => return Phdr_type::LOOS,
+        0x6474e550 => return Phdr_type::GNU_EH_FRAME,
+        0x6474e551 => return Phdr_type::GNU_STACK,
+        0x6474e552 => return Phdr_type::GNU_RELRO,
+        0x6474e553 => return Phdr_type::GNU_PROPERTY,
         0x6FFFFFFF => return Phdr_type::HIOS,
         0x70000000 => return Phdr_type::LOPROC,
         0x7FFFFFFF => return Phdr_type::HIPROC,
@@ -102,9 +166,11 @@ fn parse_phdr_type(phdr: &[u8]) -> Phdr_type {
 
 
 pub fn parse_program_header(bin: &Vec<u8>) -> Result<Vec<ProgramHeader>> {
-    let phdr_offset = LittleEndian::read_u64(&bin[0x20..0x28]); 
-    let phdr_size = LittleEndian::read_u16(&bin[0x36..0x38]); 
-    let phdr_num = LittleEndian::read_u16(&bin[0x38..0x3A]);
+    let class = crate::parse_class(bin);
+    let (phdr_offset, phdr_size, phdr_num) = match class {
+        Elf_class::ELF64 => (LittleEndian::read_u64(&bin[0x20..0x28]), LittleEndian::read_u16(&bin[0x36..0x38]), LittleEndian::read_u16(&bin[0x38..0x3A])),
+        Elf_class::ELF32 => (LittleEndian::read_u32(&bin[0x1C..0x20]) as u64, LittleEndian::read_u16(&bin[0x2A..0x2C]), LittleEndian::read_u16(&bin[0x2C..0x2E]))
+    };
     
     let mut phdrs:Vec<ProgramHeader> = vec![]; 
 
@@ -112,17 +178,17 @@ pub fn parse_program_header(bin: &Vec<u8>) -> Result<Vec<ProgramHeader>> {
     for i in 0..phdr_num {
         let start = (phdr_offset+(phdr_size as u64*i as u64) ) as usize; 
         let end = (phdr_offset+(phdr_size as u64*i as u64)+phdr_size as u64 ) as usize; 
-        phdrs.push(ProgramHeader::parse(&bin[start..end])?)
+        phdrs.push(ProgramHeader::parse(&bin[start..end], class)?)
     }
 
     return Ok(phdrs);
 }
 
-pub fn to_le(phdrs: Vec<ProgramHeader> ) -> Vec<u8> {
+pub fn to_le(phdrs: Vec<ProgramHeader>, class: Elf_class) -> Vec<u8> {
     let mut bin = vec![]; 
 
     for phdr in phdrs {
-        bin.extend(phdr.to_le()); 
+        bin.extend(phdr.to_le(class)); 
     }
 
     return bin; 
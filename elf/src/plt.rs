@@ -0,0 +1,240 @@
+use crate::Result;
+use crate::Elf;
+use crate::Elf_arch;
+use crate::shdr;
+use crate::reloc::Reloc_format;
+
+use byteorder::*;
+use std::collections::HashMap;
+
+// A GOT slot the dynamic loader fills with the address of an imported symbol
+#[derive(Clone)]
+pub struct GotEntry {
+    pub addr: u64,
+    pub r_type: u32,
+    pub symbol: String
+}
+
+// A PLT stub, the code that jumps through a GOT slot
+#[derive(Clone)]
+pub struct PltEntry {
+    pub addr: u64,
+    pub size: u64,
+    pub got: u64,
+    // the section the stub was found in, .plt, .plt.sec or .plt.got
+    pub section: String,
+    pub symbol: String
+}
+
+const PLT_SECTIONS: [&str; 3] = [".plt", ".plt.sec", ".plt.got"];
+
+impl Elf {
+    // every GOT slot with a symbol relocation against it (JUMP_SLOT and GLOB_DAT)
+    pub fn got_entries(&self) -> Result<Vec<GotEntry>> {
+        let mut entries = vec![];
+
+        for table in self.dynamic_relocations()? {
+            if table.format == Reloc_format::RELR {
+                continue;
+            }
+            for reloc in table.entries {
+                if reloc.symbol.is_empty() {
+                    continue;
+                }
+                entries.push(GotEntry {
+                    addr: reloc.offset,
+                    r_type: reloc.r_type,
+                    symbol: reloc.symbol
+                });
+            }
+        }
+
+        entries.sort_by_key(|e| e.addr);
+        return Ok(entries);
+    }
+
+    // Decode the stubs of the PLT sections and map them to the imported symbol
+    // they jump to. Only x86-64 and AArch64 stubs are understood.
+    pub fn plt_entries(&self) -> Result<Vec<PltEntry>> {
+        let got: HashMap<u64, String> = self.got_entries()?.into_iter().map(|e| (e.addr, e.symbol)).collect();
+        let jmprel = self.dynamic_relocations()?.into_iter().find(|t| t.name == "DT_JMPREL").map(|t| t.entries).unwrap_or(vec![]);
+        let mut entries = vec![];
+
+        for name in PLT_SECTIONS.iter() {
+            let shdr = match self.section_header(name) {
+                Some(shdr) => shdr,
+                None => continue
+            };
+            let section = match self.section(name) {
+                Some(section) => section,
+                None => continue
+            };
+
+            let stubs = match self.header.e_arch {
+                Elf_arch::AMD64 => x86_64_stubs(shdr, &section.content()),
+                Elf_arch::AARCH64 => aarch64_stubs(shdr, &section.content()),
+                _ => vec![]
+            };
+
+            for (addr, size, slot, index) in stubs {
+                // IBT style .plt entries only push the JMPREL index, the jump
+                // through the GOT happens in the matching .plt.sec entry
+                let (slot, symbol) = match (slot, index) {
+                    (Some(slot), _) => (slot, got.get(&slot).cloned()),
+                    (None, Some(index)) => match jmprel.get(index as usize) {
+                        Some(reloc) => (reloc.offset, Some(reloc.symbol.clone())),
+                        None => continue
+                    },
+                    _ => continue
+                };
+
+                // the resolver stub (PLT0) jumps through a slot without a symbol
+                if let Some(symbol) = symbol {
+                    entries.push(PltEntry {
+                        addr,
+                        size,
+                        got: slot,
+                        section: String::from(*name),
+                        symbol
+                    });
+                }
+            }
+        }
+
+        entries.sort_by_key(|e| e.addr);
+        return Ok(entries);
+    }
+
+    // name an address the way a disassembler would, e.g. `puts@plt`
+    pub fn plt_name(&self, addr: u64) -> Option<String> {
+        let entries = self.plt_entries().ok()?;
+        let entry = entries.iter().find(|e| addr >= e.addr && addr < e.addr + e.size)?;
+        return Some(format!("{}@plt", entry.symbol));
+    }
+
+    // Find the call sites of an imported function, either direct calls to one
+    // of its PLT stubs or indirect calls through its GOT slot (-fno-plt). This
+    // is a linear scan of the executable sections, so on x86-64 bytes inside
+    // other instructions can show up as false positives.
+    pub fn call_sites(&self, symbol: &str) -> Result<Vec<u64>> {
+        let stubs: Vec<u64> = self.plt_entries()?.iter().filter(|e| e.symbol == symbol).map(|e| e.addr).collect();
+        let slots: Vec<u64> = self.got_entries()?.iter().filter(|e| e.symbol == symbol).map(|e| e.addr).collect();
+        let mut sites = vec![];
+
+        for shdr in &self.shdrs {
+            if shdr.flags & shdr::Shdr_flags::EXECINSTR as u64 == 0 {
+                continue;
+            }
+            if PLT_SECTIONS.contains(&shdr.name.as_str()) {
+                continue;
+            }
+            let content = match self.raw.get(shdr.offset as usize..(shdr.offset+shdr.size) as usize) {
+                Some(content) => content,
+                None => continue
+            };
+
+            match self.header.e_arch {
+                Elf_arch::AMD64 => {
+                    for i in 0..content.len() {
+                        let addr = shdr.addr + i as u64;
+                        // call rel32
+                        if content[i] == 0xe8 && i + 5 <= content.len() {
+                            let target = (addr + 5).wrapping_add(LittleEndian::read_i32(&content[i+1..i+5]) as i64 as u64);
+                            if stubs.contains(&target) {
+                                sites.push(addr);
+                            }
+                        }
+                        // call *disp32(%rip)
+                        if content[i] == 0xff && i + 6 <= content.len() && content[i+1] == 0x15 {
+                            let target = (addr + 6).wrapping_add(LittleEndian::read_i32(&content[i+2..i+6]) as i64 as u64);
+                            if slots.contains(&target) {
+                                sites.push(addr);
+                            }
+                        }
+                    }
+                },
+                Elf_arch::AARCH64 => {
+                    for (i, insn) in content.chunks_exact(4).enumerate() {
+                        let addr = shdr.addr + i as u64 * 4;
+                        let insn = LittleEndian::read_u32(insn);
+                        // bl imm26
+                        if insn & 0xfc000000 == 0x94000000 {
+                            let imm = (((insn & 0x03ffffff) << 6) as i32 >> 4) as i64;
+                            if stubs.contains(&addr.wrapping_add(imm as u64)) {
+                                sites.push(addr);
+                            }
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        return Ok(sites);
+    }
+}
+
+// (stub address, stub size, GOT slot, JMPREL index)
+type Stub = (u64, u64, Option<u64>, Option<u32>);
+
+fn x86_64_stubs(shdr: &shdr::SectionHeader, content: &Vec<u8>) -> Vec<Stub> {
+    let entsize = if shdr.entsize != 0 { shdr.entsize as usize } else { 16 };
+    let mut stubs = vec![];
+
+    for (i, entry) in content.chunks(entsize).enumerate() {
+        let addr = shdr.addr + (i * entsize) as u64;
+        let mut slot = None;
+        let mut index = None;
+
+        for p in 0..entry.len() {
+            // jmp *disp32(%rip), possibly with a bnd prefix in front
+            if slot.is_none() && entry[p] == 0xff && p + 6 <= entry.len() && entry[p+1] == 0x25 {
+                let disp = LittleEndian::read_i32(&entry[p+2..p+6]) as i64;
+                slot = Some((addr + p as u64 + 6).wrapping_add(disp as u64));
+            }
+            // push imm32, the JMPREL index used for lazy binding
+            if index.is_none() && entry[p] == 0x68 && p + 5 <= entry.len() {
+                index = Some(LittleEndian::read_u32(&entry[p+1..p+5]));
+            }
+        }
+
+        stubs.push((addr, entsize as u64, slot, index));
+    }
+
+    return stubs;
+}
+
+fn aarch64_stubs(shdr: &shdr::SectionHeader, content: &Vec<u8>) -> Vec<Stub> {
+    let insns: Vec<u32> = content.chunks_exact(4).map(|i| LittleEndian::read_u32(i)).collect();
+    let mut stubs: Vec<Stub> = vec![];
+
+    for i in 0..insns.len().saturating_sub(1) {
+        let pc = shdr.addr + i as u64 * 4;
+        let adrp = insns[i];
+        let ldr = insns[i+1];
+
+        // adrp x16, page
+        if adrp & 0x9f00001f != 0x90000010 {
+            continue;
+        }
+        // ldr x17, [x16, #imm]
+        if ldr & 0xffc003ff != 0xf9400211 {
+            continue;
+        }
+
+        let immlo = ((adrp >> 29) & 0x3) as i64;
+        let immhi = ((adrp >> 5) & 0x7ffff) as i64;
+        let imm = (((immhi << 2) | immlo) << 43) >> 31;
+        let page = (pc & !0xfff).wrapping_add(imm as u64);
+        let slot = page + ((ldr >> 10) & 0xfff) as u64 * 8;
+
+        // a landing pad in front of the stub belongs to it
+        let start = if i > 0 && insns[i-1] == 0xd503245f { pc - 4 } else { pc };
+        if let Some(last) = stubs.last_mut() {
+            last.1 = start - last.0;
+        }
+        stubs.push((start, 16, Some(slot), None));
+    }
+
+    return stubs;
+}
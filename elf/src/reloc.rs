@@ -0,0 +1,843 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_class;
+use crate::Elf_arch;
+use crate::shdr;
+use crate::symbol::Symbol;
+use crate::dynamic::Dyn_tag;
+use crate::packed;
+use crate::phdr::Phdr_flags;
+
+use byteorder::*;
+
+// A relocation entry. Entries from REL tables carry an implicit addend, which
+// is stored at the patched location, so `addend` is 0 for those.
+#[derive(Clone)]
+pub struct Relocation {
+    pub offset: u64,
+    pub r_type: u32,
+    pub sym: u32,
+    pub addend: i64,
+    // name of the referenced symbol, empty for sym == 0
+    pub symbol: String
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Reloc_format {
+    REL,
+    RELA,
+    // packed relative relocations, SHT_RELR / DT_RELR
+    RELR,
+    // Android APS2 packed tables, SHT_ANDROID_REL(A) / DT_ANDROID_REL(A)
+    ANDROID_REL,
+    ANDROID_RELA
+}
+
+pub struct RelocationTable {
+    // section name, or the dynamic tag the table was found through
+    pub name: String,
+    pub format: Reloc_format,
+    pub rela: bool,
+    // file range the encoded table occupies
+    pub offset: u64,
+    pub size: u64,
+    pub entries: Vec<Relocation>
+}
+
+impl RelocationTable {
+    // encode the entries in the format of the table
+    pub fn encode(&self, class: Elf_class) -> Result<Vec<u8>> {
+        return match self.format {
+            Reloc_format::REL => Ok(to_le(&self.entries, class, false)),
+            Reloc_format::RELA => Ok(to_le(&self.entries, class, true)),
+            Reloc_format::RELR => packed::encode_relr(&self.entries.iter().map(|r| r.offset).collect(), class),
+            Reloc_format::ANDROID_REL => Ok(packed::encode_aps2(&self.entries, class, false)),
+            Reloc_format::ANDROID_RELA => Ok(packed::encode_aps2(&self.entries, class, true)),
+        }
+    }
+}
+
+impl Relocation {
+
+    pub fn parse(entry: &[u8], class: Elf_class, rela: bool) -> Result<Relocation> {
+        let (offset, r_type, sym, addend) = match class {
+            Elf_class::ELF64 => {
+                let info = LittleEndian::read_u64(&entry[0x8..0x10]);
+                let addend = if rela { LittleEndian::read_i64(&entry[0x10..0x18]) } else { 0 };
+                (LittleEndian::read_u64(&entry[0x0..0x8]), info as u32, (info >> 32) as u32, addend)
+            },
+            Elf_class::ELF32 => {
+                let info = LittleEndian::read_u32(&entry[0x4..0x8]);
+                let addend = if rela { LittleEndian::read_i32(&entry[0x8..0xC]) as i64 } else { 0 };
+                (LittleEndian::read_u32(&entry[0x0..0x4]) as u64, info & 0xff, info >> 8, addend)
+            }
+        };
+
+        return Ok(Relocation {
+            offset,
+            r_type,
+            sym,
+            addend,
+            symbol: String::new()
+        });
+    }
+
+    pub fn to_le(&self, class: Elf_class, rela: bool) -> Vec<u8> {
+        let mut bin = vec![];
+
+        match class {
+            Elf_class::ELF64 => {
+                let info = ((self.sym as u64) << 32) | self.r_type as u64;
+                bin.extend_from_slice(&self.offset.to_le_bytes());
+                bin.extend_from_slice(&info.to_le_bytes());
+                if rela {
+                    bin.extend_from_slice(&self.addend.to_le_bytes());
+                }
+            },
+            Elf_class::ELF32 => {
+                let info = (self.sym << 8) | (self.r_type & 0xff);
+                bin.extend_from_slice(&(self.offset as u32).to_le_bytes());
+                bin.extend_from_slice(&info.to_le_bytes());
+                if rela {
+                    bin.extend_from_slice(&(self.addend as i32).to_le_bytes());
+                }
+            }
+        }
+
+        return bin;
+    }
+
+    pub fn type_name(&self, arch: Elf_arch) -> &'static str {
+        return type_name(arch, self.r_type);
+    }
+}
+
+pub fn entry_size(class: Elf_class, rela: bool) -> usize {
+    return match (class, rela) {
+        (Elf_class::ELF64, true) => 0x18,
+        (Elf_class::ELF64, false) => 0x10,
+        (Elf_class::ELF32, true) => 0xC,
+        (Elf_class::ELF32, false) => 0x8
+    }
+}
+
+pub fn parse_relocations(bin: &[u8], class: Elf_class, rela: bool) -> Result<Vec<Relocation>> {
+    let mut relocs = vec![];
+
+    for entry in bin.chunks_exact(entry_size(class, rela)) {
+        relocs.push(Relocation::parse(entry, class, rela)?);
+    }
+
+    return Ok(relocs);
+}
+
+pub fn to_le(relocs: &Vec<Relocation>, class: Elf_class, rela: bool) -> Vec<u8> {
+    let mut bin = vec![];
+
+    for reloc in relocs {
+        bin.extend(reloc.to_le(class, rela));
+    }
+
+    return bin;
+}
+
+fn decode(bin: &[u8], format: Reloc_format, class: Elf_class, arch: Elf_arch) -> Result<Vec<Relocation>> {
+    return match format {
+        Reloc_format::REL => parse_relocations(bin, class, false),
+        Reloc_format::RELA => parse_relocations(bin, class, true),
+        Reloc_format::ANDROID_REL => packed::decode_aps2(bin, class, false),
+        Reloc_format::ANDROID_RELA => packed::decode_aps2(bin, class, true),
+        Reloc_format::RELR => Ok(packed::decode_relr(bin, class).iter().map(|offset| Relocation {
+            offset: *offset,
+            r_type: relative_type(arch),
+            sym: 0,
+            addend: 0,
+            symbol: String::new()
+        }).collect())
+    }
+}
+
+fn resolve_symbols(relocs: &mut Vec<Relocation>, syms: &Vec<Symbol>) {
+    for reloc in relocs {
+        if let Some(sym) = syms.get(reloc.sym as usize) {
+            reloc.symbol = sym.name.clone();
+        }
+    }
+}
+
+impl Elf {
+    // the name of a relocation type for the architecture of this binary
+    pub fn reloc_type_name(&self, r_type: u32) -> &'static str {
+        return type_name(self.header.e_arch, r_type);
+    }
+
+    // all relocation sections, with symbols resolved through their linked symbol table
+    pub fn section_relocations(&self) -> Result<Vec<RelocationTable>> {
+        let mut tables = vec![];
+
+        for shdr in &self.shdrs {
+            let format = match shdr.sh_type {
+                shdr::Shdr_type::RELA => Reloc_format::RELA,
+                shdr::Shdr_type::REL => Reloc_format::REL,
+                shdr::Shdr_type::RELR => Reloc_format::RELR,
+                shdr::Shdr_type::ANDROID_RELR => Reloc_format::RELR,
+                shdr::Shdr_type::ANDROID_REL => Reloc_format::ANDROID_REL,
+                shdr::Shdr_type::ANDROID_RELA => Reloc_format::ANDROID_RELA,
+                _ => continue
+            };
+
+            let bin = self.raw.get(shdr.offset as usize..(shdr.offset+shdr.size) as usize).ok_or(ParsingError::ParsingError)?;
+            let mut entries = decode(&bin, format, self.header.e_class, self.header.e_arch)?;
+
+            if shdr.link != 0 && format != Reloc_format::RELR {
+                let symtab = self.shdrs.get(shdr.link as usize).ok_or(ParsingError::ParsingError)?;
+                resolve_symbols(&mut entries, &self.section_symbols(symtab)?);
+            }
+
+            tables.push(RelocationTable {
+                name: shdr.name.clone(),
+                format,
+                rela: format == Reloc_format::RELA || format == Reloc_format::ANDROID_RELA,
+                offset: shdr.offset,
+                size: shdr.size,
+                entries
+            });
+        }
+
+        return Ok(tables);
+    }
+
+    // the relocations the dynamic loader applies, found through DT_RELA, DT_REL,
+    // DT_JMPREL, DT_RELR and the Android packed tables
+    pub fn dynamic_relocations(&self) -> Result<Vec<RelocationTable>> {
+        let mut tables = vec![];
+        let dynsyms = self.dynamic_symbols()?;
+        let pltrel = match self.dynamic_value(Dyn_tag::PLTREL) {
+            Some(t) if t == Dyn_tag::REL as u64 => Reloc_format::REL,
+            _ => Reloc_format::RELA
+        };
+
+        for (name, addr_tag, size_tag, format) in dynamic_tables(pltrel).iter() {
+            let addr = match self.dynamic_value(*addr_tag) {
+                Some(addr) => addr,
+                None => continue
+            };
+            let size = self.dynamic_value(*size_tag).unwrap_or(0);
+            let offset = self.vaddr_to_offset(addr).ok_or(ParsingError::ParsingError)?;
+            let bin = self.read_vaddr(addr, size).ok_or(ParsingError::ParsingError)?;
+            let mut entries = decode(&bin, *format, self.header.e_class, self.header.e_arch)?;
+
+            if dynsyms.len() > 0 {
+                resolve_symbols(&mut entries, &dynsyms);
+            } else {
+                for reloc in &mut entries {
+                    if reloc.sym == 0 {
+                        continue;
+                    }
+                    if let Some(sym) = self.dynamic_symbol(reloc.sym) {
+                        reloc.symbol = sym.name;
+                    }
+                }
+            }
+
+            tables.push(RelocationTable {
+                name: String::from(*name),
+                format: *format,
+                rela: *format == Reloc_format::RELA || *format == Reloc_format::ANDROID_RELA,
+                offset,
+                size,
+                entries
+            });
+        }
+
+        return Ok(tables);
+    }
+
+    // Re-encode a table after relocations have been added or removed. A
+    // table that still fits goes back into the space it was parsed from, the
+    // leftover space is filled with entries that relocate nothing. A table
+    // that grew is moved, see move_relocations. The dynamic tags and the
+    // section header of the table are updated.
+    pub fn write_relocations(&mut self, table: &RelocationTable) -> Result<()> {
+        let class = self.header.e_class;
+        let mut bin = table.encode(class)?;
+
+        // the dynamic tags (address, size) pointing to this table
+        let tags: Vec<(Dyn_tag, Dyn_tag)> = dynamic_tables(table.format).iter()
+            .filter(|(_, addr_tag, _, _)| self.dynamic_value(*addr_tag).and_then(|addr| self.vaddr_to_offset(addr)) == Some(table.offset))
+            .map(|(_, addr_tag, size_tag, _)| (*addr_tag, *size_tag))
+            .collect();
+
+        if bin.len() as u64 > table.size {
+            self.move_relocations(table, bin, &tags)?;
+            return self.set_relative_count(table, &tags);
+        }
+        let used = bin.len() as u64;
+
+        // R_*_NONE entries are all zero, an APS2 stream ignores trailing bytes
+        let filler = match table.format {
+            Reloc_format::RELR => packed::relr_padding(class),
+            _ => vec![0]
+        };
+        while (bin.len() as u64) < table.size {
+            bin.extend_from_slice(&filler);
+        }
+        bin.truncate(table.size as usize);
+
+        let start = table.offset as usize;
+        let end = (table.offset + table.size) as usize;
+        if end > self.raw.len() {
+            return Err(ParsingError::ParsingError);
+        }
+        self.raw.splice(start..end, bin);
+
+        for (_, size_tag) in &tags {
+            self.set_dynamic_value(*size_tag, used)?;
+        }
+
+        for shdr in &mut self.shdrs {
+            if shdr.offset == table.offset && shdr.size == table.size {
+                shdr.size = used;
+            }
+        }
+
+        return self.set_relative_count(table, &tags);
+    }
+
+    // A table that grew doesn't fit in its place. One the loader finds through
+    // the dynamic table is injected in a new load segment and its tags point
+    // there, a table that is only a section, as in objects, is moved in the
+    // file. A mapped table without dynamic tags can't be found by anything
+    // that would need to know it moved.
+    fn move_relocations(&mut self, table: &RelocationTable, bin: Vec<u8>, tags: &Vec<(Dyn_tag, Dyn_tag)>) -> Result<()> {
+        let index = self.shdrs.iter().enumerate().skip(1).find(|(_, s)| s.offset == table.offset && s.size == table.size).map(|(i, _)| i);
+
+        if tags.is_empty() {
+            let index = index.ok_or(ParsingError::Refused(format!("{} grew to {:#x} bytes and has no section or dynamic tag to move it with", table.name, bin.len())))?;
+            if self.shdrs[index].flags & shdr::Shdr_flags::ALLOC as u64 != 0 && !self.phdrs.is_empty() {
+                return Err(ParsingError::Refused(format!("{} grew to {:#x} bytes, it is mapped without a dynamic tag pointing to it so it can't be moved", table.name, bin.len())));
+            }
+            return self.set_section_content(index, bin);
+        }
+
+        let size = bin.len() as u64;
+        let addr = self.inject(&bin, Phdr_flags::R as u32)?;
+        for (addr_tag, size_tag) in tags {
+            self.set_dynamic_value(*addr_tag, addr)?;
+            self.set_dynamic_value(*size_tag, size)?;
+        }
+        if let Some(index) = index {
+            let offset = self.vaddr_to_offset(addr).ok_or(ParsingError::ParsingError)?;
+            let shdr = &mut self.shdrs[index];
+            shdr.addr = addr;
+            shdr.offset = offset;
+            shdr.size = size;
+        }
+        return Ok(());
+    }
+
+    // DT_RELACOUNT and DT_RELCOUNT hold the number of relative relocations at
+    // the start of DT_RELA and DT_REL, the loader applies those without
+    // checking their type. Count them again for the new entries.
+    fn set_relative_count(&mut self, table: &RelocationTable, tags: &Vec<(Dyn_tag, Dyn_tag)>) -> Result<()> {
+        let relative = relative_type(self.header.e_arch);
+        let count = table.entries.iter().take_while(|r| r.r_type == relative).count() as u64;
+
+        for (addr_tag, _) in tags {
+            let count_tag = match addr_tag {
+                Dyn_tag::RELA => Dyn_tag::RELACOUNT,
+                Dyn_tag::REL => Dyn_tag::RELCOUNT,
+                _ => continue
+            };
+            if self.dynamic_value(count_tag).is_some() {
+                self.set_dynamic_value(count_tag, count)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+// the dynamic tags describing relocation tables: (name, address tag, size tag, format)
+fn dynamic_tables(pltrel: Reloc_format) -> [(&'static str, Dyn_tag, Dyn_tag, Reloc_format); 7] {
+    return [
+        ("DT_RELA", Dyn_tag::RELA, Dyn_tag::RELASZ, Reloc_format::RELA),
+        ("DT_REL", Dyn_tag::REL, Dyn_tag::RELSZ, Reloc_format::REL),
+        ("DT_JMPREL", Dyn_tag::JMPREL, Dyn_tag::PLTRELSZ, pltrel),
+        ("DT_RELR", Dyn_tag::RELR, Dyn_tag::RELRSZ, Reloc_format::RELR),
+        ("DT_ANDROID_RELR", Dyn_tag::ANDROID_RELR, Dyn_tag::ANDROID_RELRSZ, Reloc_format::RELR),
+        ("DT_ANDROID_REL", Dyn_tag::ANDROID_REL, Dyn_tag::ANDROID_RELSZ, Reloc_format::ANDROID_REL),
+        ("DT_ANDROID_RELA", Dyn_tag::ANDROID_RELA, Dyn_tag::ANDROID_RELASZ, Reloc_format::ANDROID_RELA),
+    ];
+}
+
+// the relative relocation type of an architecture, which RELR entries expand to
+pub fn relative_type(arch: Elf_arch) -> u32 {
+    return match arch {
+        Elf_arch::AMD64 => 8,
+        Elf_arch::X86 => 8,
+        Elf_arch::AARCH64 => 1027,
+        Elf_arch::ARM => 23,
+        Elf_arch::RISCV => 3,
+        _ => 0
+    }
+}
+
+pub fn type_name(arch: Elf_arch, r_type: u32) -> &'static str {
+    return match arch {
+        Elf_arch::AMD64 => x86_64_type_name(r_type),
+        Elf_arch::X86 => i386_type_name(r_type),
+        Elf_arch::AARCH64 => aarch64_type_name(r_type),
+        Elf_arch::ARM => arm_type_name(r_type),
+        Elf_arch::RISCV => riscv_type_name(r_type),
+        _ => "UNKNOWN"
+    }
+}
+
+fn x86_64_type_name(r_type: u32) -> &'static str {
+    return match r_type {
+        0 => "R_X86_64_NONE",
+        1 => "R_X86_64_64",
+        2 => "R_X86_64_PC32",
+        3 => "R_X86_64_GOT32",
+        4 => "R_X86_64_PLT32",
+        5 => "R_X86_64_COPY",
+        6 => "R_X86_64_GLOB_DAT",
+        7 => "R_X86_64_JUMP_SLOT",
+        8 => "R_X86_64_RELATIVE",
+        9 => "R_X86_64_GOTPCREL",
+        10 => "R_X86_64_32",
+        11 => "R_X86_64_32S",
+        12 => "R_X86_64_16",
+        13 => "R_X86_64_PC16",
+        14 => "R_X86_64_8",
+        15 => "R_X86_64_PC8",
+        16 => "R_X86_64_DTPMOD64",
+        17 => "R_X86_64_DTPOFF64",
+        18 => "R_X86_64_TPOFF64",
+        19 => "R_X86_64_TLSGD",
+        20 => "R_X86_64_TLSLD",
+        21 => "R_X86_64_DTPOFF32",
+        22 => "R_X86_64_GOTTPOFF",
+        23 => "R_X86_64_TPOFF32",
+        24 => "R_X86_64_PC64",
+        25 => "R_X86_64_GOTOFF64",
+        26 => "R_X86_64_GOTPC32",
+        27 => "R_X86_64_GOT64",
+        28 => "R_X86_64_GOTPCREL64",
+        29 => "R_X86_64_GOTPC64",
+        30 => "R_X86_64_GOTPLT64",
+        31 => "R_X86_64_PLTOFF64",
+        32 => "R_X86_64_SIZE32",
+        33 => "R_X86_64_SIZE64",
+        34 => "R_X86_64_GOTPC32_TLSDESC",
+        35 => "R_X86_64_TLSDESC_CALL",
+        36 => "R_X86_64_TLSDESC",
+        37 => "R_X86_64_IRELATIVE",
+        38 => "R_X86_64_RELATIVE64",
+        41 => "R_X86_64_GOTPCRELX",
+        42 => "R_X86_64_REX_GOTPCRELX",
+        _ => "UNKNOWN"
+    }
+}
+
+fn i386_type_name(r_type: u32) -> &'static str {
+    return match r_type {
+        0 => "R_386_NONE",
+        1 => "R_386_32",
+        2 => "R_386_PC32",
+        3 => "R_386_GOT32",
+        4 => "R_386_PLT32",
+        5 => "R_386_COPY",
+        6 => "R_386_GLOB_DAT",
+        7 => "R_386_JMP_SLOT",
+        8 => "R_386_RELATIVE",
+        9 => "R_386_GOTOFF",
+        10 => "R_386_GOTPC",
+        11 => "R_386_32PLT",
+        14 => "R_386_TLS_TPOFF",
+        15 => "R_386_TLS_IE",
+        16 => "R_386_TLS_GOTIE",
+        17 => "R_386_TLS_LE",
+        18 => "R_386_TLS_GD",
+        19 => "R_386_TLS_LDM",
+        20 => "R_386_16",
+        21 => "R_386_PC16",
+        22 => "R_386_8",
+        23 => "R_386_PC8",
+        24 => "R_386_TLS_GD_32",
+        25 => "R_386_TLS_GD_PUSH",
+        26 => "R_386_TLS_GD_CALL",
+        27 => "R_386_TLS_GD_POP",
+        28 => "R_386_TLS_LDM_32",
+        29 => "R_386_TLS_LDM_PUSH",
+        30 => "R_386_TLS_LDM_CALL",
+        31 => "R_386_TLS_LDM_POP",
+        32 => "R_386_TLS_LDO_32",
+        33 => "R_386_TLS_IE_32",
+        34 => "R_386_TLS_LE_32",
+        35 => "R_386_TLS_DTPMOD32",
+        36 => "R_386_TLS_DTPOFF32",
+        37 => "R_386_TLS_TPOFF32",
+        38 => "R_386_SIZE32",
+        39 => "R_386_TLS_GOTDESC",
+        40 => "R_386_TLS_DESC_CALL",
+        41 => "R_386_TLS_DESC",
+        42 => "R_386_IRELATIVE",
+        43 => "R_386_GOT32X",
+        _ => "UNKNOWN"
+    }
+}
+
+fn aarch64_type_name(r_type: u32) -> &'static str {
+    return match r_type {
+        0 => "R_AARCH64_NONE",
+        256 => "R_AARCH64_NONE",
+        257 => "R_AARCH64_ABS64",
+        258 => "R_AARCH64_ABS32",
+        259 => "R_AARCH64_ABS16",
+        260 => "R_AARCH64_PREL64",
+        261 => "R_AARCH64_PREL32",
+        262 => "R_AARCH64_PREL16",
+        263 => "R_AARCH64_MOVW_UABS_G0",
+        264 => "R_AARCH64_MOVW_UABS_G0_NC",
+        265 => "R_AARCH64_MOVW_UABS_G1",
+        266 => "R_AARCH64_MOVW_UABS_G1_NC",
+        267 => "R_AARCH64_MOVW_UABS_G2",
+        268 => "R_AARCH64_MOVW_UABS_G2_NC",
+        269 => "R_AARCH64_MOVW_UABS_G3",
+        270 => "R_AARCH64_MOVW_SABS_G0",
+        271 => "R_AARCH64_MOVW_SABS_G1",
+        272 => "R_AARCH64_MOVW_SABS_G2",
+        273 => "R_AARCH64_LD_PREL_LO19",
+        274 => "R_AARCH64_ADR_PREL_LO21",
+        275 => "R_AARCH64_ADR_PREL_PG_HI21",
+        276 => "R_AARCH64_ADR_PREL_PG_HI21_NC",
+        277 => "R_AARCH64_ADD_ABS_LO12_NC",
+        278 => "R_AARCH64_LDST8_ABS_LO12_NC",
+        279 => "R_AARCH64_TSTBR14",
+        280 => "R_AARCH64_CONDBR19",
+        282 => "R_AARCH64_JUMP26",
+        283 => "R_AARCH64_CALL26",
+        284 => "R_AARCH64_LDST16_ABS_LO12_NC",
+        285 => "R_AARCH64_LDST32_ABS_LO12_NC",
+        286 => "R_AARCH64_LDST64_ABS_LO12_NC",
+        287 => "R_AARCH64_MOVW_PREL_G0",
+        288 => "R_AARCH64_MOVW_PREL_G0_NC",
+        289 => "R_AARCH64_MOVW_PREL_G1",
+        290 => "R_AARCH64_MOVW_PREL_G1_NC",
+        291 => "R_AARCH64_MOVW_PREL_G2",
+        292 => "R_AARCH64_MOVW_PREL_G2_NC",
+        293 => "R_AARCH64_MOVW_PREL_G3",
+        299 => "R_AARCH64_LDST128_ABS_LO12_NC",
+        300 => "R_AARCH64_MOVW_GOTOFF_G0",
+        301 => "R_AARCH64_MOVW_GOTOFF_G0_NC",
+        302 => "R_AARCH64_MOVW_GOTOFF_G1",
+        303 => "R_AARCH64_MOVW_GOTOFF_G1_NC",
+        304 => "R_AARCH64_MOVW_GOTOFF_G2",
+        305 => "R_AARCH64_MOVW_GOTOFF_G2_NC",
+        306 => "R_AARCH64_MOVW_GOTOFF_G3",
+        307 => "R_AARCH64_GOTREL64",
+        308 => "R_AARCH64_GOTREL32",
+        309 => "R_AARCH64_GOT_LD_PREL19",
+        310 => "R_AARCH64_LD64_GOTOFF_LO15",
+        311 => "R_AARCH64_ADR_GOT_PAGE",
+        312 => "R_AARCH64_LD64_GOT_LO12_NC",
+        313 => "R_AARCH64_LD64_GOTPAGE_LO15",
+        512 => "R_AARCH64_TLSGD_ADR_PREL21",
+        513 => "R_AARCH64_TLSGD_ADR_PAGE21",
+        514 => "R_AARCH64_TLSGD_ADD_LO12_NC",
+        515 => "R_AARCH64_TLSGD_MOVW_G1",
+        516 => "R_AARCH64_TLSGD_MOVW_G0_NC",
+        517 => "R_AARCH64_TLSLD_ADR_PREL21",
+        518 => "R_AARCH64_TLSLD_ADR_PAGE21",
+        519 => "R_AARCH64_TLSLD_ADD_LO12_NC",
+        520 => "R_AARCH64_TLSLD_MOVW_G1",
+        521 => "R_AARCH64_TLSLD_MOVW_G0_NC",
+        522 => "R_AARCH64_TLSLD_LD_PREL19",
+        523 => "R_AARCH64_TLSLD_MOVW_DTPREL_G2",
+        524 => "R_AARCH64_TLSLD_MOVW_DTPREL_G1",
+        525 => "R_AARCH64_TLSLD_MOVW_DTPREL_G1_NC",
+        526 => "R_AARCH64_TLSLD_MOVW_DTPREL_G0",
+        527 => "R_AARCH64_TLSLD_MOVW_DTPREL_G0_NC",
+        528 => "R_AARCH64_TLSLD_ADD_DTPREL_HI12",
+        529 => "R_AARCH64_TLSLD_ADD_DTPREL_LO12",
+        530 => "R_AARCH64_TLSLD_ADD_DTPREL_LO12_NC",
+        531 => "R_AARCH64_TLSLD_LDST8_DTPREL_LO12",
+        532 => "R_AARCH64_TLSLD_LDST8_DTPREL_LO12_NC",
+        533 => "R_AARCH64_TLSLD_LDST16_DTPREL_LO12",
+        534 => "R_AARCH64_TLSLD_LDST16_DTPREL_LO12_NC",
+        535 => "R_AARCH64_TLSLD_LDST32_DTPREL_LO12",
+        536 => "R_AARCH64_TLSLD_LDST32_DTPREL_LO12_NC",
+        537 => "R_AARCH64_TLSLD_LDST64_DTPREL_LO12",
+        538 => "R_AARCH64_TLSLD_LDST64_DTPREL_LO12_NC",
+        539 => "R_AARCH64_TLSIE_MOVW_GOTTPREL_G1",
+        540 => "R_AARCH64_TLSIE_MOVW_GOTTPREL_G0_NC",
+        541 => "R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21",
+        542 => "R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC",
+        543 => "R_AARCH64_TLSIE_LD_GOTTPREL_PREL19",
+        544 => "R_AARCH64_TLSLE_MOVW_TPREL_G2",
+        545 => "R_AARCH64_TLSLE_MOVW_TPREL_G1",
+        546 => "R_AARCH64_TLSLE_MOVW_TPREL_G1_NC",
+        547 => "R_AARCH64_TLSLE_MOVW_TPREL_G0",
+        548 => "R_AARCH64_TLSLE_MOVW_TPREL_G0_NC",
+        549 => "R_AARCH64_TLSLE_ADD_TPREL_HI12",
+        550 => "R_AARCH64_TLSLE_ADD_TPREL_LO12",
+        551 => "R_AARCH64_TLSLE_ADD_TPREL_LO12_NC",
+        552 => "R_AARCH64_TLSLE_LDST8_TPREL_LO12",
+        553 => "R_AARCH64_TLSLE_LDST8_TPREL_LO12_NC",
+        554 => "R_AARCH64_TLSLE_LDST16_TPREL_LO12",
+        555 => "R_AARCH64_TLSLE_LDST16_TPREL_LO12_NC",
+        556 => "R_AARCH64_TLSLE_LDST32_TPREL_LO12",
+        557 => "R_AARCH64_TLSLE_LDST32_TPREL_LO12_NC",
+        558 => "R_AARCH64_TLSLE_LDST64_TPREL_LO12",
+        559 => "R_AARCH64_TLSLE_LDST64_TPREL_LO12_NC",
+        560 => "R_AARCH64_TLSDESC_LD_PREL19",
+        561 => "R_AARCH64_TLSDESC_ADR_PREL21",
+        562 => "R_AARCH64_TLSDESC_ADR_PAGE21",
+        563 => "R_AARCH64_TLSDESC_LD64_LO12",
+        564 => "R_AARCH64_TLSDESC_ADD_LO12",
+        565 => "R_AARCH64_TLSDESC_OFF_G1",
+        566 => "R_AARCH64_TLSDESC_OFF_G0_NC",
+        567 => "R_AARCH64_TLSDESC_LDR",
+        568 => "R_AARCH64_TLSDESC_ADD",
+        569 => "R_AARCH64_TLSDESC_CALL",
+        1024 => "R_AARCH64_COPY",
+        1025 => "R_AARCH64_GLOB_DAT",
+        1026 => "R_AARCH64_JUMP_SLOT",
+        1027 => "R_AARCH64_RELATIVE",
+        1028 => "R_AARCH64_TLS_DTPMOD64",
+        1029 => "R_AARCH64_TLS_DTPREL64",
+        1030 => "R_AARCH64_TLS_TPREL64",
+        1031 => "R_AARCH64_TLSDESC",
+        1032 => "R_AARCH64_IRELATIVE",
+        _ => "UNKNOWN"
+    }
+}
+
+fn arm_type_name(r_type: u32) -> &'static str {
+    return match r_type {
+        0 => "R_ARM_NONE",
+        1 => "R_ARM_PC24",
+        2 => "R_ARM_ABS32",
+        3 => "R_ARM_REL32",
+        4 => "R_ARM_LDR_PC_G0",
+        5 => "R_ARM_ABS16",
+        6 => "R_ARM_ABS12",
+        7 => "R_ARM_THM_ABS5",
+        8 => "R_ARM_ABS8",
+        9 => "R_ARM_SBREL32",
+        10 => "R_ARM_THM_CALL",
+        11 => "R_ARM_THM_PC8",
+        12 => "R_ARM_BREL_ADJ",
+        13 => "R_ARM_TLS_DESC",
+        14 => "R_ARM_THM_SWI8",
+        15 => "R_ARM_XPC25",
+        16 => "R_ARM_THM_XPC22",
+        17 => "R_ARM_TLS_DTPMOD32",
+        18 => "R_ARM_TLS_DTPOFF32",
+        19 => "R_ARM_TLS_TPOFF32",
+        20 => "R_ARM_COPY",
+        21 => "R_ARM_GLOB_DAT",
+        22 => "R_ARM_JUMP_SLOT",
+        23 => "R_ARM_RELATIVE",
+        24 => "R_ARM_GOTOFF32",
+        25 => "R_ARM_BASE_PREL",
+        26 => "R_ARM_GOT_BREL",
+        27 => "R_ARM_PLT32",
+        28 => "R_ARM_CALL",
+        29 => "R_ARM_JUMP24",
+        30 => "R_ARM_THM_JUMP24",
+        31 => "R_ARM_BASE_ABS",
+        32 => "R_ARM_ALU_PCREL_7_0",
+        33 => "R_ARM_ALU_PCREL_15_8",
+        34 => "R_ARM_ALU_PCREL_23_15",
+        35 => "R_ARM_LDR_SBREL_11_0_NC",
+        36 => "R_ARM_ALU_SBREL_19_12_NC",
+        37 => "R_ARM_ALU_SBREL_27_20_CK",
+        38 => "R_ARM_TARGET1",
+        39 => "R_ARM_SBREL31",
+        40 => "R_ARM_V4BX",
+        41 => "R_ARM_TARGET2",
+        42 => "R_ARM_PREL31",
+        43 => "R_ARM_MOVW_ABS_NC",
+        44 => "R_ARM_MOVT_ABS",
+        45 => "R_ARM_MOVW_PREL_NC",
+        46 => "R_ARM_MOVT_PREL",
+        47 => "R_ARM_THM_MOVW_ABS_NC",
+        48 => "R_ARM_THM_MOVT_ABS",
+        49 => "R_ARM_THM_MOVW_PREL_NC",
+        50 => "R_ARM_THM_MOVT_PREL",
+        51 => "R_ARM_THM_JUMP19",
+        52 => "R_ARM_THM_JUMP6",
+        53 => "R_ARM_THM_ALU_PREL_11_0",
+        54 => "R_ARM_THM_PC12",
+        55 => "R_ARM_ABS32_NOI",
+        56 => "R_ARM_REL32_NOI",
+        57 => "R_ARM_ALU_PC_G0_NC",
+        58 => "R_ARM_ALU_PC_G0",
+        59 => "R_ARM_ALU_PC_G1_NC",
+        60 => "R_ARM_ALU_PC_G1",
+        61 => "R_ARM_ALU_PC_G2",
+        62 => "R_ARM_LDR_PC_G1",
+        63 => "R_ARM_LDR_PC_G2",
+        64 => "R_ARM_LDRS_PC_G0",
+        65 => "R_ARM_LDRS_PC_G1",
+        66 => "R_ARM_LDRS_PC_G2",
+        67 => "R_ARM_LDC_PC_G0",
+        68 => "R_ARM_LDC_PC_G1",
+        69 => "R_ARM_LDC_PC_G2",
+        70 => "R_ARM_ALU_SB_G0_NC",
+        71 => "R_ARM_ALU_SB_G0",
+        72 => "R_ARM_ALU_SB_G1_NC",
+        73 => "R_ARM_ALU_SB_G1",
+        74 => "R_ARM_ALU_SB_G2",
+        75 => "R_ARM_LDR_SB_G0",
+        76 => "R_ARM_LDR_SB_G1",
+        77 => "R_ARM_LDR_SB_G2",
+        78 => "R_ARM_LDRS_SB_G0",
+        79 => "R_ARM_LDRS_SB_G1",
+        80 => "R_ARM_LDRS_SB_G2",
+        81 => "R_ARM_LDC_SB_G0",
+        82 => "R_ARM_LDC_SB_G1",
+        83 => "R_ARM_LDC_SB_G2",
+        84 => "R_ARM_MOVW_BREL_NC",
+        85 => "R_ARM_MOVT_BREL",
+        86 => "R_ARM_MOVW_BREL",
+        87 => "R_ARM_THM_MOVW_BREL_NC",
+        88 => "R_ARM_THM_MOVT_BREL",
+        89 => "R_ARM_THM_MOVW_BREL",
+        90 => "R_ARM_TLS_GOTDESC",
+        91 => "R_ARM_TLS_CALL",
+        92 => "R_ARM_TLS_DESCSEQ",
+        93 => "R_ARM_THM_TLS_CALL",
+        94 => "R_ARM_PLT32_ABS",
+        95 => "R_ARM_GOT_ABS",
+        96 => "R_ARM_GOT_PREL",
+        97 => "R_ARM_GOT_BREL12",
+        98 => "R_ARM_GOTOFF12",
+        99 => "R_ARM_GOTRELAX",
+        100 => "R_ARM_GNU_VTENTRY",
+        101 => "R_ARM_GNU_VTINHERIT",
+        102 => "R_ARM_THM_JUMP11",
+        103 => "R_ARM_THM_JUMP8",
+        104 => "R_ARM_TLS_GD32",
+        105 => "R_ARM_TLS_LDM32",
+        106 => "R_ARM_TLS_LDO32",
+        107 => "R_ARM_TLS_IE32",
+        108 => "R_ARM_TLS_LE32",
+        109 => "R_ARM_TLS_LDO12",
+        110 => "R_ARM_TLS_LE12",
+        111 => "R_ARM_TLS_IE12GP",
+        160 => "R_ARM_IRELATIVE",
+        _ => "UNKNOWN"
+    }
+}
+
+fn riscv_type_name(r_type: u32) -> &'static str {
+    return match r_type {
+        0 => "R_RISCV_NONE",
+        1 => "R_RISCV_32",
+        2 => "R_RISCV_64",
+        3 => "R_RISCV_RELATIVE",
+        4 => "R_RISCV_COPY",
+        5 => "R_RISCV_JUMP_SLOT",
+        6 => "R_RISCV_TLS_DTPMOD32",
+        7 => "R_RISCV_TLS_DTPMOD64",
+        8 => "R_RISCV_TLS_DTPREL32",
+        9 => "R_RISCV_TLS_DTPREL64",
+        10 => "R_RISCV_TLS_TPREL32",
+        11 => "R_RISCV_TLS_TPREL64",
+        12 => "R_RISCV_TLSDESC",
+        16 => "R_RISCV_BRANCH",
+        17 => "R_RISCV_JAL",
+        18 => "R_RISCV_CALL",
+        19 => "R_RISCV_CALL_PLT",
+        20 => "R_RISCV_GOT_HI20",
+        21 => "R_RISCV_TLS_GOT_HI20",
+        22 => "R_RISCV_TLS_GD_HI20",
+        23 => "R_RISCV_PCREL_HI20",
+        24 => "R_RISCV_PCREL_LO12_I",
+        25 => "R_RISCV_PCREL_LO12_S",
+        26 => "R_RISCV_HI20",
+        27 => "R_RISCV_LO12_I",
+        28 => "R_RISCV_LO12_S",
+        29 => "R_RISCV_TPREL_HI20",
+        30 => "R_RISCV_TPREL_LO12_I",
+        31 => "R_RISCV_TPREL_LO12_S",
+        32 => "R_RISCV_TPREL_ADD",
+        33 => "R_RISCV_ADD8",
+        34 => "R_RISCV_ADD16",
+        35 => "R_RISCV_ADD32",
+        36 => "R_RISCV_ADD64",
+        37 => "R_RISCV_SUB8",
+        38 => "R_RISCV_SUB16",
+        39 => "R_RISCV_SUB32",
+        40 => "R_RISCV_SUB64",
+        41 => "R_RISCV_GNU_VTINHERIT",
+        42 => "R_RISCV_GNU_VTENTRY",
+        43 => "R_RISCV_ALIGN",
+        44 => "R_RISCV_RVC_BRANCH",
+        45 => "R_RISCV_RVC_JUMP",
+        46 => "R_RISCV_RVC_LUI",
+        47 => "R_RISCV_GPREL_I",
+        48 => "R_RISCV_GPREL_S",
+        49 => "R_RISCV_TPREL_I",
+        50 => "R_RISCV_TPREL_S",
+        51 => "R_RISCV_RELAX",
+        52 => "R_RISCV_SUB6",
+        53 => "R_RISCV_SET6",
+        54 => "R_RISCV_SET8",
+        55 => "R_RISCV_SET16",
+        56 => "R_RISCV_SET32",
+        57 => "R_RISCV_32_PCREL",
+        58 => "R_RISCV_IRELATIVE",
+        59 => "R_RISCV_PLT32",
+        60 => "R_RISCV_SET_ULEB128",
+        61 => "R_RISCV_SUB_ULEB128",
+        62 => "R_RISCV_TLSDESC_HI20",
+        63 => "R_RISCV_TLSDESC_LOAD_LO12",
+        64 => "R_RISCV_TLSDESC_ADD_LO12",
+        65 => "R_RISCV_TLSDESC_CALL",
+        _ => "UNKNOWN"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic::Dyn_tag;
+
+    fn rela_table(elf: &crate::Elf) -> crate::reloc::RelocationTable {
+        return elf.dynamic_relocations().unwrap().into_iter().find(|t| t.name == "DT_RELA").unwrap();
+    }
+
+    #[test]
+    fn remove_relocation() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let mut table = rela_table(&elf);
+        let count = elf.dynamic_value(Dyn_tag::RELACOUNT).unwrap();
+        let removed = table.entries.remove(0);
+        elf.write_relocations(&table).unwrap();
+
+        let written = rela_table(&elf);
+        assert_eq!(written.size, table.size - 0x18);
+        assert_eq!(written.entries.iter().map(|r| r.offset).collect::<Vec<u64>>(), table.entries.iter().map(|r| r.offset).collect::<Vec<u64>>());
+        assert!(!written.entries.iter().any(|r| r.offset == removed.offset));
+        assert_eq!(elf.dynamic_value(Dyn_tag::RELACOUNT), Some(count - 1));
+    }
+
+    #[test]
+    fn grow_relocations() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let mut table = rela_table(&elf);
+        let old = elf.dynamic_value(Dyn_tag::RELA).unwrap();
+        let entry = table.entries[0].clone();
+        table.entries.insert(0, entry);
+        elf.write_relocations(&table).unwrap();
+
+        let written = rela_table(&elf);
+        assert_ne!(elf.dynamic_value(Dyn_tag::RELA), Some(old));
+        assert_eq!(written.entries.len(), table.entries.len());
+        assert_eq!(elf.dynamic_value(Dyn_tag::RELASZ), Some(table.size + 0x18));
+    }
+}
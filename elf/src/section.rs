@@ -1,16 +1,505 @@
-use crate::shdr::SectionHeader; 
+use crate::shdr::SectionHeader;
+use crate::shdr::Shdr_type;
+use crate::shdr::Shdr_flags;
+use crate::phdr::Phdr_flags;
+use crate::phdr::Phdr_type;
+use crate::Elf;
+use crate::Elf_type;
+use crate::compress;
+use crate::compress::Compression;
+use crate::buffer::Buffer;
+use crate::buffer::View;
+use crate::Elf_class;
+use crate::Result;
+use crate::ParsingError;
+use crate::symbol;
+use crate::symbol::Sym_type;
+use crate::reloc;
 
+// A section and its bytes in the file. The bytes are not copied, the section
+// is a view on the file content, so a write through the section is seen by the
+// Elf and its segments, and the other way around.
+#[derive(Clone)]
 pub struct Section {
     pub hdr: SectionHeader,
-    pub content: Vec<u8>
+    // the content is always uncompressed, this is how it is stored in the file
+    pub compression: Compression,
+    pub data: View,
+    class: Elf_class
 }
 
 impl Section {
-    // this is where we 
-    pub fn from(hdr: SectionHeader, bin: &Vec<u8>) -> Section {
-        Section { 
+    pub fn from(hdr: SectionHeader, buffer: &Buffer) -> Section {
+        let size = match hdr.sh_type {
+            Shdr_type::NOBITS => 0,
+            _ => hdr.size
+        };
+        let class = crate::parse_class(&buffer.bytes());
+        let data = buffer.view(hdr.offset, size);
+        return Section::view(hdr, data, class);
+    }
+
+    // a section on a view that isn't at sh_offset, like the content of a segment
+    pub fn view(hdr: SectionHeader, data: View, class: Elf_class) -> Section {
+        let compression = compress::compression(&hdr, &data.bytes());
+
+        Section {
             hdr,
-            content: vec![]
+            compression,
+            data,
+            class
+        }
+    }
+
+    // The content of the section. Compressed debug sections are decompressed
+    // transparently, content that fails to decompress is left as it is.
+    pub fn content(&self) -> Vec<u8> {
+        let bin = self.data.to_vec();
+        return match self.compression {
+            Compression::NONE => bin,
+            _ => match compress::decompress(&self.hdr, &bin, self.class) {
+                Ok((content, _)) => content,
+                Err(_) => bin
+            }
+        };
+    }
+
+    // Patch the content in place, the section keeps its size. Compressed
+    // content can't be patched, set_section_content replaces it as a whole.
+    pub fn write(&self, at: u64, bin: &[u8]) -> Result<()> {
+        if self.compression != Compression::NONE {
+            return Err(ParsingError::ParsingError);
+        }
+        return self.data.write(at, bin);
+    }
+}
+
+impl Elf {
+    // Add a section with `content` at the end of the section header table and
+    // return its index. In a linked file an SHF_ALLOC section is mapped by a
+    // new load segment with the permissions of its flags, in a relocatable
+    // object the linker places it. The file is laid out again afterwards.
+    pub fn add_section(&mut self, name: &str, sh_type: Shdr_type, flags: u64, content: &[u8]) -> Result<usize> {
+        if sh_type == Shdr_type::NOBITS || self.shdrs.is_empty() {
+            return Err(ParsingError::ParsingError);
+        }
+
+        // notes are read in 4 byte words unless they are aligned to 8
+        let align = if sh_type == Shdr_type::NOTE { 4 } else { 8 };
+        let mut shdr = SectionHeader::new(name, sh_type, flags, 0, 0, content.len() as u64, align);
+        let linked = if let Elf_type::REL = self.header.e_type { false } else { true };
+
+        if linked && flags & Shdr_flags::ALLOC as u64 != 0 {
+            let mut perm = Phdr_flags::R as u32;
+            if flags & Shdr_flags::WRITE as u64 != 0 {
+                perm |= Phdr_flags::W as u32;
+            }
+            if flags & Shdr_flags::EXECINSTR as u64 != 0 {
+                perm |= Phdr_flags::X as u32;
+            }
+            shdr.addr = self.add_segment(content, perm, shdr.addralign)?;
+            shdr.offset = self.vaddr_to_offset(shdr.addr).ok_or(ParsingError::ParsingError)?;
+        } else {
+            let offset = (self.raw.len() + 7) / 8 * 8;
+            self.raw.resize(offset, 0);
+            self.raw.extend_from_slice(content);
+            shdr.offset = offset as u64;
+        }
+
+        let mut table = self.shstrtab()?;
+        let size = table.len();
+        shdr.shstrndx_offset = table.add(name);
+        self.shdrs.push(shdr);
+        if table.len() != size {
+            self.set_section_content(self.header.shstrndx as usize, table.to_le())?;
+        }
+
+        self.layout()?;
+        return Ok(self.shdrs.len() - 1);
+    }
+}
+
+// does sh_info of this section hold a section index
+fn info_is_section(shdr: &SectionHeader) -> bool {
+    return shdr.flags & Shdr_flags::INFO_LINK as u64 != 0 || shdr.sh_type == Shdr_type::REL || shdr.sh_type == Shdr_type::RELA;
+}
+
+impl Elf {
+    // Remove a section and its header. The sections after it move down one
+    // index and the references to them in sh_link, sh_info, symbols and
+    // groups follow. The symbols defined in the removed section are dropped
+    // from the symbol table and the relocations and groups referring to the
+    // symbols after them are renumbered. A section that is still needed is
+    // refused with the reason: one referred to by another section (the
+    // string table of a symbol table, the target of relocations), one whose
+    // symbols relocations or dynamic symbols refer to, relocations whose
+    // target is still there, and the section name table. The file is laid
+    // out again, which drops the content of a section outside of the load
+    // segments.
+    pub fn remove_section(&mut self, name: &str) -> Result<()> {
+        let index = self.section_index(name).ok_or(ParsingError::Refused(format!("there is no section {}", name)))?;
+        self.remove_section_index(index)?;
+        return self.layout();
+    }
+
+    fn remove_section_index(&mut self, index: usize) -> Result<()> {
+        if index == 0 || index >= self.shdrs.len() {
+            return Err(ParsingError::Refused(format!("there is no section with index {} to remove", index)));
+        }
+        let name = self.shdrs[index].name.clone();
+        if index == self.header.shstrndx as usize {
+            return Err(ParsingError::Refused(format!("{} is the section name table, it can't be removed", name)));
+        }
+        for (i, shdr) in self.shdrs.iter().enumerate() {
+            if i != index && shdr.link as usize == index {
+                return Err(ParsingError::Refused(format!("{} is linked to by {}, it can't be removed", name, shdr.name)));
+            }
+            if i != index && (shdr.sh_type == Shdr_type::REL || shdr.sh_type == Shdr_type::RELA) && shdr.info as usize == index {
+                return Err(ParsingError::Refused(format!("the relocations in {} apply to {}, it can't be removed", shdr.name, name)));
+            }
+            if i != index && info_is_section(shdr) && shdr.info as usize == index {
+                return Err(ParsingError::Refused(format!("{} is referred to by sh_info of {}, it can't be removed", name, shdr.name)));
+            }
+        }
+
+        // relocations without their target would leave it unrelocated
+        let shdr = self.shdrs[index].clone();
+        if (shdr.sh_type == Shdr_type::REL || shdr.sh_type == Shdr_type::RELA) && shdr.info != 0 {
+            if let Some(target) = self.shdrs.get(shdr.info as usize).filter(|s| s.sh_type != Shdr_type::NULL) {
+                return Err(ParsingError::Refused(format!("{} holds the relocations of {}, it can't be removed", name, target.name)));
+            }
+        }
+        self.check_section_symbols(index)?;
+
+        // the members of a removed group no longer belong to one
+        if let Some(group) = self.groups()?.into_iter().find(|g| g.index == index) {
+            for member in group.members {
+                if let Some(shdr) = self.shdrs.get_mut(member as usize) {
+                    shdr.flags &= !(Shdr_flags::GROUP as u64);
+                }
+            }
+        }
+
+        let emptied = self.remove_from_group(index)?;
+        self.drop_section_symbols(index)?;
+
+        self.shdrs.remove(index);
+        for shdr in &mut self.shdrs {
+            if shdr.link as usize > index {
+                shdr.link -= 1;
+            }
+            if info_is_section(shdr) && shdr.info as usize > index {
+                shdr.info -= 1;
+            }
+        }
+        if self.header.shstrndx as usize > index {
+            self.header.shstrndx -= 1;
+        }
+        self.renumber_groups(index)?;
+
+        // a group left without members goes as well
+        if let Some(group) = emptied {
+            return self.remove_section_index(if group > index { group - 1 } else { group });
+        }
+        return Ok(());
+    }
+
+    // Refuse the removal of a section while its symbols are in use: by a
+    // relocation, as a dynamic symbol or as the signature of a group that
+    // keeps other members.
+    fn check_section_symbols(&self, index: usize) -> Result<()> {
+        let name = &self.shdrs[index].name;
+
+        for (i, shdr) in self.shdrs.iter().enumerate() {
+            if i == index || (shdr.sh_type != Shdr_type::SYMTAB && shdr.sh_type != Shdr_type::DYNSYM) {
+                continue;
+            }
+            let syms = self.section_symbols(shdr)?;
+            let defined = |sym: u32| syms.get(sym as usize).map(|s| s.shndx as usize == index).unwrap_or(false);
+
+            if shdr.sh_type == Shdr_type::DYNSYM {
+                if let Some(sym) = syms.iter().find(|s| s.shndx as usize == index) {
+                    return Err(ParsingError::Refused(format!("{} defines the dynamic symbol {}, it can't be removed", name, sym.name)));
+                }
+                continue;
+            }
+
+            for (j, rel) in self.shdrs.iter().enumerate() {
+                if j == index || rel.link as usize != i || (rel.sh_type != Shdr_type::REL && rel.sh_type != Shdr_type::RELA) {
+                    continue;
+                }
+                let bin = self.raw.get(rel.offset as usize..(rel.offset + rel.size) as usize).ok_or(ParsingError::ParsingError)?;
+                for entry in reloc::parse_relocations(&bin, self.header.e_class, rel.sh_type == Shdr_type::RELA)? {
+                    if entry.sym != 0 && defined(entry.sym) {
+                        let sym = &syms[entry.sym as usize];
+                        let sym_name = if sym.sym_type() == Sym_type::SECTION { format!("the section symbol of {}", name) } else { sym.name.clone() };
+                        return Err(ParsingError::Refused(format!("a relocation in {} refers to {}, {} can't be removed", rel.name, sym_name, name)));
+                    }
+                }
+            }
+
+            for group in self.groups()? {
+                if group.index != index && group.symtab as usize == i && defined(group.sym_index) && group.members != vec![index as u32] {
+                    return Err(ParsingError::Refused(format!("the signature of the group {} is defined in {}, it can't be removed", group.signature, name)));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Drop the symbols defined in a section about to be removed and number
+    // the sections after it down by one in the others. The relocations and
+    // groups referring to the symbol table are renumbered along, the local
+    // symbols count in sh_info follows. The dynamic symbol tables keep
+    // their size, they are rewritten in place.
+    fn drop_section_symbols(&mut self, index: usize) -> Result<()> {
+        let class = self.header.e_class;
+
+        for i in 0..self.shdrs.len() {
+            let shdr = self.shdrs[i].clone();
+            if i == index || (shdr.sh_type != Shdr_type::SYMTAB && shdr.sh_type != Shdr_type::DYNSYM) {
+                continue;
+            }
+
+            let mut bin = vec![];
+            let mut renumber = vec![];
+            let mut locals = 0;
+            for (old, mut sym) in self.section_symbols(&shdr)?.into_iter().enumerate() {
+                if sym.shndx as usize == index && old != 0 {
+                    renumber.push(None);
+                    continue;
+                }
+                if sym.shndx as usize > index && sym.shndx < symbol::SHN_LORESERVE {
+                    sym.shndx -= 1;
+                }
+                if old < shdr.info as usize {
+                    locals += 1;
+                }
+                renumber.push(Some((bin.len() / symbol::entry_size(class)) as u32));
+                bin.extend(sym.to_le(class));
+            }
+
+            if bin.len() as u64 == shdr.size {
+                Section::from(shdr, &self.raw).write(0, &bin)?;
+                continue;
+            }
+            self.set_section_content(i, bin)?;
+            self.shdrs[i].info = locals;
+
+            for j in 0..self.shdrs.len() {
+                let rel = self.shdrs[j].clone();
+                if j == index || rel.link as usize != i {
+                    continue;
+                }
+                if rel.sh_type == Shdr_type::GROUP {
+                    // the signature of a group emptied by the removal is gone with it
+                    self.shdrs[j].info = renumber.get(rel.info as usize).cloned().flatten().unwrap_or(0);
+                }
+                if rel.sh_type == Shdr_type::REL || rel.sh_type == Shdr_type::RELA {
+                    let rela = rel.sh_type == Shdr_type::RELA;
+                    let bin = self.raw.get(rel.offset as usize..(rel.offset + rel.size) as usize).ok_or(ParsingError::ParsingError)?.to_vec();
+                    let mut entries = reloc::parse_relocations(&bin, class, rela)?;
+                    for entry in &mut entries {
+                        entry.sym = renumber.get(entry.sym as usize).cloned().flatten().ok_or(ParsingError::ParsingError)?;
+                    }
+                    Section::from(rel, &self.raw).write(0, &reloc::to_le(&entries, class, rela))?;
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl Elf {
+    // Give a section a new size, new bytes are zero. The content after it in
+    // the file moves along and the offsets and sizes in the headers follow.
+    // A mapped section can only change size at the end of its load segment,
+    // anywhere else the content after it would move to other addresses, and
+    // it can't grow into the next load segment. Those are refused with the
+    // reason, such a section can be copied to a new one with add_section.
+    pub fn resize_section(&mut self, name: &str, size: u64) -> Result<()> {
+        let index = self.section_index(name).filter(|i| *i != 0).ok_or(ParsingError::Refused(format!("there is no section {}", name)))?;
+        let shdr = self.shdrs[index].clone();
+        let linked = if let Elf_type::REL = self.header.e_type { false } else { true };
+        let page = self.load_align();
+
+        if linked && shdr.flags & Shdr_flags::ALLOC as u64 != 0 {
+            let end = shdr.addr + shdr.size;
+            let load = self.phdrs.iter().find(|p| if let Phdr_type::LOAD = p.p_type { shdr.addr >= p.vaddr && end <= p.vaddr + p.memsz } else { false }).cloned();
+            let load = load.ok_or(ParsingError::Refused(format!("{} is allocated but not in a load segment", name)))?;
+
+            if end != load.vaddr + load.memsz {
+                let next = self.shdrs.iter().filter(|s| s.flags & Shdr_flags::ALLOC as u64 != 0 && s.addr >= end && s.addr < load.vaddr + load.memsz)
+                    .min_by_key(|s| s.addr).map(|s| s.name.clone()).unwrap_or(String::from("other content"));
+                return Err(ParsingError::Refused(format!("{} is followed by {} in its load segment, resizing it would move {} to another address", name, next, next)));
+            }
+
+            let new_end = (shdr.addr + size + page - 1) / page * page;
+            for p in &self.phdrs {
+                if let Phdr_type::LOAD = p.p_type {
+                    if p.vaddr >= end && p.vaddr / page * page < new_end {
+                        return Err(ParsingError::Refused(format!("growing {} to {:#x} bytes would run into the load segment at {:#x}", name, size, p.vaddr)));
+                    }
+                }
+            }
+
+            // the load segment and the segments in it that end with the section
+            for p in &mut self.phdrs {
+                if p.vaddr <= shdr.addr && p.vaddr + p.memsz == end {
+                    p.memsz = p.memsz + size - shdr.size;
+                    if shdr.sh_type != Shdr_type::NOBITS {
+                        p.filesz = p.filesz + size - shdr.size;
+                    }
+                }
+            }
+        }
+
+        // Make room by moving what follows by whole pages, which keeps it
+        // aligned, the layout takes out the gap again. Space given up by
+        // shrinking is dropped by the layout as well.
+        if shdr.sh_type != Shdr_type::NOBITS && size > shdr.size {
+            let end = shdr.offset + shdr.size;
+            let pad = (size - shdr.size + page - 1) / page * page;
+            self.raw.splice(end as usize..end as usize, vec![0; pad as usize]);
+
+            for p in &mut self.phdrs {
+                if p.offset >= end {
+                    p.offset += pad;
+                }
+            }
+            for (i, s) in self.shdrs.iter_mut().enumerate() {
+                if i != index && s.offset >= end {
+                    s.offset += pad;
+                }
+            }
+            if self.header.phdr_offset >= end {
+                self.header.phdr_offset += pad;
+            }
+            if self.header.shdr_offset >= end {
+                self.header.shdr_offset += pad;
+            }
         }
+
+        self.shdrs[index].size = size;
+        return self.layout();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ParsingError;
+    use crate::shdr::{Shdr_type, Shdr_flags};
+
+    #[test]
+    fn sections_are_views() {
+        let elf = crate::from_file("../test/testBin").unwrap();
+        let data = elf.section(".data").unwrap();
+        data.write(8, &[0xaa; 8]).unwrap();
+
+        let offset = data.hdr.offset as usize + 8;
+        assert_eq!(elf.raw.get(offset..offset + 8).unwrap().to_vec(), vec![0xaa; 8]);
+
+        // the same bytes through the segment that maps the section
+        let segments = elf.segments().unwrap();
+        let section = segments.iter().filter_map(|s| (0..s.shdrs.len()).filter_map(|i| s.section(i)).find(|s| s.hdr.name == ".data")).next().unwrap();
+        assert_eq!(&section.content()[8..], &[0xaa; 8]);
+        section.write(0, &[0xbb; 8]).unwrap();
+        assert_eq!(&elf.section(".data").unwrap().content()[..8], &[0xbb; 8]);
+
+        // a section keeps its size
+        match data.write(12, &[0; 8]) {
+            Err(ParsingError::ParsingError) => {},
+            _ => panic!("a write past the end of .data was taken")
+        }
+    }
+
+    #[test]
+    fn add_section() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let count = elf.shdrs.len();
+        let note = elf.add_section(".note.build", Shdr_type::PROGBITS, 0, b"built by the tests").unwrap();
+        let data = elf.add_section(".extra", Shdr_type::PROGBITS, Shdr_flags::ALLOC as u64 | Shdr_flags::WRITE as u64, b"mapped content").unwrap();
+        assert_eq!((note, data), (count, count + 1));
+
+        let bin = elf.to_le();
+        crate::verify::verify(&bin).unwrap();
+        let elf = crate::Elf::parse(bin).unwrap();
+        assert_eq!(elf.section(".note.build").unwrap().content(), b"built by the tests".to_vec());
+
+        let shdr = elf.section_header(".extra").unwrap();
+        assert_ne!(shdr.addr, 0);
+        assert_eq!(elf.read_vaddr(shdr.addr, 14).unwrap().to_vec(), b"mapped content".to_vec());
+        // the names of the sections that were there survive the grown name table
+        assert_eq!(elf.shdrs[count - 1].name, ".shstrtab");
+        assert_eq!(elf.shdrs[elf.header.shstrndx as usize].name, ".shstrtab");
+    }
+
+    // the symbols of a file with the name of the section they are defined in
+    fn symbol_sections(elf: &crate::Elf) -> Vec<(String, String)> {
+        return elf.symbols().unwrap().iter().map(|s| (s.name.clone(), elf.shdrs.get(s.shndx as usize).map(|s| s.name.clone()).unwrap_or(String::new()))).collect();
+    }
+
+    #[test]
+    fn remove_section() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let mut before = symbol_sections(&elf);
+        let count = elf.shdrs.len();
+        elf.remove_section(".eh_frame_hdr").unwrap();
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        assert_eq!(elf.shdrs.len(), count - 1);
+        assert!(elf.section_header(".eh_frame_hdr").is_none());
+
+        // the symbols of the section are gone, the others keep their section
+        before.retain(|(name, section)| section != ".eh_frame_hdr" && name != ".eh_frame_hdr");
+        assert_eq!(symbol_sections(&elf), before);
+        let symtab = elf.section_header(".symtab").unwrap();
+        let syms = elf.symbols().unwrap();
+        assert!(syms[..symtab.info as usize].iter().all(|s| s.bind() == crate::symbol::Sym_bind::LOCAL));
+        assert!(syms[symtab.info as usize..].iter().all(|s| s.bind() != crate::symbol::Sym_bind::LOCAL));
+
+        // sh_link and sh_info follow the sections that moved down
+        assert_eq!(elf.shdrs[symtab.link as usize].name, ".strtab");
+        let plt = elf.section_header(".rela.plt").unwrap();
+        assert_eq!(elf.shdrs[plt.info as usize].name, ".got");
+        assert_eq!(elf.shdrs[plt.link as usize].name, ".dynsym");
+    }
+
+    #[test]
+    fn remove_section_refused() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        for name in [".dynstr", ".dynsym", ".got", ".shstrtab", ".missing"] {
+            match elf.remove_section(name) {
+                Err(ParsingError::Refused(_)) => {},
+                _ => panic!("{} was removed", name)
+            }
+        }
+    }
+
+    #[test]
+    fn resize_section() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let comment = elf.section(".comment").unwrap().content();
+        let bss = elf.section_header(".bss").unwrap().clone();
+
+        // mapped content after the section would move
+        for name in [".text", ".data", ".missing"] {
+            match elf.resize_section(name, 0x1000) {
+                Err(ParsingError::Refused(_)) => {},
+                _ => panic!("{} was resized", name)
+            }
+        }
+
+        elf.resize_section(".comment", comment.len() as u64 + 0x10).unwrap();
+        elf.resize_section(".bss", 0x100).unwrap();
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        let mut grown = comment.clone();
+        grown.extend(vec![0; 0x10]);
+        assert_eq!(elf.section(".comment").unwrap().content(), grown);
+
+        let load = elf.phdrs.iter().find(|p| p.vaddr <= bss.addr && bss.addr < p.vaddr + p.memsz && if let crate::phdr::Phdr_type::LOAD = p.p_type { true } else { false }).unwrap();
+        assert_eq!(load.vaddr + load.memsz, bss.addr + 0x100);
+        assert_eq!(elf.section_header(".bss").unwrap().size, 0x100);
+    }
+}
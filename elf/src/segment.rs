@@ -1,188 +1,489 @@
 use crate::phdr;
 use crate::shdr;
-use byteorder::*; 
+use byteorder::*;
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_class;
 use crate::phdr::ProgramHeader;
-use crate::shdr::SectionHeader; 
+use crate::phdr::Phdr_type;
+use crate::shdr::SectionHeader;
 use crate::Section;
-
+use crate::strtab::StringTable;
+use crate::buffer::Buffer;
+use crate::buffer::View;
+
+// The file seen through its program headers. The load segments are at the
+// top with the file content they map, segments inside of them (PT_INTERP,
+// PT_DYNAMIC, PT_GNU_RELRO, PT_TLS...) are their children. Segments that
+// aren't in a load segment, like PT_GNU_STACK or the notes of a core dump,
+// are at the top as well.
+//
+// The offset of a top level segment is a file offset, the offsets of its
+// children and sections are relative to their parent, so moving a segment
+// takes everything in it along.
+//
+// The content of a segment is a view on the file, shared with the Elf and the
+// children and sections of the segment, a patch through any of them is seen by
+// all of them. Growing or shrinking a segment gives it content of its own,
+// set_segments puts that back in the file.
 pub struct Segment {
-    // Either should be able not to be set
     pub phdr: ProgramHeader,
-    pub raw_content: Vec<u8>,
+    // the position of the header in the program header table
+    pub index: usize,
+    pub raw_content: View,
+    // the sections in the segment, and their index in the section header table
     pub shdrs: Vec<SectionHeader>,
-    // store nested segments -- ideally we would have all load segments at the
-    // top level, and their children nested
+    pub shndx: Vec<usize>,
+    pub children: Vec<Segment>,
+    pub class: Elf_class
 }
 
 impl Segment {
-    pub fn from(bin: Vec<u8>,phdr: ProgramHeader, shdrs: Vec<SectionHeader>) -> Segment {
+    pub fn from(bin: View, phdr: ProgramHeader, index: usize, class: Elf_class) -> Segment {
 
         return Segment{
             phdr,
-            shdrs,
+            index,
+            shdrs: vec![],
+            shndx: vec![],
             raw_content: bin,
+            children: vec![],
+            class
         }
-        
+
     }
 
+    // move the segment in the file, children and sections move along
     pub fn offset(&mut self, offset: usize) {
-        self.phdr.offset = offset as u64; 
+        self.phdr.offset = offset as u64;
     }
-}
 
-// bin: The loaded binary file
-pub fn parse_segments(bin: Vec<u8>) -> crate::Result< Vec<Segment> > {
-    let shstrndx = LittleEndian::read_u16(&bin[0x3E..0x40]); 
-    let program_hdrs = phdr::parse_program_header(&bin)?;
-    let section_hdrs = shdr::parse_section_header(&bin, shstrndx)?; 
-    let mut segments = vec![]; 
-    
+    // Move the segment in memory, the addresses of its children and
+    // sections move along
+    pub fn relocate(&mut self, vaddr: u64) {
+        let delta = vaddr.wrapping_sub(self.phdr.vaddr);
+        self.shift_vaddr(delta);
+    }
 
-    // TODO: fix offset calculation. We are currently adding segments after eachother, but some might be nested within others. 
-    // ALso there is really no need for this calculation. As we simply need to determine where the segments begin, and then 
-    // subtract that from their current offset, then we get an offset relative to the beginning of the segments blob.
+    fn shift_vaddr(&mut self, delta: u64) {
+        self.phdr.vaddr = self.phdr.vaddr.wrapping_add(delta);
+        self.phdr.paddr = self.phdr.paddr.wrapping_add(delta);
+        for shdr in &mut self.shdrs {
+            shdr.addr = shdr.addr.wrapping_add(delta);
+        }
+        for child in &mut self.children {
+            child.shift_vaddr(delta);
+        }
+    }
 
-    let mut offset:usize = 0; 
-    // use the program headers to parse the file 
-    
-    for mut phdr in program_hdrs {
-        let mut shdrs:Vec<SectionHeader> = vec![]; 
-        let mut raw_content = bin[phdr.offset as usize..(&phdr.offset+&phdr.filesz) as usize].to_vec(); 
+    // does this segment hold another one, by file range or for segments
+    // without file content by address
+    fn contains(&self, phdr: &ProgramHeader) -> bool {
+        let p = &self.phdr;
+        if phdr.filesz > 0 {
+            return phdr.offset >= p.offset && phdr.offset + phdr.filesz <= p.offset + p.filesz;
+        }
+        return phdr.memsz > 0 && phdr.vaddr >= p.vaddr && phdr.vaddr + phdr.memsz <= p.vaddr + p.memsz;
+    }
 
+    fn contains_section(&self, shdr: &SectionHeader) -> bool {
+        let p = &self.phdr;
+        if shdr.sh_type == shdr::Shdr_type::NOBITS {
+            return shdr.flags & shdr::Shdr_flags::ALLOC as u64 != 0 && shdr.addr >= p.vaddr && shdr.addr < p.vaddr + p.memsz;
+        }
+        return shdr.offset >= p.offset && shdr.offset + shdr.size <= p.offset + p.filesz && (shdr.size > 0 || shdr.offset < p.offset + p.filesz);
+    }
 
-        // We need to include the end, but exclude the beginning?
-        for shdr in &section_hdrs {
+    // Replace `remove` bytes at `at` (relative to the segment) with `insert`.
+    // The segment and the children and sections around `at` grow or shrink,
+    // the ones after it move in the file and in memory.
+    pub fn splice(&mut self, at: u64, remove: u64, insert: &[u8]) -> Result<()> {
+        if at + remove > self.raw_content.len() as u64 {
+            return Err(ParsingError::ParsingError);
+        }
+        let mut bin = self.raw_content.to_vec();
+        bin.splice(at as usize..(at + remove) as usize, insert.iter().cloned());
+        self.raw_content = View::detached(bin);
+        self.adjust(at, remove, insert.len() as u64)?;
+        self.attach_children();
+        return Ok(());
+    }
 
-            if let shdr::Shdr_type::NOBITS = shdr.sh_type {
-                if shdr.offset > phdr.offset && shdr.offset <= phdr.offset+phdr.filesz {
-                    // the offset needs to be relative to the segment start
-                    let mut t_shdr = shdr.clone();
-                    t_shdr.offset = t_shdr.offset - phdr.offset; 
+    // point the content of the children at the content of the segment again
+    fn attach_children(&mut self) {
+        for child in &mut self.children {
+            child.raw_content = self.raw_content.sub(child.phdr.offset, child.phdr.filesz);
+            child.attach_children();
+        }
+    }
 
-                    shdrs.push(t_shdr); 
-                }  
-            } else {
-                if shdr.offset >= phdr.offset && shdr.offset < phdr.offset+phdr.filesz {
-                    // the offset needs to be relative to the segment start
-                    let mut t_shdr = shdr.clone();
-                    t_shdr.offset = t_shdr.offset - phdr.offset; 
+    // Content holding `at` grows or shrinks, content from the end of the
+    // replaced range on moves. A zero sized child or section at `at` moves.
+    fn adjust(&mut self, at: u64, remove: u64, insert: u64) -> Result<()> {
+        let end = at + remove;
+        let delta = insert.wrapping_sub(remove);
+
+        self.phdr.filesz = self.phdr.filesz + insert - remove;
+        self.phdr.memsz = (self.phdr.memsz + insert).saturating_sub(remove);
+
+        for child in &mut self.children {
+            let (start, size) = (child.phdr.offset, child.phdr.filesz);
+            if size > 0 && start <= at && at < start + size && end <= start + size {
+                child.adjust(at - start, remove, insert)?;
+            } else if start >= end {
+                child.phdr.offset = start + insert - remove;
+                child.shift_vaddr(delta);
+            } else if at < start + size && end > start {
+                // the change straddles the border of the child
+                return Err(ParsingError::ParsingError);
+            }
+        }
 
-                    shdrs.push(t_shdr); 
-                } 
+        for shdr in &mut self.shdrs {
+            let (start, size) = (shdr.offset, if shdr.sh_type == shdr::Shdr_type::NOBITS { 0 } else { shdr.size });
+            if size > 0 && start <= at && at < start + size && end <= start + size {
+                shdr.size = shdr.size + insert - remove;
+            } else if start >= end {
+                shdr.offset = start + insert - remove;
+                shdr.addr = shdr.addr.wrapping_add(delta);
+            } else if at < start + size && end > start {
+                return Err(ParsingError::ParsingError);
             }
-                    
-            // The section is a part of a section if it's offset is between the segments offset and filez 
-            
         }
 
+        return Ok(());
+    }
+
+    // the program headers of the segment and its children, with file offsets
+    pub fn program_headers(&self, base: u64) -> Vec<(usize, ProgramHeader)> {
+        let mut phdr = self.phdr.clone();
+        phdr.offset += base;
+        let mut phdrs = vec![(self.index, phdr.clone())];
+
+        for child in &self.children {
+            phdrs.extend(child.program_headers(phdr.offset));
+        }
+        return phdrs;
+    }
+
+    // the sections of the segment, with file offsets
+    pub fn section_headers(&self) -> Vec<(usize, SectionHeader)> {
+        let mut shdrs = vec![];
+        for (i, shdr) in self.shdrs.iter().enumerate() {
+            let mut shdr = shdr.clone();
+            shdr.offset += self.phdr.offset;
+            shdrs.push((self.shndx[i], shdr));
+        }
+        return shdrs;
+    }
 
-        // TODO: pad the segment according to allignment 
-        // TODO: make the phdr offset relative 
-        phdr.offset = offset as u64; 
+    // the file content of a child segment
+    pub fn child_content(&self, child: &Segment) -> View {
+        return self.raw_content.sub(child.phdr.offset, child.phdr.filesz);
+    }
 
-        pad(&mut raw_content, &phdr); 
+    // A section of the segment, on the content of the segment. The header
+    // has the file offset.
+    pub fn section(&self, i: usize) -> Option<Section> {
+        let mut hdr = self.shdrs.get(i)?.clone();
+        let size = if hdr.sh_type == shdr::Shdr_type::NOBITS { 0 } else { hdr.size };
+        let data = self.raw_content.sub(hdr.offset, size);
+        hdr.offset += self.phdr.offset;
+        return Some(Section::view(hdr, data, self.class));
+    }
+}
 
-        offset += raw_content.len(); 
+// Build the segment tree from the headers of a file
+pub fn build_segments(bin: &Buffer, phdrs: &Vec<ProgramHeader>, shdrs: &Vec<SectionHeader>) -> Result< Vec<Segment> > {
+    let mut segments: Vec<Segment> = vec![];
+    let class = crate::parse_class(&bin.bytes());
+    let content = |phdr: &ProgramHeader| match phdr.offset + phdr.filesz <= bin.len() as u64 {
+        true => Ok(bin.view(phdr.offset, phdr.filesz)),
+        false => Err(ParsingError::ParsingError)
+    };
+
+    for (i, phdr) in phdrs.iter().enumerate() {
+        if let Phdr_type::LOAD = phdr.p_type {
+            segments.push(Segment::from(content(phdr)?, phdr.clone(), i, class));
+        }
+    }
 
+    // the largest first, so the containing segments are there before their children
+    let mut others: Vec<usize> = (0..phdrs.len()).filter(|i| if let Phdr_type::LOAD = phdrs[*i].p_type { false } else { true }).collect();
+    others.sort_by_key(|i| std::cmp::Reverse((phdrs[*i].filesz, phdrs[*i].memsz)));
 
-        segments.push(
-            Segment::from(
-                raw_content, //  i the sections to reference the raw content, so that if either are changed it's all changed 
-                phdr,
-                shdrs)
-        ); 
+    for i in others {
+        let phdr = &phdrs[i];
+        match segments.iter().position(|s| s.contains(phdr)) {
+            Some(parent) => add_child(&mut segments[parent], phdr.clone(), i),
+            None => segments.push(Segment::from(content(phdr)?, phdr.clone(), i, class))
+        }
+    }
 
+    // every section goes in the top level segment holding it
+    for (i, shdr) in shdrs.iter().enumerate().skip(1) {
+        if let Some(segment) = segments.iter_mut().find(|s| s.contains_section(shdr)) {
+            let mut shdr = shdr.clone();
+            shdr.offset -= segment.phdr.offset.min(shdr.offset);
+            segment.shdrs.push(shdr);
+            segment.shndx.push(i);
+        }
     }
 
-    return Ok(segments); 
+    segments.sort_by_key(|s| s.phdr.offset);
+    return Ok(segments);
+}
+
+// Put a segment under the deepest child of `parent` holding it. The offset of
+// `phdr` is relative to the same base as the offset of `parent`.
+fn add_child(parent: &mut Segment, mut phdr: ProgramHeader, index: usize) {
+    phdr.offset = phdr.offset.saturating_sub(parent.phdr.offset);
+    match parent.children.iter().position(|c| c.contains(&phdr)) {
+        Some(i) => add_child(&mut parent.children[i], phdr, index),
+        None => {
+            let content = parent.raw_content.sub(phdr.offset, phdr.filesz);
+            parent.children.push(Segment::from(content, phdr, index, parent.class));
+        }
+    }
+}
+
+// bin: The loaded binary file
+pub fn parse_segments(bin: Vec<u8>) -> crate::Result< Vec<Segment> > {
+    let shstrndx = LittleEndian::read_u16(&bin[0x3E..0x40]);
+    let program_hdrs = phdr::parse_program_header(&bin)?;
+    let section_hdrs = shdr::parse_section_header(&bin, shstrndx)?;
+
+    return build_segments(&Buffer::new(bin), &program_hdrs, &section_hdrs);
 }
 
-fn pad(buf: &mut Vec<u8>, phdr: &phdr::ProgramHeader) {
-    let missing_bytes = phdr.p_align - (buf.len() as u64 % phdr.p_align); 
-    buf.extend_from_slice(&vec![0; missing_bytes as usize]);
+fn overlaps(a: u64, a_size: u64, b: u64, b_size: u64) -> bool {
+    return a_size > 0 && b_size > 0 && a < b + b_size && b < a + a_size;
 }
 
+// the program header table of the segments, in their original order
+pub fn program_headers(segments: &Vec<Segment>) -> Vec<ProgramHeader> {
+    let mut phdrs: Vec<(usize, ProgramHeader)> = segments.iter().flat_map(|s| s.program_headers(0)).collect();
+    phdrs.sort_by_key(|(i, _)| *i);
+    return phdrs.into_iter().map(|(_, phdr)| phdr).collect();
+}
 
 pub fn get_segments_size(segments: &Vec<Segment>) -> u64 {
-    let mut t = 0; 
-    
+    let mut t = 0;
+
     for seg in segments {
-        t += seg.phdr.filesz + (seg.phdr.p_align - (seg.phdr.filesz % seg.phdr.p_align))
+        t = t.max(seg.phdr.offset + seg.raw_content.len() as u64);
     }
     return t;
 }
 
 pub fn get_segments_blob(segments: &Vec<Segment>) -> Vec<u8> {
-    let mut blob = vec![]; 
-    
+    let mut blob = vec![0; get_segments_size(segments) as usize];
+
     for segment in segments {
-        // we want to make sure everything is properly aligned at this point.
-        blob.extend_from_slice(&segment.raw_content); 
+        let start = segment.phdr.offset as usize;
+        blob.splice(start..start + segment.raw_content.len(), segment.raw_content.to_vec());
     }
 
-    return blob; 
+    return blob;
 }
 
 pub fn get_phdrs_blob(segments: &Vec<Segment>, offset: usize) -> Vec<u8> {
-    let mut blob = vec![]; 
-    
-    for segment in segments {
-        blob.extend_from_slice(&segment.phdr.to_le_offset(offset)); 
+    let mut blob = vec![];
+    let class = segments.first().map(|s| s.class).unwrap_or(Elf_class::ELF64);
+
+    for phdr in program_headers(segments) {
+        blob.extend_from_slice(&phdr.to_le_offset(offset, class));
     }
 
-    return blob; 
+    return blob;
 }
 
-pub fn get_shdrs_blob(segments: &Vec<Segment>) -> Vec<u8> {
-    let mut blob = vec![]; 
-    
-    // TODO: We need to construct the strings table.
+// the names of the sections in the segments, as a section name table
+pub fn get_shstrtab(segments: &Vec<Segment>) -> StringTable {
+    return StringTable::from_strings(segments.iter().flat_map(|seg| seg.shdrs.iter().map(|shdr| shdr.name.as_str())));
+}
 
-    // We need to update the section offsets to global offsets and not local ones
+pub fn get_shdrs_blob(segments: &Vec<Segment>, shstrtab: &StringTable) -> Vec<u8> {
+    let mut blob = vec![];
 
     for segment in segments {
-        for shdr in &segment.shdrs {
-            blob.extend_from_slice(&shdr.to_le_offset(segment.phdr.offset as usize)); 
+        for (_, mut shdr) in segment.section_headers() {
+            shdr.shstrndx_offset = shstrtab.offset(&shdr.name).unwrap_or(0);
+            blob.extend_from_slice(&shdr.to_le(segment.class));
         }
     }
 
-    return blob; 
+    return blob;
 }
 
 pub fn phdrs_size(segments: &Vec<Segment>) -> usize {
     // 32-bit = 0x20
     // 64-bit = 0x38
-    return 0x38*segments.len(); 
+    return 0x38*program_headers(segments).len();
 }
 
 pub fn shdrs_size(segments: &Vec<Segment>) -> usize {
     // 32-bit = 0x28
     // 64-bit = 0x40
-    let mut size = 0;  
-    
-    // TODO: We need to construct the strings table.
+    return 0x40*shdrs_len(segments);
+}
+
+pub fn shdrs_len(segments: &Vec<Segment>) -> usize {
+    // the names of these sections go in the table from get_shstrtab
+    let mut size = 0;
 
     for segment in segments {
-        for shdr in &segment.shdrs {
-            size += 0x40; 
-        }
+        size += segment.shdrs.len();
     }
 
-    return size; 
+    return size;
 }
 
-pub fn shdrs_len(segments: &Vec<Segment>) -> usize {
-        // 32-bit = 0x28
-    // 64-bit = 0x40
-    let mut size = 0;  
-    
-    // TODO: We need to construct the strings table.
+impl Elf {
+    pub fn segments(&self) -> Result<Vec<Segment>> {
+        return build_segments(&self.raw, &self.phdrs, &self.shdrs);
+    }
 
-    for segment in segments {
-        for shdr in &segment.shdrs {
-            size += 1; 
+    // Take over the edited segment tree: the program headers, the sections in
+    // the segments and their content. Sections outside of the segments that
+    // now overlap them are moved out of the way, then the file is laid out
+    // again. A segment that now runs into another one is refused, the ones
+    // after it don't move along.
+    pub fn set_segments(&mut self, segments: &Vec<Segment>) -> Result<()> {
+        let phdrs = program_headers(segments);
+        if phdrs.len() > self.header.phdr_num as usize {
+            return Err(ParsingError::ParsingError);
+        }
+
+        // A segment that grew or moved can't run into another one, the file
+        // content or the memory of that one would be overwritten. Segments
+        // that overlapped in the file already are left as they were.
+        for (i, a) in segments.iter().enumerate() {
+            for b in segments.iter().skip(i + 1) {
+                let (old_a, old_b) = (self.phdrs.get(a.index), self.phdrs.get(b.index));
+                let overlapped = |file: bool| match (old_a, old_b) {
+                    (Some(x), Some(y)) if file => overlaps(x.offset, x.filesz, y.offset, y.filesz),
+                    (Some(x), Some(y)) => overlaps(x.vaddr, x.memsz, y.vaddr, y.memsz),
+                    _ => false
+                };
+
+                if overlaps(a.phdr.offset, a.raw_content.len() as u64, b.phdr.offset, b.raw_content.len() as u64) && !overlapped(true) {
+                    return Err(ParsingError::Refused(format!("segment {} at offset {:#x} runs into segment {} at offset {:#x} in the file", a.index, a.phdr.offset, b.index, b.phdr.offset)));
+                }
+                let loads = if let (Phdr_type::LOAD, Phdr_type::LOAD) = (a.phdr.p_type, b.phdr.p_type) { true } else { false };
+                if loads && overlaps(a.phdr.vaddr, a.phdr.memsz, b.phdr.vaddr, b.phdr.memsz) && !overlapped(false) {
+                    return Err(ParsingError::Refused(format!("segment {} at {:#x} runs into segment {} at {:#x} in memory", a.index, a.phdr.vaddr, b.index, b.phdr.vaddr)));
+                }
+            }
+        }
+
+        let mut in_segment = vec![false; self.shdrs.len()];
+        for segment in segments {
+            for (i, shdr) in segment.section_headers() {
+                self.shdrs[i] = shdr;
+                in_segment[i] = true;
+            }
+        }
+
+        // the content of the other sections, before it gets overwritten
+        let mut loose = vec![];
+        for (i, shdr) in self.shdrs.iter().enumerate().skip(1) {
+            if !in_segment[i] && shdr.sh_type != shdr::Shdr_type::NOBITS && shdr.size > 0 {
+                loose.push((i, self.raw.get(shdr.offset as usize..(shdr.offset + shdr.size) as usize).ok_or(ParsingError::ParsingError)?.to_vec()));
+            }
+        }
+
+        let mut raw = self.raw.to_vec();
+        raw.resize(raw.len().max(get_segments_size(segments) as usize), 0);
+        for segment in segments {
+            let start = segment.phdr.offset as usize;
+            raw.splice(start..start + segment.raw_content.len(), segment.raw_content.to_vec());
+        }
+
+        for (i, content) in loose {
+            let shdr = &mut self.shdrs[i];
+            let overlaps = segments.iter().any(|s| shdr.offset < s.phdr.offset + s.raw_content.len() as u64 && s.phdr.offset < shdr.offset + shdr.size);
+            if overlaps {
+                let align = shdr.addralign.max(1) as usize;
+                let offset = (raw.len() + align - 1) / align * align;
+                raw.resize(offset, 0);
+                shdr.offset = offset as u64;
+                raw.extend(content);
+            } else {
+                let start = shdr.offset as usize;
+                raw.splice(start..start + content.len(), content);
+            }
         }
+
+        self.raw.replace(raw);
+        self.phdrs = phdrs;
+        return self.layout();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::phdr::Phdr_type;
+    use crate::ParsingError;
+
+    fn is_load(segment: &crate::segment::Segment) -> bool {
+        return if let Phdr_type::LOAD = segment.phdr.p_type { true } else { false };
     }
 
-    return size; 
-}
\ No newline at end of file
+    #[test]
+    fn segment_tree() {
+        let elf = crate::from_file("../test/testBin").unwrap();
+        let segments = elf.segments().unwrap();
+
+        // every program header is in the tree once, with its file offset
+        let mut phdrs = vec![];
+        for segment in &segments {
+            phdrs.extend(segment.program_headers(0));
+        }
+        phdrs.sort_by_key(|(i, _)| *i);
+        assert_eq!(phdrs.iter().map(|(i, _)| *i).collect::<Vec<usize>>(), (0..elf.phdrs.len()).collect::<Vec<usize>>());
+        for (i, phdr) in &phdrs {
+            assert_eq!(phdr.to_le(elf.header.e_class), elf.phdrs[*i].to_le(elf.header.e_class));
+        }
+
+        // PT_DYNAMIC is in the writable load segment, under PT_GNU_RELRO, .dynamic a section of it
+        let rw = segments.iter().find(|s| is_load(s) && s.program_headers(0).iter().any(|(_, p)| if let Phdr_type::DYNAMIC = p.p_type { true } else { false }));
+        let rw = rw.unwrap();
+        assert!(rw.children.iter().any(|c| if let Phdr_type::GNU_RELRO = c.phdr.p_type { true } else { false }));
+        assert!(rw.section_headers().iter().any(|(_, s)| s.name == ".dynamic"));
+        assert!(!segments.iter().any(|s| if let Phdr_type::DYNAMIC = s.phdr.p_type { true } else { false }));
+    }
+
+    #[test]
+    fn splice_moves_following_sections() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let data = elf.section_header(".data").unwrap().clone();
+        let bss = elf.section_header(".bss").unwrap().clone();
+
+        let mut segments = elf.segments().unwrap();
+        let load = segments.iter_mut().find(|s| is_load(s) && s.section_headers().iter().any(|(_, s)| s.name == ".data")).unwrap();
+        load.splice(data.offset - load.phdr.offset, 0, &[0xaa; 8]).unwrap();
+        elf.set_segments(&segments).unwrap();
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        let content = elf.section(".data").unwrap().content();
+        assert_eq!(content.len() as u64, data.size + 8);
+        assert_eq!(&content[..8], &[0xaa; 8]);
+        assert_eq!(elf.section_header(".bss").unwrap().addr, bss.addr + 8);
+    }
+
+    #[test]
+    fn set_segments_refuses_overlap() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let mut segments = elf.segments().unwrap();
+        let mut loads: Vec<&mut crate::segment::Segment> = segments.iter_mut().filter(|s| is_load(s)).collect();
+        let vaddr = loads[1].phdr.vaddr;
+        loads[2].relocate(vaddr);
+
+        match elf.set_segments(&segments) {
+            Err(ParsingError::Refused(_)) => {},
+            _ => panic!("overlapping load segments were taken")
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use crate::Result;
+use crate::ParsingError;
+use crate::Elf_class;
  
 use byteorder::*; 
 
 
 #[repr(u32)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum Shdr_type {
     NULL = 0x0,
     PROGBITS = 0x1,
@@ -23,15 +25,20 @@ pub enum Shdr_type {
     PRE_INIT_ARRAY = 0x10,
     GROUP = 0x11,
     SYMTAB_SHNDX = 0x12,
-    NUM = 0x13,
+    RELR = 0x13,
     LOOS = 0x60000000,
-    GNU_VERDEF,
-    GNU_VERNEED,
-    GNU_VERSYM,
+    ANDROID_REL = 0x60000001,
+    ANDROID_RELA = 0x60000002,
+    GNU_HASH = 0x6ffffff6,
+    ANDROID_RELR = 0x6fffff00,
+    GNU_VERDEF = 0x6ffffffd,
+    GNU_VERNEED = 0x6ffffffe,
+    GNU_VERSYM = 0x6fffffff,
 }
+// section flags are a bitmask, these are the bits
 #[repr(u64)]
 #[derive(Copy, Clone)]
-enum Shdr_flags {
+pub enum Shdr_flags {
     NONE = 0x0,
     WRITE = 0x1,
     ALLOC = 0x2,
@@ -43,6 +50,7 @@ enum Shdr_flags {
     OS_NONCONFORMING = 0x100,
     GROUP = 0x200,
     TLS = 0x400,
+    COMPRESSED = 0x800,
     MASKOS = 0x0ff00000,
     MASKPROC = 0xf0000000,
     ORDERED = 0x4000000,
@@ -51,105 +59,146 @@ enum Shdr_flags {
 #[derive(Clone)]
 pub struct SectionHeader {
     pub name: String,
-    shstrndx_offset: u32,
+    pub shstrndx_offset: u32,
     pub sh_type: Shdr_type,
-    flags: Shdr_flags,
-    addr: u64,
+    // the type as read from the file, so types we don't know survive a write
+    sh_type_raw: u32,
+    pub flags: u64,
+    pub addr: u64,
     pub offset: u64,
     pub size: u64,
-    link: u32,
-    info: u32,
-    addralign: u64,
-    entsize: u64,
+    pub link: u32,
+    pub info: u32,
+    pub addralign: u64,
+    pub entsize: u64,
 }
 
 fn parse_shdr_type(phdr: &[u8]) -> Shdr_type {
-    return match LittleEndian::read_u32(&phdr[0x04..0x08]) {
-        0x0 => return Shdr_type::NULL,
-        0x1 => return Shdr_type::PROGBITS,
-        0x2 => return Shdr_type::SYMTAB,
-        0x3 => return Shdr_type::STRTAB,
-        0x4 => return Shdr_type::RELA,
-        0x5 => return Shdr_type::HASH,
-        0x6 => return Shdr_type::DYNAMIC,
-        0x7 => return Shdr_type::NOTE,
-        0x8 => return Shdr_type::NOBITS,
-        0x9 => return Shdr_type::REL,
-        0xA => return Shdr_type::SHLIB,
-        0xB => return Shdr_type::DYNSYM,
-        0xE => return Shdr_type::INIT_ARRAY,
-        0xF => return Shdr_type::FINI_ARRAY,
-        0x10 => return Shdr_type::PRE_INIT_ARRAY,
-        0x11 => return Shdr_type::GROUP,
-        0x12 => return Shdr_type::SYMTAB_SHNDX,
-        0x13 => return Shdr_type::NUM,
-        0x60000000 => return Shdr_type::LOOS,
-        // 0xXXXXX => return Shdr_type::GNU_VERDEF,
-        // 0xXXXXX => return Shdr_type::GNU_VERNEED,
-        // 0xXXXXX => return Shdr_type::GNU_VERSYM,
-        _ => return Shdr_type::NULL
-    }
+    return shdr_type(LittleEndian::read_u32(&phdr[0x04..0x08]));
 }
 
-
-fn parse_shdr_flags(phdr: &[u8]) -> Shdr_flags {
-    return match LittleEndian::read_u32(&phdr[0x08..0x10]) {
-        0x0 => return Shdr_flags::NONE,
-        0x1 => return Shdr_flags::WRITE,
-        0x2 => return Shdr_flags::ALLOC,
-        0x4 => return Shdr_flags::EXECINSTR,
-        0x10 => return Shdr_flags::MERGE,
-        0x20 => return Shdr_flags::STRINGS,
-        0x40 => return Shdr_flags::INFO_LINK,
-        0x80 => return Shdr_flags::LINK_ORDER,
-        0x100 => return Shdr_flags::OS_NONCONFORMING,
-        0x200 => return Shdr_flags::GROUP,
-        0x400 => return Shdr_flags::TLS,
-        0x0ff00000 => return Shdr_flags::MASKOS,
-        0xf0000000 => return Shdr_flags::MASKPROC,
-        0x4000000 => return Shdr_flags::ORDERED,
-        0x8000000 => return Shdr_flags::EXCLUDE,
-
-        // TODO: Throw error here instead -> and return Result
-        _ => return Shdr_flags::NONE
+fn shdr_type(sh_type: u32) -> Shdr_type {
+    return match sh_type {
+        0x0 => Shdr_type::NULL,
+        0x1 => Shdr_type::PROGBITS,
+        0x2 => Shdr_type::SYMTAB,
+        0x3 => Shdr_type::STRTAB,
+        0x4 => Shdr_type::RELA,
+        0x5 => Shdr_type::HASH,
+        0x6 => Shdr_type::DYNAMIC,
+        0x7 => Shdr_type::NOTE,
+        0x8 => Shdr_type::NOBITS,
+        0x9 => Shdr_type::REL,
+        0xA => Shdr_type::SHLIB,
+        0xB => Shdr_type::DYNSYM,
+        0xE => Shdr_type::INIT_ARRAY,
+        0xF => Shdr_type::FINI_ARRAY,
+        0x10 => Shdr_type::PRE_INIT_ARRAY,
+        0x11 => Shdr_type::GROUP,
+        0x12 => Shdr_type::SYMTAB_SHNDX,
+        0x13 => Shdr_type::RELR,
+        0x60000000 => Shdr_type::LOOS,
+        0x60000001 => Shdr_type::ANDROID_REL,
+        0x60000002 => Shdr_type::ANDROID_RELA,
+        0x6ffffff6 => Shdr_type::GNU_HASH,
+        0x6fffff00 => Shdr_type::ANDROID_RELR,
+        0x6ffffffd => Shdr_type::GNU_VERDEF,
+        0x6ffffffe => Shdr_type::GNU_VERNEED,
+        0x6fffffff => Shdr_type::GNU_VERSYM,
+        _ => Shdr_type::NULL
     }
 }
 
+
 impl SectionHeader{
-  
-    // Parse programheaders
-    pub fn parse(shdr: &[u8], name: &str) -> Result< SectionHeader > {
-        Ok(SectionHeader{
+
+    // a header for a new section, placed by the caller
+    pub fn new(name: &str, sh_type: Shdr_type, flags: u64, addr: u64, offset: u64, size: u64, addralign: u64) -> SectionHeader {
+        SectionHeader {
             name: String::from(name),
-            shstrndx_offset: LittleEndian::read_u32(&shdr[0x0..0x4]),
-            sh_type: parse_shdr_type(&shdr),
-            flags: parse_shdr_flags(&shdr),
-            addr: LittleEndian::read_u64(&shdr[0x10..0x18]),
-            offset: LittleEndian::read_u64(&shdr[0x18..0x20]),
-            size: LittleEndian::read_u64(&shdr[0x20..0x28]),
-            link: LittleEndian::read_u32(&shdr[0x28..0x2C]),
-            info: LittleEndian::read_u32(&shdr[0x2C..0x30]),
-            addralign: LittleEndian::read_u64(&shdr[0x30..0x38]),
-            entsize: LittleEndian::read_u64(&shdr[0x38..0x40]),
-        })
+            shstrndx_offset: 0,
+            sh_type,
+            sh_type_raw: sh_type as u32,
+            flags,
+            addr,
+            offset,
+            size,
+            link: 0,
+            info: 0,
+            addralign,
+            entsize: 0
+        }
+    }
+  
+    // Parse programheaders, in 32-bit files every field is 4 bytes
+    pub fn parse(shdr: &[u8], name: &str, class: Elf_class) -> Result< SectionHeader > {
+        return match class {
+            Elf_class::ELF64 => Ok(SectionHeader{
+                name: String::from(name),
+                shstrndx_offset: LittleEndian::read_u32(&shdr[0x0..0x4]),
+                sh_type: parse_shdr_type(&shdr),
+                sh_type_raw: LittleEndian::read_u32(&shdr[0x04..0x08]),
+                flags: LittleEndian::read_u64(&shdr[0x08..0x10]),
+                addr: LittleEndian::read_u64(&shdr[0x10..0x18]),
+                offset: LittleEndian::read_u64(&shdr[0x18..0x20]),
+                size: LittleEndian::read_u64(&shdr[0x20..0x28]),
+                link: LittleEndian::read_u32(&shdr[0x28..0x2C]),
+                info: LittleEndian::read_u32(&shdr[0x2C..0x30]),
+                addralign: LittleEndian::read_u64(&shdr[0x30..0x38]),
+                entsize: LittleEndian::read_u64(&shdr[0x38..0x40]),
+            }),
+            Elf_class::ELF32 => Ok(SectionHeader{
+                name: String::from(name),
+                shstrndx_offset: LittleEndian::read_u32(&shdr[0x0..0x4]),
+                sh_type: parse_shdr_type(&shdr),
+                sh_type_raw: LittleEndian::read_u32(&shdr[0x04..0x08]),
+                flags: LittleEndian::read_u32(&shdr[0x08..0x0C]) as u64,
+                addr: LittleEndian::read_u32(&shdr[0x0C..0x10]) as u64,
+                offset: LittleEndian::read_u32(&shdr[0x10..0x14]) as u64,
+                size: LittleEndian::read_u32(&shdr[0x14..0x18]) as u64,
+                link: LittleEndian::read_u32(&shdr[0x18..0x1C]),
+                info: LittleEndian::read_u32(&shdr[0x1C..0x20]),
+                addralign: LittleEndian::read_u32(&shdr[0x20..0x24]) as u64,
+                entsize: LittleEndian::read_u32(&shdr[0x24..0x28]) as u64,
+            })
+        };
     }
     
     
     // print the section header as a LittleEndian formatted object
     // should this come with/or without padding??? 
-    pub fn to_le(&self) -> Vec<u8> {
-        self.to_le_offset(0)
+    pub fn to_le(&self, class: Elf_class) -> Vec<u8> {
+        self.to_le_offset(0, class)
+    }
+
+    // the type to write, the raw value unless sh_type was changed since parsing
+    pub fn type_value(&self) -> u32 {
+        if shdr_type(self.sh_type_raw) == self.sh_type {
+            return self.sh_type_raw; 
+        }
+        return self.sh_type as u32; 
     }
 
-    pub fn to_le_offset(&self, offset: usize) -> Vec<u8> {
+    pub fn to_le_offset(&self, offset: usize, class: Elf_class) -> Vec<u8> {
         // bin.append([1,2,3].to_vec())
         let mut bin = vec![]; 
 
         bin.extend_from_slice(&self.shstrndx_offset.to_le_bytes()); 
         
         // do i end up owning this data, thus preventing me from using sh_type elsewhere? 
-        bin.extend_from_slice(&(self.sh_type as u32).to_le_bytes()); 
-        bin.extend_from_slice(&(self.flags as u64).to_le_bytes()); 
+        bin.extend_from_slice(&self.type_value().to_le_bytes()); 
+        if let Elf_class::ELF32 = class {
+            bin.extend_from_slice(&(self.flags as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.addr as u32).to_le_bytes()); 
+            bin.extend_from_slice(&((self.offset + offset as u64) as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.size as u32).to_le_bytes()); 
+            bin.extend_from_slice(&self.link.to_le_bytes()); 
+            bin.extend_from_slice(&self.info.to_le_bytes()); 
+            bin.extend_from_slice(&(self.addralign as u32).to_le_bytes()); 
+            bin.extend_from_slice(&(self.entsize as u32).to_le_bytes()); 
+            return bin; 
+        }
+        bin.extend_from_slice(&self.flags.to_le_bytes()); 
         bin.extend_from_slice(&self.addr.to_le_bytes()); 
         bin.extend_from_slice(&(self.offset + offset as u64).to_le_bytes()); 
         bin.extend_from_slice(&self.size.to_le_bytes()); 
@@ -167,12 +216,18 @@ impl SectionHeader{
 
 
 pub fn parse_section_header(bin: &Vec<u8>, shstrndx: u16) -> Result<Vec<SectionHeader>> {
-    let shdr_offset = LittleEndian::read_u64(&bin[0x28..0x30]); 
-    let shdr_size = LittleEndian::read_u16(&bin[0x3A..0x3C]); 
-    let shdr_num = LittleEndian::read_u16(&bin[0x3C..0x3E]); 
-    let shstr_table_offset: usize = (shdr_offset + (shdr_size * shstrndx) as u64 ) as usize;
-    let str_table_offset = LittleEndian::read_u64(&bin[shstr_table_offset+0x18..shstr_table_offset+0x20]) as usize;
-    let str_table_size = LittleEndian::read_u64(&bin[shstr_table_offset+0x20..shstr_table_offset+0x28]) as usize; 
+    let class = crate::parse_class(bin);
+    let (shdr_offset, shdr_size, shdr_num) = match class {
+        Elf_class::ELF64 => (LittleEndian::read_u64(&bin[0x28..0x30]), LittleEndian::read_u16(&bin[0x3A..0x3C]), LittleEndian::read_u16(&bin[0x3C..0x3E])),
+        Elf_class::ELF32 => (LittleEndian::read_u32(&bin[0x20..0x24]) as u64, LittleEndian::read_u16(&bin[0x2E..0x30]), LittleEndian::read_u16(&bin[0x30..0x32]))
+    };
+    if shdr_num == 0 {
+        return Ok(vec![]); 
+    }
+    let shstr_table_offset: usize = (shdr_offset + shdr_size as u64 * shstrndx as u64) as usize;
+    let shstr_table = SectionHeader::parse(&bin[shstr_table_offset..shstr_table_offset + shdr_size as usize], "", class)?;
+    let str_table_offset = shstr_table.offset as usize;
+    let str_table_size = shstr_table.size as usize; 
 
     let mut shdrs:Vec<SectionHeader> = vec![]; 
 
@@ -182,8 +237,8 @@ pub fn parse_section_header(bin: &Vec<u8>, shstrndx: u16) -> Result<Vec<SectionH
         let end = (shdr_offset+(shdr_size as u64*i as u64)+shdr_size as u64 ) as usize; 
         let name_offset = str_table_offset + LittleEndian::read_u32(&bin[start..start+0x4]) as usize; 
         
-        let name = str_from_u8_nul_utf8(&bin[name_offset..str_table_offset+str_table_size])?;
-        let section = SectionHeader::parse(&bin[start..end], name)?; 
+        let name = name_from_u8_nul(bin.get(name_offset..str_table_offset+str_table_size).ok_or(ParsingError::ParsingError)?);
+        let section = SectionHeader::parse(&bin[start..end], &name, class)?; 
         // println!("{}", String::from_utf8_lossy(&bin[name_offset..name_offset+0x4])); 
         // add the section to the table of sections 
         shdrs.push(section); 
@@ -195,9 +250,45 @@ pub fn parse_section_header(bin: &Vec<u8>, shstrndx: u16) -> Result<Vec<SectionH
 } 
 
 
+pub fn to_le(shdrs: &Vec<SectionHeader>, class: Elf_class) -> Vec<u8> {
+    let mut bin = vec![]; 
+
+    for shdr in shdrs {
+        bin.extend(shdr.to_le(class)); 
+    }
+
+    return bin; 
+}
+
+
 pub fn str_from_u8_nul_utf8(utf8_src: &[u8]) -> Result<&str> {
     let nul_range_end = utf8_src.iter()
         .position(|&c| c == b'\0')
         .unwrap_or(utf8_src.len()); // default to length if no `\0` present
-    return Ok(::std::str::from_utf8(&utf8_src[0..nul_range_end]).unwrap())
-}
\ No newline at end of file
+    return ::std::str::from_utf8(&utf8_src[0..nul_range_end]).map_err(|_| ParsingError::ParsingError)
+}
+
+// A name from a string table. Names don't have to be UTF-8, bytes that
+// aren't are replaced, the name is written back through its offset anyway.
+pub fn name_from_u8_nul(src: &[u8]) -> String {
+    let end = src.iter().position(|&c| c == b'\0').unwrap_or(src.len());
+    return String::from_utf8_lossy(&src[..end]).into_owned();
+}
+#[cfg(test)]
+mod tests {
+    use crate::Elf;
+
+    #[test]
+    fn names_that_are_not_utf8() {
+        let mut bin = std::fs::read("../test/testBin").unwrap();
+        let elf = Elf::parse(bin.clone()).unwrap();
+        let table = elf.shdrs[elf.header.shstrndx as usize].offset;
+        let comment = elf.section_header(".comment").unwrap().shstrndx_offset as u64;
+        bin[(table + comment + 1) as usize] = 0xff;
+
+        let elf = Elf::parse(bin.clone()).unwrap();
+        assert!(elf.section_header(".comment").is_none());
+        assert!(elf.shdrs.iter().any(|s| s.name == ".\u{fffd}omment"));
+        assert!(elf.to_le() == bin);
+    }
+}
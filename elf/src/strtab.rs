@@ -0,0 +1,171 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::shdr;
+
+use std::collections::HashMap;
+
+// A string table (SHT_STRTAB) writer. Strings are stored once, offsets never
+// change once handed out and new strings are only ever appended. Tables built
+// with from_strings also share the tails of strings, so "name" points into
+// "section_name".
+#[derive(Clone)]
+pub struct StringTable {
+    bin: Vec<u8>,
+    offsets: HashMap<String, u32>
+}
+
+impl StringTable {
+    // an empty table, with the empty string at offset 0
+    pub fn new() -> StringTable {
+        let mut offsets = HashMap::new();
+        offsets.insert(String::new(), 0);
+
+        StringTable {
+            bin: vec![0],
+            offsets
+        }
+    }
+
+    // Continue an existing table, its strings keep their offsets
+    pub fn from_bytes(bin: &[u8]) -> StringTable {
+        let mut table = StringTable::new();
+        if bin.is_empty() {
+            return table;
+        }
+
+        table.bin = bin.to_vec();
+        if table.bin.last() != Some(&0) {
+            table.bin.push(0);
+        }
+
+        let mut start = 0;
+        for (i, byte) in table.bin.iter().enumerate() {
+            if *byte == 0 {
+                let s = String::from_utf8_lossy(&table.bin[start..i]).into_owned();
+                table.offsets.entry(s).or_insert(start as u32);
+                start = i + 1;
+            }
+        }
+        return table;
+    }
+
+    // Build a table from a set of strings with the most tail merging, the way
+    // ld does for SHF_MERGE|SHF_STRINGS sections. Sorting on the reversed
+    // strings puts every string right after the longer ones ending in it, so
+    // it only has to be compared with the one before it.
+    pub fn from_strings<'a, I: IntoIterator<Item = &'a str>>(strings: I) -> StringTable {
+        let mut strings: Vec<&str> = strings.into_iter().collect();
+        strings.sort_by(|a, b| b.bytes().rev().cmp(a.bytes().rev()));
+        strings.dedup();
+
+        let mut table = StringTable::new();
+        let mut previous: Option<(&str, u32)> = None;
+        for s in strings {
+            // the empty string stays at offset 0
+            if s.is_empty() {
+                continue;
+            }
+            let offset = match previous {
+                Some((p, offset)) if p.ends_with(s) => offset + (p.len() - s.len()) as u32,
+                _ => table.add(s)
+            };
+            table.offsets.insert(String::from(s), offset);
+            previous = Some((s, offset));
+        }
+        return table;
+    }
+
+    // the offset of a string, appended if it isn't in the table yet
+    pub fn add(&mut self, s: &str) -> u32 {
+        if let Some(offset) = self.offsets.get(s) {
+            return *offset;
+        }
+
+        let offset = self.bin.len() as u32;
+        self.bin.extend_from_slice(s.as_bytes());
+        self.bin.push(0);
+        self.offsets.insert(String::from(s), offset);
+        return offset;
+    }
+
+    pub fn offset(&self, s: &str) -> Option<u32> {
+        return self.offsets.get(s).cloned();
+    }
+
+    // the string at an offset
+    pub fn get(&self, offset: u32) -> Option<&str> {
+        return shdr::str_from_u8_nul_utf8(self.bin.get(offset as usize..)?).ok();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.bin.len();
+    }
+
+    pub fn to_le(&self) -> Vec<u8> {
+        return self.bin.clone();
+    }
+}
+
+impl Elf {
+    // the section name table, to add names to
+    pub fn shstrtab(&self) -> Result<StringTable> {
+        let shdr = self.shdrs.get(self.header.shstrndx as usize).ok_or(ParsingError::ParsingError)?;
+        let bin = self.raw.get(shdr.offset as usize..(shdr.offset + shdr.size) as usize).ok_or(ParsingError::ParsingError)?;
+        return Ok(StringTable::from_bytes(&bin));
+    }
+
+    // Write a section name table back and point the names of the sections
+    // to their offsets in it
+    pub fn set_shstrtab(&mut self, table: &StringTable) -> Result<()> {
+        let mut table = table.clone();
+        for i in 0..self.shdrs.len() {
+            self.shdrs[i].shstrndx_offset = table.add(&self.shdrs[i].name);
+        }
+        return self.set_section_content(self.header.shstrndx as usize, table.to_le());
+    }
+
+    // Build the section name table again from the names of the sections,
+    // dropping the names no longer used and merging the rest
+    pub fn rebuild_shstrtab(&mut self) -> Result<()> {
+        let table = StringTable::from_strings(self.shdrs.iter().map(|s| s.name.as_str()));
+        return self.set_shstrtab(&table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strtab::StringTable;
+
+    #[test]
+    fn tail_merging() {
+        let table = StringTable::from_strings([".rela.text", ".text", "text", "", ".data", ".rela.data", ".text"]);
+        assert_eq!(table.to_le(), b"\0.rela.text\0.rela.data\0".to_vec());
+        assert_eq!(table.offset(""), Some(0));
+        for s in [".rela.text", ".text", "text", ".data", ".rela.data"] {
+            assert_eq!(table.get(table.offset(s).unwrap()), Some(s));
+        }
+    }
+
+    #[test]
+    fn offsets_are_kept() {
+        let mut table = StringTable::from_bytes(b"\0.text\0.data\0");
+        assert_eq!(table.offset(".data"), Some(7));
+        let offset = table.add(".bss");
+        assert_eq!(offset, 13);
+        assert_eq!(table.add(".text"), 1);
+        assert_eq!(table.to_le(), b"\0.text\0.data\0.bss\0".to_vec());
+    }
+
+    #[test]
+    fn rebuild_shstrtab() {
+        let mut elf = crate::from_file("../test/testBin").unwrap();
+        let names: Vec<String> = elf.shdrs.iter().map(|s| s.name.clone()).collect();
+        let size = elf.section_header(".shstrtab").unwrap().size;
+        elf.rebuild_shstrtab().unwrap();
+
+        let elf = crate::Elf::parse(elf.to_le()).unwrap();
+        assert_eq!(elf.shdrs.iter().map(|s| s.name.clone()).collect::<Vec<String>>(), names);
+        assert!(elf.section_header(".shstrtab").unwrap().size <= size);
+    }
+}
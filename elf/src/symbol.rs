@@ -0,0 +1,206 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::Elf_class;
+use crate::shdr;
+use crate::dynamic::Dyn_tag;
+
+use byteorder::*;
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Sym_type {
+    NOTYPE = 0,
+    OBJECT = 1,
+    FUNC = 2,
+    SECTION = 3,
+    FILE = 4,
+    COMMON = 5,
+    TLS = 6,
+    GNU_IFUNC = 10,
+    OTHER
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Sym_bind {
+    LOCAL = 0,
+    GLOBAL = 1,
+    WEAK = 2,
+    GNU_UNIQUE = 10,
+    OTHER
+}
+
+#[derive(Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub name_offset: u32,
+    pub value: u64,
+    pub size: u64,
+    pub info: u8,
+    pub other: u8,
+    pub shndx: u16
+}
+
+impl Symbol {
+
+    pub fn parse(sym: &[u8], strtab: &[u8], class: Elf_class) -> Result<Symbol> {
+        let name_offset = LittleEndian::read_u32(&sym[0x0..0x4]);
+        let name = match strtab.get(name_offset as usize..) {
+            Some(s) => shdr::name_from_u8_nul(s),
+            None => String::new()
+        };
+
+        return match class {
+            Elf_class::ELF64 => Ok(Symbol {
+                name,
+                name_offset,
+                info: sym[0x4],
+                other: sym[0x5],
+                shndx: LittleEndian::read_u16(&sym[0x6..0x8]),
+                value: LittleEndian::read_u64(&sym[0x8..0x10]),
+                size: LittleEndian::read_u64(&sym[0x10..0x18]),
+            }),
+            Elf_class::ELF32 => Ok(Symbol {
+                name,
+                name_offset,
+                value: LittleEndian::read_u32(&sym[0x4..0x8]) as u64,
+                size: LittleEndian::read_u32(&sym[0x8..0xC]) as u64,
+                info: sym[0xC],
+                other: sym[0xD],
+                shndx: LittleEndian::read_u16(&sym[0xE..0x10]),
+            })
+        }
+    }
+
+    pub fn to_le(&self, class: Elf_class) -> Vec<u8> {
+        let mut bin = vec![];
+
+        match class {
+            Elf_class::ELF64 => {
+                bin.extend_from_slice(&self.name_offset.to_le_bytes());
+                bin.push(self.info);
+                bin.push(self.other);
+                bin.extend_from_slice(&self.shndx.to_le_bytes());
+                bin.extend_from_slice(&self.value.to_le_bytes());
+                bin.extend_from_slice(&self.size.to_le_bytes());
+            },
+            Elf_class::ELF32 => {
+                bin.extend_from_slice(&self.name_offset.to_le_bytes());
+                bin.extend_from_slice(&(self.value as u32).to_le_bytes());
+                bin.extend_from_slice(&(self.size as u32).to_le_bytes());
+                bin.push(self.info);
+                bin.push(self.other);
+                bin.extend_from_slice(&self.shndx.to_le_bytes());
+            }
+        }
+
+        return bin;
+    }
+
+    pub fn sym_type(&self) -> Sym_type {
+        return match self.info & 0xf {
+            0 => Sym_type::NOTYPE,
+            1 => Sym_type::OBJECT,
+            2 => Sym_type::FUNC,
+            3 => Sym_type::SECTION,
+            4 => Sym_type::FILE,
+            5 => Sym_type::COMMON,
+            6 => Sym_type::TLS,
+            10 => Sym_type::GNU_IFUNC,
+            _ => Sym_type::OTHER
+        }
+    }
+
+    pub fn bind(&self) -> Sym_bind {
+        return match self.info >> 4 {
+            0 => Sym_bind::LOCAL,
+            1 => Sym_bind::GLOBAL,
+            2 => Sym_bind::WEAK,
+            10 => Sym_bind::GNU_UNIQUE,
+            _ => Sym_bind::OTHER
+        }
+    }
+
+    // an undefined symbol is one that has to be resolved by the loader
+    pub fn is_undefined(&self) -> bool {
+        return self.shndx == 0;
+    }
+}
+
+// section indices from here on are special values (SHN_ABS, SHN_COMMON...)
+pub const SHN_LORESERVE: u16 = 0xff00;
+
+pub fn entry_size(class: Elf_class) -> usize {
+    return match class {
+        Elf_class::ELF64 => 0x18,
+        Elf_class::ELF32 => 0x10
+    }
+}
+
+pub fn parse_symbols(bin: &[u8], strtab: &[u8], class: Elf_class) -> Result<Vec<Symbol>> {
+    let mut syms = vec![];
+
+    for entry in bin.chunks_exact(entry_size(class)) {
+        syms.push(Symbol::parse(entry, strtab, class)?);
+    }
+
+    return Ok(syms);
+}
+
+impl Elf {
+    // parse the symbols of a SYMTAB or DYNSYM section, using its linked string table
+    pub fn section_symbols(&self, shdr: &shdr::SectionHeader) -> Result<Vec<Symbol>> {
+        let strtab = self.shdrs.get(shdr.link as usize).ok_or(ParsingError::ParsingError)?;
+        let str_bin = self.raw.get(strtab.offset as usize..(strtab.offset+strtab.size) as usize).ok_or(ParsingError::ParsingError)?;
+        let sym_bin = self.raw.get(shdr.offset as usize..(shdr.offset+shdr.size) as usize).ok_or(ParsingError::ParsingError)?;
+
+        return parse_symbols(&sym_bin, &str_bin, self.header.e_class);
+    }
+
+    // The static symbol table (.symtab), empty if the binary is stripped. The
+    // symbols of an attached debug file are merged in.
+    pub fn symbols(&self) -> Result<Vec<Symbol>> {
+        let mut syms = match self.shdrs.iter().find(|s| if let shdr::Shdr_type::SYMTAB = s.sh_type { true } else { false }) {
+            Some(shdr) => self.section_symbols(shdr)?,
+            None => vec![]
+        };
+
+        if let Some(debug) = &self.debug {
+            let mut known: HashSet<(String, u64)> = syms.iter().map(|s| (s.name.clone(), s.value)).collect();
+            for sym in debug.symbols()? {
+                if known.insert((sym.name.clone(), sym.value)) {
+                    syms.push(sym);
+                }
+            }
+        }
+
+        return Ok(syms);
+    }
+
+    // the dynamic symbol table (.dynsym)
+    pub fn dynamic_symbols(&self) -> Result<Vec<Symbol>> {
+        return match self.shdrs.iter().find(|s| if let shdr::Shdr_type::DYNSYM = s.sh_type { true } else { false }) {
+            Some(shdr) => self.section_symbols(shdr),
+            None => Ok(vec![])
+        }
+    }
+
+    // look up a single dynamic symbol by index. When the section headers are
+    // missing we fall back on DT_SYMTAB and DT_STRTAB, which have no size, so
+    // the table can only be indexed and not iterated.
+    pub fn dynamic_symbol(&self, index: u32) -> Option<Symbol> {
+        if let Some(shdr) = self.shdrs.iter().find(|s| if let shdr::Shdr_type::DYNSYM = s.sh_type { true } else { false }) {
+            return self.section_symbols(shdr).ok()?.get(index as usize).cloned();
+        }
+
+        let class = self.header.e_class;
+        let symtab = self.vaddr_to_offset(self.dynamic_value(Dyn_tag::SYMTAB)?)? as usize;
+        let strtab = self.vaddr_to_offset(self.dynamic_value(Dyn_tag::STRTAB)?)? as usize;
+        let strsz = self.dynamic_value(Dyn_tag::STRSZ)? as usize;
+        let start = symtab + index as usize * entry_size(class);
+
+        let sym_bin = self.raw.get(start..start + entry_size(class))?;
+        let str_bin = self.raw.get(strtab..strtab + strsz)?;
+        return Symbol::parse(&sym_bin, &str_bin, class).ok();
+    }
+}
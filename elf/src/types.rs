@@ -0,0 +1,512 @@
+use crate::Result;
+use crate::Elf;
+use crate::dwarf::*;
+
+use std::fmt;
+
+// Types described by .debug_info: C style type names, the layout of
+// structs and unions (like pahole) and function signatures.
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: Option<String>,
+    pub type_name: String,
+    // the C declaration of the member, e.g. "void (*cb)(int)"
+    pub decl: String,
+    // offset and size in bytes, bitfields also have their position in bits
+    pub offset: u64,
+    pub size: u64,
+    pub bit_offset: Option<u64>,
+    pub bit_size: Option<u64>
+}
+
+impl Member {
+    // the first bit and the number of bits the member covers
+    fn bits(&self) -> (u64, u64) {
+        return match (self.bit_offset, self.bit_size) {
+            (Some(offset), Some(size)) => (offset, size),
+            _ => (self.offset * 8, self.size * 8)
+        }
+    }
+}
+
+// Unused space between two members, `bits` is set for a gap that is not a
+// whole number of bytes, next to a bitfield.
+#[derive(Clone, Debug)]
+pub struct Hole {
+    // the member the hole follows
+    pub after: usize,
+    pub offset: u64,
+    pub size: u64,
+    pub bits: u64
+}
+
+#[derive(Clone, Debug)]
+pub struct StructLayout {
+    // "struct", "union" or "class"
+    pub kind: &'static str,
+    pub name: String,
+    pub size: u64,
+    pub members: Vec<Member>,
+    pub holes: Vec<Hole>,
+    // unused bytes at the end
+    pub padding: u64,
+    // the offset of the type in .debug_info
+    pub die: u64
+}
+
+#[derive(Clone, Debug)]
+pub struct Parameter {
+    pub name: Option<String>,
+    pub type_name: String,
+    pub decl: String
+}
+
+#[derive(Clone, Debug)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub linkage_name: Option<String>,
+    pub return_type: String,
+    pub params: Vec<Parameter>,
+    pub variadic: bool,
+    // where the function is, for definitions
+    pub low_pc: Option<u64>
+}
+
+// DWARF 2 and 3 counted DW_AT_bit_offset from the most significant bit of
+// the storage unit, on little endian that is the far end.
+fn legacy_bit_offset(offset: u64, storage: u64, bit_offset: u64, bit_size: u64) -> u64 {
+    return offset * 8 + (storage * 8).saturating_sub(bit_offset + bit_size);
+}
+
+// DW_AT_data_member_location is a constant, or for old producers an
+// expression of just DW_OP_plus_uconst
+fn member_location(value: &AttrValue) -> Option<u64> {
+    return match value {
+        AttrValue::Block(expr) if expr.first() == Some(&0x23) => {
+            let mut r = Reader::new(expr, 1);
+            r.uleb()
+        },
+        other => other.udata()
+    }
+}
+
+impl Dwarf {
+    fn type_die(&self, die: &Die) -> Option<(&Unit, &Die)> {
+        return match die.attr(DW_AT_type) {
+            Some(AttrValue::Ref(offset)) => self.die(*offset),
+            _ => None
+        }
+    }
+
+    // The size in bytes of the type at `offset`. Typedefs and qualifiers
+    // are looked through, arrays multiply their element size.
+    pub fn type_size(&self, offset: u64) -> Option<u64> {
+        return self.size_of(offset, 0);
+    }
+
+    fn size_of(&self, offset: u64, depth: usize) -> Option<u64> {
+        if depth > 32 {
+            return None;
+        }
+        let (unit, die) = self.die(offset)?;
+
+        if let Some(size) = die.attr(DW_AT_byte_size).and_then(|s| s.udata()) {
+            return Some(size);
+        }
+
+        return match die.tag {
+            DW_TAG_pointer_type | DW_TAG_reference_type | DW_TAG_rvalue_reference_type | DW_TAG_ptr_to_member_type => Some(unit.encoding.address_size as u64),
+            DW_TAG_typedef | DW_TAG_const_type | DW_TAG_volatile_type | DW_TAG_restrict_type | DW_TAG_atomic_type => {
+                self.size_of(self.type_die(die)?.1.offset, depth + 1)
+            },
+            DW_TAG_array_type => {
+                let elem = self.size_of(self.type_die(die)?.1.offset, depth + 1)?;
+                let mut count = 1;
+                for dim in self.array_dims(unit, die) {
+                    count *= dim?;
+                }
+                Some(elem * count)
+            },
+            _ => None
+        }
+    }
+
+    // the number of elements of every dimension of an array, None when unknown
+    fn array_dims(&self, unit: &Unit, die: &Die) -> Vec<Option<u64>> {
+        let mut dims = vec![];
+
+        for child in &die.children {
+            let sub = &unit.dies[*child];
+            if sub.tag != DW_TAG_subrange_type {
+                continue;
+            }
+            let count = match (sub.attr(DW_AT_count), sub.attr(DW_AT_upper_bound)) {
+                (Some(count), _) => count.udata(),
+                (None, Some(upper)) => {
+                    let lower = sub.attr(DW_AT_lower_bound).and_then(|l| l.udata()).unwrap_or(0);
+                    match upper {
+                        // a flexible array member has an upper bound of -1
+                        AttrValue::Sdata(-1) => Some(0),
+                        AttrValue::Udata(u64::MAX) => Some(0),
+                        _ => upper.udata().map(|u| u + 1 - lower)
+                    }
+                },
+                _ => None
+            };
+            dims.push(count);
+        }
+
+        return dims;
+    }
+
+    // the C spelling of the type at `offset`, e.g. "const char *" or "int (*)(void *)"
+    pub fn type_name(&self, offset: u64) -> String {
+        return self.declaration(Some(offset), String::new(), 0);
+    }
+
+    // the C spelling of a type attribute, a missing type is void
+    fn attr_type_name(&self, die: &Die) -> String {
+        return self.attr_declaration(die, "");
+    }
+
+    // declare `name` with the type of an entry
+    fn attr_declaration(&self, die: &Die, name: &str) -> String {
+        return match die.attr(DW_AT_type) {
+            Some(AttrValue::Ref(offset)) => self.declaration(Some(*offset), String::from(name), 0),
+            Some(_) => format!("? {}", name).trim_end().to_string(),
+            None => format!("void {}", name).trim_end().to_string()
+        }
+    }
+
+    // Build a C declarator inside out: `inner` is what has been built so
+    // far, e.g. "*" for a pointer, and the type wraps around it.
+    fn declaration(&self, offset: Option<u64>, inner: String, depth: usize) -> String {
+        let join = |base: &str, inner: &str| if inner.is_empty() { String::from(base) } else if inner.starts_with('[') { format!("{}{}", base, inner) } else { format!("{} {}", base, inner) };
+        let wrap = |inner: String| if inner.starts_with('*') || inner.starts_with('&') { format!("({})", inner) } else { inner };
+
+        let offset = match offset {
+            Some(offset) => offset,
+            None => return join("void", &inner)
+        };
+        let (unit, die) = match self.die(offset) {
+            Some(found) if depth < 32 => found,
+            _ => return join("?", &inner)
+        };
+        let target = match die.attr(DW_AT_type) {
+            Some(AttrValue::Ref(target)) => Some(*target),
+            _ => None
+        };
+        let name = self.die_name(die);
+
+        return match die.tag {
+            DW_TAG_pointer_type => self.declaration(target, format!("*{}", inner), depth + 1),
+            DW_TAG_reference_type => self.declaration(target, format!("&{}", inner), depth + 1),
+            DW_TAG_rvalue_reference_type => self.declaration(target, format!("&&{}", inner), depth + 1),
+            DW_TAG_const_type | DW_TAG_volatile_type | DW_TAG_restrict_type | DW_TAG_atomic_type => {
+                let qualifier = match die.tag {
+                    DW_TAG_const_type => "const",
+                    DW_TAG_volatile_type => "volatile",
+                    DW_TAG_restrict_type => "restrict",
+                    _ => "_Atomic"
+                };
+                // a qualified pointer puts the qualifier after the star, a
+                // qualified array is spelled on its elements, which carry it as well
+                let target_tag = target.and_then(|t| self.die(t)).map(|(_, t)| t.tag);
+                if target_tag == Some(DW_TAG_array_type) {
+                    self.declaration(target, inner, depth + 1)
+                } else if target_tag == Some(DW_TAG_pointer_type) {
+                    self.declaration(target, join(qualifier, &inner), depth + 1)
+                } else {
+                    format!("{} {}", qualifier, self.declaration(target, inner, depth + 1))
+                }
+            },
+            DW_TAG_array_type => {
+                let dims: String = self.array_dims(unit, die).iter().map(|d| match d {
+                    Some(count) => format!("[{}]", count),
+                    None => String::from("[]")
+                }).collect();
+                self.declaration(target, format!("{}{}", wrap(inner), dims), depth + 1)
+            },
+            DW_TAG_subroutine_type => {
+                let mut params = vec![];
+                for child in &die.children {
+                    match unit.dies[*child].tag {
+                        DW_TAG_formal_parameter => params.push(self.attr_type_name(&unit.dies[*child])),
+                        DW_TAG_unspecified_parameters => params.push(String::from("...")),
+                        _ => {}
+                    }
+                }
+                if params.is_empty() {
+                    params.push(String::from("void"));
+                }
+                self.declaration(target, format!("{}({})", wrap(inner), params.join(", ")), depth + 1)
+            },
+            DW_TAG_structure_type | DW_TAG_union_type | DW_TAG_class_type | DW_TAG_enumeration_type => {
+                let kind = match die.tag {
+                    DW_TAG_structure_type => "struct",
+                    DW_TAG_union_type => "union",
+                    DW_TAG_class_type => "class",
+                    _ => "enum"
+                };
+                join(&format!("{} {}", kind, name.unwrap_or(String::from("{...}"))), &inner)
+            },
+            DW_TAG_ptr_to_member_type => self.declaration(target, format!("::*{}", inner), depth + 1),
+            _ => join(&name.unwrap_or(String::from("?")), &inner)
+        }
+    }
+
+    fn member(&self, die: &Die) -> Member {
+        let type_offset = match die.attr(DW_AT_type) {
+            Some(AttrValue::Ref(offset)) => Some(*offset),
+            _ => None
+        };
+        let size = type_offset.and_then(|t| self.type_size(t)).unwrap_or(0);
+        let offset = die.attr(DW_AT_data_member_location).and_then(|l| member_location(l)).unwrap_or(0);
+        let bit_size = die.attr(DW_AT_bit_size).and_then(|b| b.udata());
+
+        let bit_offset = match (bit_size, die.attr(DW_AT_data_bit_offset), die.attr(DW_AT_bit_offset)) {
+            (Some(_), Some(data_bit_offset), _) => data_bit_offset.udata(),
+            (Some(bits), None, Some(bit_offset)) => {
+                let storage = die.attr(DW_AT_byte_size).and_then(|s| s.udata()).unwrap_or(size);
+                Some(legacy_bit_offset(offset, storage, bit_offset.udata().unwrap_or(0), bits))
+            },
+            (Some(_), None, None) => Some(offset * 8),
+            _ => None
+        };
+
+        Member {
+            name: die.name().map(|n| String::from(n)),
+            type_name: self.attr_type_name(die),
+            decl: self.attr_declaration(die, die.name().unwrap_or("")),
+            offset: bit_offset.map(|b| b / 8).unwrap_or(offset),
+            size,
+            bit_offset,
+            bit_size
+        }
+    }
+
+    // The layout of the struct, union or class at `offset`, None for
+    // declarations without members.
+    pub fn struct_layout(&self, offset: u64) -> Option<StructLayout> {
+        let (unit, die) = self.die(offset)?;
+        let kind = match die.tag {
+            DW_TAG_structure_type => "struct",
+            DW_TAG_union_type => "union",
+            DW_TAG_class_type => "class",
+            _ => return None
+        };
+        if die.attr(DW_AT_declaration).is_some() {
+            return None;
+        }
+
+        let size = die.attr(DW_AT_byte_size).and_then(|s| s.udata()).unwrap_or(0);
+        let members: Vec<Member> = die.children.iter().map(|c| &unit.dies[*c]).filter(|c| (c.tag == DW_TAG_member || c.tag == DW_TAG_inheritance) && c.attr(DW_AT_external).is_none() && c.attr(DW_AT_declaration).is_none()).map(|c| self.member(c)).collect();
+
+        // walk the members in bits, a gap between them is a hole
+        let mut holes = vec![];
+        let mut end = 0;
+        for (i, member) in members.iter().enumerate() {
+            let (start, bits) = member.bits();
+            if kind != "union" && i > 0 && start > end {
+                let gap = start - end;
+                holes.push(Hole {
+                    after: i - 1,
+                    offset: (end + 7) / 8,
+                    size: gap / 8,
+                    bits: gap % 8
+                });
+            }
+            end = end.max(start + bits);
+        }
+
+        Some(StructLayout {
+            kind,
+            name: self.die_name(die).unwrap_or(String::new()),
+            size,
+            padding: (size * 8).saturating_sub(end) / 8,
+            members,
+            holes,
+            die: offset
+        })
+    }
+
+    // All struct, union and class definitions. A type defined by several
+    // units is listed once.
+    pub fn struct_layouts(&self) -> Vec<StructLayout> {
+        let mut layouts: Vec<StructLayout> = vec![];
+
+        for unit in &self.units {
+            for die in &unit.dies {
+                if die.tag != DW_TAG_structure_type && die.tag != DW_TAG_union_type && die.tag != DW_TAG_class_type {
+                    continue;
+                }
+                if let Some(layout) = self.struct_layout(die.offset) {
+                    let duplicate = !layout.name.is_empty() && layouts.iter().any(|l| l.kind == layout.kind && l.name == layout.name && l.size == layout.size);
+                    if !duplicate {
+                        layouts.push(layout);
+                    }
+                }
+            }
+        }
+
+        return layouts;
+    }
+
+    // find a struct, union or class by name
+    pub fn find_struct(&self, name: &str) -> Option<StructLayout> {
+        return self.struct_layouts().into_iter().find(|l| l.name == name);
+    }
+
+    // The signature of the function at `offset`. Concrete out of line and
+    // inlined instances take their types from the abstract instance.
+    pub fn signature(&self, offset: u64) -> Option<FunctionSignature> {
+        let (unit, die) = self.die(offset)?;
+        if die.tag != DW_TAG_subprogram {
+            return None;
+        }
+
+        // the declaration carries the return type, the definition the parameter names
+        let mut decl = die;
+        for _ in 0..16 {
+            if decl.attr(DW_AT_type).is_some() || decl.attr(DW_AT_name).is_some() {
+                break;
+            }
+            decl = match decl.attr(DW_AT_abstract_origin).or(decl.attr(DW_AT_specification)) {
+                Some(AttrValue::Ref(target)) => self.die(*target)?.1,
+                _ => break
+            };
+        }
+        let spec = match decl.attr(DW_AT_specification) {
+            Some(AttrValue::Ref(target)) => self.die(*target).map(|(_, d)| d).unwrap_or(decl),
+            _ => decl
+        };
+        let return_type = if decl.attr(DW_AT_type).is_some() { self.attr_type_name(decl) } else { self.attr_type_name(spec) };
+
+        // parameters of concrete instances point to the abstract ones
+        let (param_unit, param_die) = if die.children.iter().any(|c| unit.dies[*c].tag == DW_TAG_formal_parameter) {
+            (unit, die)
+        } else {
+            self.die(decl.offset)?
+        };
+
+        let mut params = vec![];
+        let mut variadic = false;
+        for child in &param_die.children {
+            let child = &param_unit.dies[*child];
+            match child.tag {
+                DW_TAG_formal_parameter => {
+                    let origin = match child.attr(DW_AT_abstract_origin) {
+                        Some(AttrValue::Ref(target)) => self.die(*target).map(|(_, d)| d).unwrap_or(child),
+                        _ => child
+                    };
+                    // the implicit this pointer of C++ methods is listed too
+                    params.push(Parameter {
+                        name: origin.name().map(|n| String::from(n)),
+                        type_name: self.attr_type_name(origin),
+                        decl: self.attr_declaration(origin, origin.name().unwrap_or(""))
+                    });
+                },
+                DW_TAG_unspecified_parameters => variadic = true,
+                _ => {}
+            }
+        }
+
+        let low_pc = match die.attr(DW_AT_low_pc) {
+            Some(AttrValue::Addr(addr)) => Some(*addr),
+            _ => None
+        };
+
+        Some(FunctionSignature {
+            name: self.die_name(die)?,
+            linkage_name: self.linkage_name(die),
+            return_type,
+            params,
+            variadic,
+            low_pc
+        })
+    }
+
+    // The signatures of all functions, definitions first. A function that is
+    // declared and defined in several units is listed once.
+    pub fn functions(&self) -> Vec<FunctionSignature> {
+        let mut sigs: Vec<FunctionSignature> = vec![];
+
+        for unit in &self.units {
+            for die in &unit.dies {
+                if die.tag != DW_TAG_subprogram {
+                    continue;
+                }
+                if let Some(sig) = self.signature(die.offset) {
+                    match sigs.iter().position(|s| s.name == sig.name && s.linkage_name == sig.linkage_name) {
+                        Some(i) => if sigs[i].low_pc.is_none() && sig.low_pc.is_some() {
+                            sigs[i] = sig;
+                        },
+                        None => sigs.push(sig)
+                    }
+                }
+            }
+        }
+
+        sigs.sort_by_key(|s| s.low_pc.is_none());
+        return sigs;
+    }
+
+    pub fn find_function(&self, name: &str) -> Option<FunctionSignature> {
+        return self.functions().into_iter().find(|s| s.name == name || s.linkage_name.as_deref() == Some(name));
+    }
+}
+
+// the pahole style listing of a layout
+impl fmt::Display for StructLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {} {{", self.kind, self.name)?;
+
+        for (i, member) in self.members.iter().enumerate() {
+            match (member.bit_offset, member.bit_size) {
+                (Some(bit), Some(bits)) => writeln!(f, "\t{:<40} /* {:5}:{:2} {:5} */", format!("{}:{};", member.decl, bits), member.offset, bit % 8, member.size)?,
+                _ => writeln!(f, "\t{:<40} /* {:5} {:5} */", format!("{};", member.decl), member.offset, member.size)?
+            }
+
+            for hole in self.holes.iter().filter(|h| h.after == i) {
+                if hole.size > 0 {
+                    writeln!(f, "\n\t/* XXX {} bytes hole, try to pack */\n", hole.size)?;
+                }
+                if hole.bits > 0 {
+                    writeln!(f, "\n\t/* XXX {} bits hole, try to pack */\n", hole.bits)?;
+                }
+            }
+        }
+
+        writeln!(f, "\n\t/* size: {}, members: {} */", self.size, self.members.len())?;
+        if self.padding > 0 {
+            writeln!(f, "\t/* padding: {} */", self.padding)?;
+        }
+        return write!(f, "}};");
+    }
+}
+
+impl fmt::Display for FunctionSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut params: Vec<String> = self.params.iter().map(|p| p.decl.clone()).collect();
+        if self.variadic {
+            params.push(String::from("..."));
+        }
+        if params.is_empty() {
+            params.push(String::from("void"));
+        }
+
+        let sep = if self.return_type.ends_with('*') || self.return_type.ends_with('&') { "" } else { " " };
+        return write!(f, "{}{}{}({})", self.return_type, sep, self.name, params.join(", "));
+    }
+}
+
+impl Elf {
+    pub fn struct_layouts(&self) -> Result<Vec<StructLayout>> {
+        return Ok(self.dwarf()?.struct_layouts());
+    }
+
+    pub fn functions(&self) -> Result<Vec<FunctionSignature>> {
+        return Ok(self.dwarf()?.functions());
+    }
+}
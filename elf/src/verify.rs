@@ -0,0 +1,87 @@
+use crate::Result;
+use crate::ParsingError;
+use crate::Elf;
+use crate::shdr::Shdr_type;
+
+use std::fs;
+
+// Check that a file comes out of the parser and the writer unchanged. The
+// file is parsed, written again without edits and compared with the
+// original, the first byte that differs is reported with the structure it
+// belongs to. Run this on a file before trusting edits to it.
+//
+//     elf::verify::verify_file("/usr/bin/ls")?;
+
+pub fn verify(bin: &Vec<u8>) -> Result<()> {
+    let elf = Elf::parse(bin.clone())?;
+    let out = elf.to_le();
+
+    let at = match bin.iter().zip(out.iter()).position(|(a, b)| a != b) {
+        Some(at) => at,
+        None if bin.len() == out.len() => return Ok(()),
+        None => {
+            return Err(ParsingError::Refused(format!("the file was written back as {:#x} bytes instead of {:#x}", out.len(), bin.len())));
+        }
+    };
+
+    return Err(ParsingError::Refused(format!("byte {:#x} in {} was written back as {:#04x} instead of {:#04x}",
+        at, describe_offset(&elf, at as u64), out[at], bin[at])));
+}
+
+pub fn verify_file(path: &str) -> Result<()> {
+    let bin = fs::read(path)?;
+    return verify(&bin);
+}
+
+// the part of the file an offset is in, the headers before the content
+pub fn describe_offset(elf: &Elf, offset: u64) -> String {
+    let header = &elf.header;
+    if offset < header.size as u64 {
+        return String::from("the ELF header");
+    }
+
+    let table = header.phdr_offset;
+    if header.phdr_num != 0 && offset >= table && offset < table + header.phdr_num as u64 * header.phdr_size as u64 {
+        return format!("program header {}", (offset - table) / header.phdr_size as u64);
+    }
+
+    let table = header.shdr_offset;
+    if header.shdr_num != 0 && offset >= table && offset < table + header.shdr_num as u64 * header.shdr_size as u64 {
+        let index = ((offset - table) / header.shdr_size as u64) as usize;
+        let name = elf.shdrs.get(index).map(|s| s.name.clone()).unwrap_or(String::new());
+        return format!("section header {} ({})", index, name);
+    }
+
+    for (index, shdr) in elf.shdrs.iter().enumerate() {
+        if index != 0 && shdr.sh_type != Shdr_type::NOBITS && offset >= shdr.offset && offset < shdr.offset + shdr.size {
+            return format!("the content of section {} ({})", index, shdr.name);
+        }
+    }
+    for (index, phdr) in elf.phdrs.iter().enumerate() {
+        if offset >= phdr.offset && offset < phdr.offset + phdr.filesz {
+            return format!("the content of segment {}", index);
+        }
+    }
+    return String::from("content outside of any section or segment");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Elf;
+
+    #[test]
+    fn round_trip() {
+        let bin = std::fs::read("../test/testBin").unwrap();
+        assert!(Elf::parse(bin.clone()).unwrap().to_le() == bin);
+        crate::verify::verify_file("../test/testBin").unwrap();
+    }
+
+    #[test]
+    fn describe_offset() {
+        let elf = crate::from_file("../test/testBin").unwrap();
+        let text = elf.section_header(".text").unwrap();
+        assert_eq!(crate::verify::describe_offset(&elf, 4), "the ELF header");
+        assert_eq!(crate::verify::describe_offset(&elf, elf.header.phdr_offset + elf.header.phdr_size as u64), "program header 1");
+        assert_eq!(crate::verify::describe_offset(&elf, text.offset + 1), "the content of section 16 (.text)");
+    }
+}
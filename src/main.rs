@@ -2,11 +2,24 @@
 use std::result::*;
 
 fn main() {
+    // `verify <file>` checks that the file is written back byte for byte
+    let args: Vec<String> = std::env::args().collect(); 
+    if args.len() == 3 && args[1] == "verify" {
+        match elf::verify::verify_file(&args[2]) {
+            Ok(_) => println!("{}: ok", args[2]),
+            Err(e) => {
+                println!("{}: {}", args[2], e); 
+                std::process::exit(1); 
+            }
+        }
+        return; 
+    }
 
     let mut elf = elf::from_file("test/testBin").expect("Failed to open file"); 
 
     // API 
     // elf.inject( ELF )
+    // elf.hook( "puts", Hook::Code( shim ) )
     // elf.strip()
     // elf.obfuscate()
     // elf.encrypt( key )